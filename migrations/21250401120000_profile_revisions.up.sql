@@ -0,0 +1,17 @@
+-- Add up migration script here
+CREATE EXTENSION IF NOT EXISTS "uuid-ossp";
+
+CREATE TABLE IF NOT EXISTS profile_revisions (
+  id UUID PRIMARY KEY NOT NULL DEFAULT uuid_generate_v4 (),
+  profile_id UUID NOT NULL REFERENCES profiles (id) ON DELETE CASCADE,
+  viewer_id UUID REFERENCES viewers (id) ON DELETE SET NULL,
+  changes JSONB NOT NULL,
+  snapshot JSONB NOT NULL,
+  rollback_of UUID REFERENCES profile_revisions (id) ON DELETE SET NULL,
+  version SMALLINT NOT NULL DEFAULT 1,
+  created_at TIMESTAMP
+  WITH
+    TIME ZONE NOT NULL DEFAULT NOW ()
+);
+
+CREATE INDEX idx_profile_revisions_profile_id ON profile_revisions (profile_id, created_at);
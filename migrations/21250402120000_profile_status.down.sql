@@ -0,0 +1,16 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS profile_status_changes;
+
+ALTER TABLE profiles
+ADD COLUMN accepted BOOLEAN NOT NULL DEFAULT FALSE;
+
+UPDATE profiles
+SET
+  accepted = status = 'approved';
+
+DROP INDEX IF EXISTS idx_profiles_status;
+
+ALTER TABLE profiles
+DROP COLUMN status,
+DROP COLUMN status_reason,
+DROP COLUMN status_changed_at;
@@ -0,0 +1,37 @@
+-- Add up migration script here
+CREATE EXTENSION IF NOT EXISTS "uuid-ossp";
+
+ALTER TABLE profiles
+ADD COLUMN status VARCHAR(20) NOT NULL DEFAULT 'pending' CHECK (
+  status IN ('draft', 'pending', 'approved', 'rejected', 'suspended')
+),
+ADD COLUMN status_reason VARCHAR(500),
+ADD COLUMN status_changed_at TIMESTAMP
+WITH
+  TIME ZONE NOT NULL DEFAULT NOW ();
+
+UPDATE profiles
+SET
+  status = CASE
+    WHEN accepted THEN 'approved'
+    ELSE 'pending'
+  END;
+
+ALTER TABLE profiles
+DROP COLUMN accepted;
+
+CREATE INDEX idx_profiles_status ON profiles (status);
+
+CREATE TABLE IF NOT EXISTS profile_status_changes (
+  id UUID PRIMARY KEY NOT NULL DEFAULT (uuid_generate_v4 ()),
+  profile_id UUID NOT NULL REFERENCES profiles (id) ON DELETE CASCADE,
+  viewer_id UUID REFERENCES viewers (id) ON DELETE SET NULL,
+  from_status VARCHAR(20) NOT NULL,
+  to_status VARCHAR(20) NOT NULL,
+  reason VARCHAR(500),
+  created_at TIMESTAMP
+  WITH
+    TIME ZONE NOT NULL DEFAULT NOW ()
+);
+
+CREATE INDEX idx_profile_status_changes_profile_id ON profile_status_changes (profile_id, created_at);
@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS handwerkskarten_verifications;
+DROP TABLE IF EXISTS handwerkskammern;
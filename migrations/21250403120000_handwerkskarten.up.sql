@@ -0,0 +1,41 @@
+-- Add up migration script here
+CREATE EXTENSION IF NOT EXISTS "uuid-ossp";
+
+-- number_pattern is a POSIX regex the Handwerkskarte number has to match
+CREATE TABLE IF NOT EXISTS handwerkskammern (
+    id UUID PRIMARY KEY NOT NULL DEFAULT uuid_generate_v4(),
+    name VARCHAR(100) NOT NULL UNIQUE,
+    number_pattern VARCHAR(200) NOT NULL,
+    example VARCHAR(100) NOT NULL,
+    version SMALLINT NOT NULL DEFAULT 1,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+INSERT INTO handwerkskammern (name, number_pattern, example) VALUES ('Handwerkskammer Berlin', '^[0-9]{5,7}$', '1234567');
+INSERT INTO handwerkskammern (name, number_pattern, example) VALUES ('Handwerkskammer Hamburg', '^[0-9]{6}$', '123456');
+INSERT INTO handwerkskammern (name, number_pattern, example) VALUES ('Handwerkskammer für München und Oberbayern', '^[0-9]{5,8}$', '12345678');
+INSERT INTO handwerkskammern (name, number_pattern, example) VALUES ('Handwerkskammer zu Köln', '^[0-9]{5,7}$', '123456');
+INSERT INTO handwerkskammern (name, number_pattern, example) VALUES ('Handwerkskammer Frankfurt-Rhein-Main', '^[0-9]{4,6}/[0-9]{2}$', '12345/01');
+INSERT INTO handwerkskammern (name, number_pattern, example) VALUES ('Handwerkskammer Region Stuttgart', '^[A-Z]?[0-9]{5,7}$', 'S123456');
+
+CREATE TABLE IF NOT EXISTS handwerkskarten_verifications (
+    id UUID PRIMARY KEY NOT NULL DEFAULT uuid_generate_v4(),
+    profile_id UUID NOT NULL REFERENCES profiles (id) ON DELETE CASCADE,
+    handwerkskammer_id UUID NOT NULL REFERENCES handwerkskammern (id) ON DELETE RESTRICT,
+    nummer VARCHAR(100) NOT NULL,
+    scan_data BYTEA NOT NULL,
+    scan_content_type VARCHAR(100) NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'verified', 'rejected')),
+    reason VARCHAR(500),
+    reviewed_by UUID REFERENCES viewers (id) ON DELETE SET NULL,
+    reviewed_at TIMESTAMP WITH TIME ZONE,
+    version SMALLINT NOT NULL DEFAULT 1,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_handwerkskarten_verifications_profile_id ON handwerkskarten_verifications (profile_id, created_at);
+
+CREATE INDEX idx_handwerkskarten_verifications_pending ON handwerkskarten_verifications (created_at)
+WHERE status = 'pending';
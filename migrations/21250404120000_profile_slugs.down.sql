@@ -0,0 +1,7 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS profile_slug_redirects;
+
+DROP INDEX IF EXISTS idx_profiles_slug;
+
+ALTER TABLE profiles
+DROP COLUMN slug;
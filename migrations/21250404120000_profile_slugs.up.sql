@@ -0,0 +1,102 @@
+-- Add up migration script here
+CREATE EXTENSION IF NOT EXISTS "uuid-ossp";
+
+ALTER TABLE profiles
+ADD COLUMN slug VARCHAR(100);
+
+-- Same transliteration as utils::slugify for the existing rows
+WITH
+  bases AS (
+    SELECT
+      id,
+      COALESCE(
+        NULLIF(
+          LEFT(
+            TRIM(
+              BOTH '-'
+              FROM
+                REGEXP_REPLACE(
+                  TRANSLATE(
+                    REPLACE(
+                      REPLACE(
+                        REPLACE(
+                          REPLACE(
+                            REPLACE(
+                              REPLACE(
+                                REPLACE(LOWER(name || ' ' || location), 'ä', 'ae'),
+                                'ö',
+                                'oe'
+                              ),
+                              'ü',
+                              'ue'
+                            ),
+                            'ß',
+                            'ss'
+                          ),
+                          'æ',
+                          'ae'
+                        ),
+                        'œ',
+                        'oe'
+                      ),
+                      'ẞ',
+                      'ss'
+                    ),
+                    'àáâãåçèéêëìíîïñòóôõøùúûýÿ',
+                    'aaaaaceeeeiiiinooooouuuyy'
+                  ),
+                  '[^a-z0-9]+',
+                  '-',
+                  'g'
+                )
+            ),
+            80
+          ),
+          ''
+        ),
+        'profil'
+      ) AS base,
+      created_at
+    FROM
+      profiles
+  ),
+  numbered AS (
+    SELECT
+      id,
+      TRIM(BOTH '-' FROM base) AS base,
+      ROW_NUMBER() OVER (
+        PARTITION BY
+          TRIM(BOTH '-' FROM base)
+        ORDER BY
+          created_at,
+          id
+      ) AS n
+    FROM
+      bases
+  )
+UPDATE profiles p
+SET
+  slug = CASE
+    WHEN numbered.n = 1 THEN numbered.base
+    ELSE numbered.base || '-' || numbered.n
+  END
+FROM
+  numbered
+WHERE
+  p.id = numbered.id;
+
+ALTER TABLE profiles
+ALTER COLUMN slug SET NOT NULL;
+
+CREATE UNIQUE INDEX idx_profiles_slug ON profiles (slug);
+
+-- Slugs a profile had before it was renamed
+CREATE TABLE IF NOT EXISTS profile_slug_redirects (
+  slug VARCHAR(100) PRIMARY KEY NOT NULL,
+  profile_id UUID NOT NULL REFERENCES profiles (id) ON DELETE CASCADE,
+  created_at TIMESTAMP
+  WITH
+    TIME ZONE NOT NULL DEFAULT NOW ()
+);
+
+CREATE INDEX idx_profile_slug_redirects_profile_id ON profile_slug_redirects (profile_id);
@@ -0,0 +1,10 @@
+-- Add down migration script here
+ALTER TABLE profiles
+ADD COLUMN craft_id UUID REFERENCES crafts (id) ON DELETE SET NULL;
+
+UPDATE profiles p
+SET craft_id = pc.craft_id
+FROM profile_craft pc
+WHERE pc.profile_id = p.id AND pc.is_primary;
+
+DROP TABLE IF EXISTS profile_craft;
@@ -0,0 +1,24 @@
+-- Add up migration script here
+CREATE EXTENSION IF NOT EXISTS "uuid-ossp";
+
+CREATE TABLE IF NOT EXISTS profile_craft (
+    profile_id UUID NOT NULL REFERENCES profiles(id) ON DELETE CASCADE,
+    craft_id UUID NOT NULL REFERENCES crafts(id) ON DELETE CASCADE,
+    is_primary BOOLEAN NOT NULL DEFAULT FALSE,
+    PRIMARY KEY (profile_id, craft_id),
+    version SMALLINT NOT NULL DEFAULT 1,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+-- At most one primary craft per profile
+CREATE UNIQUE INDEX idx_profile_craft_primary ON profile_craft (profile_id)
+WHERE is_primary;
+
+CREATE INDEX idx_profile_craft_craft_id ON profile_craft (craft_id);
+
+INSERT INTO profile_craft (profile_id, craft_id, is_primary)
+SELECT id, craft_id, TRUE FROM profiles WHERE craft_id IS NOT NULL;
+
+ALTER TABLE profiles
+DROP COLUMN craft_id;
@@ -0,0 +1,5 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS profile_service_areas;
+
+ALTER TABLE profiles
+DROP COLUMN service_radius_km;
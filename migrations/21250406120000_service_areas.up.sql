@@ -0,0 +1,21 @@
+-- Add up migration script here
+CREATE EXTENSION IF NOT EXISTS "uuid-ossp";
+
+ALTER TABLE profiles
+ADD COLUMN service_radius_km SMALLINT CHECK (
+  service_radius_km > 0
+  AND service_radius_km <= 500
+);
+
+-- Postcodes (or postcode prefixes like '904') and districts a profile serves
+CREATE TABLE IF NOT EXISTS profile_service_areas (
+    profile_id UUID NOT NULL REFERENCES profiles(id) ON DELETE CASCADE,
+    kind VARCHAR(20) NOT NULL CHECK (kind IN ('plz', 'district')),
+    value VARCHAR(100) NOT NULL,
+    PRIMARY KEY (profile_id, kind, value),
+    version SMALLINT NOT NULL DEFAULT 1,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_profile_service_areas_value ON profile_service_areas (kind, value);
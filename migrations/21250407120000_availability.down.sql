@@ -0,0 +1,10 @@
+-- Add down migration script here
+DROP FUNCTION IF EXISTS profile_next_available_date(UUID);
+
+DROP TABLE IF EXISTS profile_closures;
+
+DROP TABLE IF EXISTS profile_opening_hours;
+
+ALTER TABLE profiles
+DROP COLUMN available_from,
+DROP COLUMN availability_status;
@@ -0,0 +1,66 @@
+-- Add up migration script here
+CREATE EXTENSION IF NOT EXISTS "uuid-ossp";
+
+ALTER TABLE profiles
+ADD COLUMN availability_status VARCHAR(20) NOT NULL DEFAULT 'available' CHECK (
+  availability_status IN ('available', 'limited', 'booked', 'paused')
+),
+ADD COLUMN available_from DATE;
+
+-- Weekly opening hours, weekday as ISO day (1 = Monday .. 7 = Sunday)
+CREATE TABLE IF NOT EXISTS profile_opening_hours (
+    id UUID PRIMARY KEY NOT NULL DEFAULT (uuid_generate_v4()),
+    profile_id UUID NOT NULL REFERENCES profiles(id) ON DELETE CASCADE,
+    weekday SMALLINT NOT NULL CHECK (weekday BETWEEN 1 AND 7),
+    opens_at TIME NOT NULL,
+    closes_at TIME NOT NULL CHECK (closes_at > opens_at),
+    version SMALLINT NOT NULL DEFAULT 1,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_profile_opening_hours_profile_id ON profile_opening_hours (profile_id, weekday);
+
+-- Holidays and other days on which the profile takes no jobs
+CREATE TABLE IF NOT EXISTS profile_closures (
+    id UUID PRIMARY KEY NOT NULL DEFAULT (uuid_generate_v4()),
+    profile_id UUID NOT NULL REFERENCES profiles(id) ON DELETE CASCADE,
+    starts_on DATE NOT NULL,
+    ends_on DATE NOT NULL CHECK (ends_on >= starts_on),
+    note VARCHAR(200),
+    version SMALLINT NOT NULL DEFAULT 1,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_profile_closures_profile_id ON profile_closures (profile_id, ends_on);
+
+-- First day within a year from today on which the profile takes jobs: not
+-- paused, past `available_from`, outside closures and, if opening hours are
+-- set, on a weekday with opening hours. Booked profiles without an
+-- `available_from` date have none.
+CREATE OR REPLACE FUNCTION profile_next_available_date(p_id UUID) RETURNS DATE AS $$
+    SELECT day::date
+    FROM profiles p,
+        generate_series(
+            GREATEST(CURRENT_DATE, COALESCE(p.available_from, CURRENT_DATE)),
+            CURRENT_DATE + 365,
+            INTERVAL '1 day'
+        ) AS day
+    WHERE p.id = p_id
+        AND p.availability_status <> 'paused'
+        AND (p.availability_status <> 'booked' OR p.available_from IS NOT NULL)
+        AND NOT EXISTS (
+            SELECT 1 FROM profile_closures c
+            WHERE c.profile_id = p.id AND day::date BETWEEN c.starts_on AND c.ends_on
+        )
+        AND (
+            NOT EXISTS (SELECT 1 FROM profile_opening_hours h WHERE h.profile_id = p.id)
+            OR EXISTS (
+                SELECT 1 FROM profile_opening_hours h
+                WHERE h.profile_id = p.id AND h.weekday = EXTRACT(ISODOW FROM day)
+            )
+        )
+    ORDER BY day
+    LIMIT 1
+$$ LANGUAGE sql STABLE;
@@ -0,0 +1,5 @@
+-- Add down migration script here
+ALTER TABLE profiles
+DROP COLUMN price_level;
+
+DROP TABLE IF EXISTS profile_services;
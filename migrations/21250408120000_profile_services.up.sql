@@ -0,0 +1,30 @@
+-- Add up migration script here
+CREATE EXTENSION IF NOT EXISTS "uuid-ossp";
+
+-- Service catalogue of a profile, each service belonging to a skill. Prices
+-- are in cents, per hour for hourly services.
+CREATE TABLE IF NOT EXISTS profile_services (
+    id UUID PRIMARY KEY NOT NULL DEFAULT (uuid_generate_v4()),
+    profile_id UUID NOT NULL REFERENCES profiles(id) ON DELETE CASCADE,
+    skill_id UUID NOT NULL REFERENCES skills(id) ON DELETE CASCADE,
+    name VARCHAR(100) NOT NULL,
+    price_model VARCHAR(20) NOT NULL CHECK (
+        price_model IN ('hourly', 'fixed', 'on_request')
+    ),
+    price_min_cents INTEGER CHECK (price_min_cents >= 0),
+    price_max_cents INTEGER CHECK (price_max_cents >= 0),
+    position SMALLINT NOT NULL DEFAULT 0,
+    version SMALLINT NOT NULL DEFAULT 1,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    CHECK (price_max_cents IS NULL OR price_min_cents IS NULL OR price_max_cents >= price_min_cents),
+    CHECK (price_model <> 'on_request' OR (price_min_cents IS NULL AND price_max_cents IS NULL))
+);
+
+CREATE INDEX idx_profile_services_profile_id ON profile_services (profile_id, position);
+
+-- 1 (€) to 3 (€€€), derived from the hourly services of the catalogue
+ALTER TABLE profiles
+ADD COLUMN price_level SMALLINT CHECK (price_level BETWEEN 1 AND 3);
+
+CREATE INDEX idx_profiles_price_level ON profiles (price_level);
@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP VIEW IF EXISTS profile_response_stats;
+
+DROP TABLE IF EXISTS profile_contacts;
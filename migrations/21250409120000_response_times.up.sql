@@ -0,0 +1,44 @@
+-- Add up migration script here
+CREATE EXTENSION IF NOT EXISTS "uuid-ossp";
+
+-- Every time a customer contacts a profile, e.g. through an inquiry, and
+-- when the profile first answered it
+CREATE TABLE IF NOT EXISTS profile_contacts (
+    id UUID PRIMARY KEY NOT NULL DEFAULT (uuid_generate_v4()),
+    profile_id UUID NOT NULL REFERENCES profiles(id) ON DELETE CASCADE,
+    viewer_id UUID REFERENCES viewers(id) ON DELETE SET NULL,
+    source VARCHAR(20) NOT NULL,
+    source_id UUID NOT NULL,
+    received_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    first_response_at TIMESTAMP WITH TIME ZONE,
+    version SMALLINT NOT NULL DEFAULT 1,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    UNIQUE (source, source_id)
+);
+
+CREATE INDEX idx_profile_contacts_profile_id ON profile_contacts (profile_id, received_at);
+
+-- Response behaviour over the last 90 days. Rate and median stay NULL below
+-- three contacts. Unanswered contacts count against the rate only once they
+-- are two days old.
+CREATE OR REPLACE VIEW profile_response_stats AS
+SELECT profile_id,
+    COUNT(*) AS contact_count,
+    CASE WHEN COUNT(*) >= 3 THEN
+        COUNT(*) FILTER (WHERE first_response_at IS NOT NULL)::DOUBLE PRECISION
+        / NULLIF(
+            COUNT(*) FILTER (
+                WHERE first_response_at IS NOT NULL OR received_at < NOW() - INTERVAL '2 days'
+            ),
+            0
+        )
+    END AS response_rate,
+    CASE WHEN COUNT(*) >= 3 THEN
+        percentile_cont(0.5) WITHIN GROUP (
+            ORDER BY EXTRACT(EPOCH FROM first_response_at - received_at)
+        )
+    END AS median_response_seconds
+FROM profile_contacts
+WHERE received_at >= NOW() - INTERVAL '90 days'
+GROUP BY profile_id;
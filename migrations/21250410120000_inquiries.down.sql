@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS inquiry_photos;
+
+DROP TABLE IF EXISTS inquiries;
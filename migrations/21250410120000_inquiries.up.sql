@@ -0,0 +1,39 @@
+-- Add up migration script here
+CREATE EXTENSION IF NOT EXISTS "uuid-ossp";
+
+-- Job descriptions customers send to a profile
+CREATE TABLE IF NOT EXISTS inquiries (
+    id UUID PRIMARY KEY NOT NULL DEFAULT (uuid_generate_v4()),
+    profile_id UUID NOT NULL REFERENCES profiles(id) ON DELETE CASCADE,
+    viewer_id UUID NOT NULL REFERENCES viewers(id) ON DELETE CASCADE,
+    craft_id UUID REFERENCES crafts(id) ON DELETE SET NULL,
+    message VARCHAR(2000) NOT NULL,
+    location VARCHAR(200) NOT NULL,
+    timeframe VARCHAR(20) NOT NULL CHECK (
+        timeframe IN ('asap', 'weeks', 'months', 'flexible')
+    ),
+    status VARCHAR(20) NOT NULL DEFAULT 'new' CHECK (
+        status IN ('new', 'answered', 'declined', 'done')
+    ),
+    status_changed_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    version SMALLINT NOT NULL DEFAULT 1,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_inquiries_profile_id ON inquiries (profile_id, created_at DESC);
+CREATE INDEX idx_inquiries_viewer_id ON inquiries (viewer_id, created_at DESC);
+
+-- Photos are only visible to the customer and the profile owner
+CREATE TABLE IF NOT EXISTS inquiry_photos (
+    id UUID PRIMARY KEY NOT NULL DEFAULT (uuid_generate_v4()),
+    inquiry_id UUID NOT NULL REFERENCES inquiries(id) ON DELETE CASCADE,
+    file_name VARCHAR(255) NOT NULL,
+    content_type VARCHAR(50) NOT NULL,
+    photo_data BYTEA NOT NULL,
+    version SMALLINT NOT NULL DEFAULT 1,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_inquiry_photos_inquiry_id ON inquiry_photos (inquiry_id);
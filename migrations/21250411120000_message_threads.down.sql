@@ -0,0 +1,6 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS message_attachments;
+
+DROP TABLE IF EXISTS messages;
+
+DROP TABLE IF EXISTS message_threads;
@@ -0,0 +1,51 @@
+-- Add up migration script here
+CREATE EXTENSION IF NOT EXISTS "uuid-ossp";
+
+-- One conversation between a customer and a profile. Each side tracks up to
+-- when it has read the thread, when it was last emailed about unread
+-- messages and whether it shares its contact details with the other side.
+CREATE TABLE IF NOT EXISTS message_threads (
+    id UUID PRIMARY KEY NOT NULL DEFAULT (uuid_generate_v4()),
+    profile_id UUID NOT NULL REFERENCES profiles(id) ON DELETE CASCADE,
+    customer_id UUID NOT NULL REFERENCES viewers(id) ON DELETE CASCADE,
+    inquiry_id UUID REFERENCES inquiries(id) ON DELETE SET NULL,
+    customer_last_read_at TIMESTAMP WITH TIME ZONE,
+    owner_last_read_at TIMESTAMP WITH TIME ZONE,
+    customer_notified_at TIMESTAMP WITH TIME ZONE,
+    owner_notified_at TIMESTAMP WITH TIME ZONE,
+    customer_shares_contact BOOLEAN NOT NULL DEFAULT FALSE,
+    owner_shares_contact BOOLEAN NOT NULL DEFAULT FALSE,
+    last_message_at TIMESTAMP WITH TIME ZONE,
+    version SMALLINT NOT NULL DEFAULT 1,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    UNIQUE (profile_id, customer_id)
+);
+
+CREATE INDEX idx_message_threads_customer_id ON message_threads (customer_id, last_message_at DESC);
+
+CREATE TABLE IF NOT EXISTS messages (
+    id UUID PRIMARY KEY NOT NULL DEFAULT (uuid_generate_v4()),
+    thread_id UUID NOT NULL REFERENCES message_threads(id) ON DELETE CASCADE,
+    sender_id UUID REFERENCES viewers(id) ON DELETE SET NULL,
+    sender_role VARCHAR(10) NOT NULL CHECK (sender_role IN ('customer', 'owner')),
+    body VARCHAR(4000) NOT NULL DEFAULT '',
+    version SMALLINT NOT NULL DEFAULT 1,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_messages_thread_id ON messages (thread_id, created_at DESC);
+
+CREATE TABLE IF NOT EXISTS message_attachments (
+    id UUID PRIMARY KEY NOT NULL DEFAULT (uuid_generate_v4()),
+    message_id UUID NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    file_name VARCHAR(255) NOT NULL,
+    content_type VARCHAR(50) NOT NULL,
+    photo_data BYTEA NOT NULL,
+    version SMALLINT NOT NULL DEFAULT 1,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_message_attachments_message_id ON message_attachments (message_id);
@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS idx_ratings_profile_id;
+
+DROP TABLE IF EXISTS reviews;
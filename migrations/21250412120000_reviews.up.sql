@@ -0,0 +1,29 @@
+-- Add up migration script here
+CREATE EXTENSION IF NOT EXISTS "uuid-ossp";
+
+-- One review per viewer and profile, optionally for an inquiry the profile
+-- completed. The owner may reply once.
+CREATE TABLE IF NOT EXISTS reviews (
+    id UUID PRIMARY KEY NOT NULL DEFAULT (uuid_generate_v4()),
+    profile_id UUID NOT NULL REFERENCES profiles(id) ON DELETE CASCADE,
+    viewer_id UUID NOT NULL REFERENCES viewers(id) ON DELETE CASCADE,
+    inquiry_id UUID REFERENCES inquiries(id) ON DELETE SET NULL,
+    rating SMALLINT NOT NULL CHECK (rating BETWEEN 1 AND 5),
+    body VARCHAR(2000) NOT NULL,
+    owner_reply VARCHAR(2000),
+    owner_replied_at TIMESTAMP WITH TIME ZONE,
+    version SMALLINT NOT NULL DEFAULT 1,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    UNIQUE (profile_id, viewer_id)
+);
+
+CREATE INDEX idx_reviews_profile_id ON reviews (profile_id, created_at DESC);
+
+-- `ratings` now holds the aggregate of the reviews, one row per profile
+DELETE FROM ratings a
+USING ratings b
+WHERE a.profile_id = b.profile_id
+    AND (a.updated_at, a.id) < (b.updated_at, b.id);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_ratings_profile_id ON ratings (profile_id);
@@ -0,0 +1,11 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS review_reports;
+
+DROP TABLE IF EXISTS review_status_changes;
+
+DROP INDEX IF EXISTS idx_reviews_status;
+
+ALTER TABLE reviews
+DROP COLUMN IF EXISTS status,
+DROP COLUMN IF EXISTS status_reason,
+DROP COLUMN IF EXISTS status_changed_at;
@@ -0,0 +1,43 @@
+-- Add up migration script here
+CREATE EXTENSION IF NOT EXISTS "uuid-ossp";
+
+-- Only published reviews are shown and counted. Reviews with links or
+-- blocked words are held for an admin, reported ones may be hidden.
+ALTER TABLE reviews
+ADD COLUMN status VARCHAR(20) NOT NULL DEFAULT 'published' CHECK (
+    status IN ('published', 'held', 'hidden')
+),
+ADD COLUMN status_reason VARCHAR(500),
+ADD COLUMN status_changed_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW();
+
+CREATE INDEX idx_reviews_status ON reviews (status);
+
+CREATE TABLE IF NOT EXISTS review_status_changes (
+    id UUID PRIMARY KEY NOT NULL DEFAULT (uuid_generate_v4()),
+    review_id UUID NOT NULL REFERENCES reviews(id) ON DELETE CASCADE,
+    viewer_id UUID REFERENCES viewers(id) ON DELETE SET NULL,
+    from_status VARCHAR(20) NOT NULL,
+    to_status VARCHAR(20) NOT NULL,
+    reason VARCHAR(500),
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_review_status_changes_review_id ON review_status_changes (review_id, created_at);
+
+-- One report per viewer and review, open until an admin acts on the review
+CREATE TABLE IF NOT EXISTS review_reports (
+    id UUID PRIMARY KEY NOT NULL DEFAULT (uuid_generate_v4()),
+    review_id UUID NOT NULL REFERENCES reviews(id) ON DELETE CASCADE,
+    viewer_id UUID NOT NULL REFERENCES viewers(id) ON DELETE CASCADE,
+    reason VARCHAR(20) NOT NULL CHECK (
+        reason IN ('spam', 'offensive', 'fake', 'personal_data', 'conflict_of_interest', 'other')
+    ),
+    comment VARCHAR(500),
+    resolved_at TIMESTAMP WITH TIME ZONE,
+    version SMALLINT NOT NULL DEFAULT 1,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    UNIQUE (review_id, viewer_id)
+);
+
+CREATE INDEX idx_review_reports_open ON review_reports (review_id) WHERE resolved_at IS NULL;
@@ -0,0 +1,14 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS profile_rating_sources;
+
+DELETE FROM ratings
+WHERE source <> 'reviews';
+
+DROP INDEX IF EXISTS idx_ratings_profile_id_source;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_ratings_profile_id ON ratings (profile_id);
+
+ALTER TABLE ratings
+DROP COLUMN IF EXISTS source,
+DROP COLUMN IF EXISTS url,
+DROP COLUMN IF EXISTS fetched_at;
@@ -0,0 +1,26 @@
+-- Add up migration script here
+CREATE EXTENSION IF NOT EXISTS "uuid-ossp";
+
+-- One rating per profile and source: "reviews" is the aggregate of our own
+-- reviews, other sources are imported from external providers
+ALTER TABLE ratings
+ADD COLUMN source VARCHAR(30) NOT NULL DEFAULT 'reviews',
+ADD COLUMN url VARCHAR(500),
+ADD COLUMN fetched_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW();
+
+DROP INDEX IF EXISTS idx_ratings_profile_id;
+
+CREATE UNIQUE INDEX idx_ratings_profile_id_source ON ratings (profile_id, source);
+
+-- Where a profile can be found at an external provider, e.g. its place id
+CREATE TABLE IF NOT EXISTS profile_rating_sources (
+    profile_id UUID NOT NULL REFERENCES profiles(id) ON DELETE CASCADE,
+    source VARCHAR(30) NOT NULL,
+    external_id VARCHAR(200) NOT NULL,
+    version SMALLINT NOT NULL DEFAULT 1,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (profile_id, source)
+);
+
+CREATE INDEX idx_profile_rating_sources_source ON profile_rating_sources (source);
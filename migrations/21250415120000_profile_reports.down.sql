@@ -0,0 +1,5 @@
+-- Add down migration script here
+ALTER TABLE profiles
+DROP COLUMN IF EXISTS reports_hidden_at;
+
+DROP TABLE IF EXISTS profile_reports;
@@ -0,0 +1,28 @@
+-- Add up migration script here
+CREATE EXTENSION IF NOT EXISTS "uuid-ossp";
+
+-- Visitors flag fake or offensive profiles. A viewer has at most one open
+-- report per profile; reports stay open until an admin triages them.
+CREATE TABLE IF NOT EXISTS profile_reports (
+    id UUID PRIMARY KEY NOT NULL DEFAULT (uuid_generate_v4()),
+    profile_id UUID NOT NULL REFERENCES profiles(id) ON DELETE CASCADE,
+    viewer_id UUID NOT NULL REFERENCES viewers(id) ON DELETE CASCADE,
+    category VARCHAR(20) NOT NULL CHECK (
+        category IN ('fake', 'offensive', 'spam', 'impersonation', 'wrong_information', 'other')
+    ),
+    comment VARCHAR(1000),
+    resolved_at TIMESTAMP WITH TIME ZONE,
+    resolved_by UUID REFERENCES viewers(id) ON DELETE SET NULL,
+    resolution VARCHAR(20) CHECK (resolution IN ('dismissed', 'suspended')),
+    version SMALLINT NOT NULL DEFAULT 1,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX idx_profile_reports_open ON profile_reports (profile_id, viewer_id)
+WHERE resolved_at IS NULL;
+
+-- Set once enough independent reports are open; such profiles are left out
+-- of listings and search until an admin has looked at them
+ALTER TABLE profiles
+ADD COLUMN reports_hidden_at TIMESTAMP WITH TIME ZONE;
@@ -0,0 +1,12 @@
+-- Add down migration script here
+DROP TRIGGER IF EXISTS trigger_queue_photo_blob_deletions ON photos;
+DROP FUNCTION IF EXISTS queue_photo_blob_deletions();
+DROP TABLE IF EXISTS photo_blob_deletions;
+
+DROP INDEX IF EXISTS idx_photos_storage_key;
+-- Fails while photos exist only in the blob store
+ALTER TABLE photos
+DROP CONSTRAINT IF EXISTS photos_blob_location,
+DROP COLUMN IF EXISTS byte_size,
+DROP COLUMN IF EXISTS storage_key,
+ALTER COLUMN photo_data SET NOT NULL;
@@ -0,0 +1,37 @@
+-- Add up migration script here
+CREATE EXTENSION IF NOT EXISTS "uuid-ossp";
+
+-- Photo bytes move to the blob store; photo_data only remains for rows that
+-- `mano migrate-photo-blobs` has not moved yet
+ALTER TABLE photos
+ADD COLUMN storage_key VARCHAR(300),
+ADD COLUMN byte_size INTEGER,
+ALTER COLUMN photo_data DROP NOT NULL,
+ADD CONSTRAINT photos_blob_location CHECK (storage_key IS NOT NULL OR photo_data IS NOT NULL);
+
+CREATE UNIQUE INDEX idx_photos_storage_key ON photos (storage_key);
+
+-- Keys of deleted photos, removed from the blob store by a background job.
+-- Filled by a trigger so cascading deletes are covered as well.
+CREATE TABLE IF NOT EXISTS photo_blob_deletions (
+  storage_key VARCHAR(300) PRIMARY KEY NOT NULL,
+  created_at TIMESTAMP
+  WITH
+    TIME ZONE NOT NULL DEFAULT NOW ()
+);
+
+CREATE OR REPLACE FUNCTION queue_photo_blob_deletions()
+RETURNS TRIGGER AS $$
+BEGIN
+    INSERT INTO photo_blob_deletions (storage_key)
+    SELECT storage_key FROM deleted_photos WHERE storage_key IS NOT NULL
+    ON CONFLICT DO NOTHING;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trigger_queue_photo_blob_deletions
+AFTER DELETE ON photos
+REFERENCING OLD TABLE AS deleted_photos
+FOR EACH STATEMENT
+EXECUTE FUNCTION queue_photo_blob_deletions();
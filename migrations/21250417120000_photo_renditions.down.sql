@@ -0,0 +1,5 @@
+-- Add down migration script here
+-- Queues the rendition blobs for removal before the table goes away
+DELETE FROM photo_renditions;
+DROP TRIGGER IF EXISTS trigger_queue_photo_rendition_blob_deletions ON photo_renditions;
+DROP TABLE IF EXISTS photo_renditions;
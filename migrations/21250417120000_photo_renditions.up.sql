@@ -0,0 +1,23 @@
+-- Add up migration script here
+CREATE EXTENSION IF NOT EXISTS "uuid-ossp";
+
+-- Scaled copies of a photo, generated on upload or on first request
+CREATE TABLE IF NOT EXISTS photo_renditions (
+  photo_id UUID NOT NULL REFERENCES photos (id) ON DELETE CASCADE,
+  size VARCHAR(10) NOT NULL CHECK (size IN ('thumb', 'card', 'full', 'large')),
+  format VARCHAR(10) NOT NULL CHECK (format IN ('jpeg', 'webp')),
+  storage_key VARCHAR(300) NOT NULL UNIQUE,
+  width INTEGER NOT NULL,
+  height INTEGER NOT NULL,
+  byte_size INTEGER NOT NULL,
+  created_at TIMESTAMP
+  WITH
+    TIME ZONE NOT NULL DEFAULT NOW (),
+  PRIMARY KEY (photo_id, size, format)
+);
+
+CREATE TRIGGER trigger_queue_photo_rendition_blob_deletions
+AFTER DELETE ON photo_renditions
+REFERENCING OLD TABLE AS deleted_photos
+FOR EACH STATEMENT
+EXECUTE FUNCTION queue_photo_blob_deletions();
@@ -0,0 +1,11 @@
+-- Add down migration script here
+ALTER TABLE profiles
+DROP COLUMN cover_photo_id;
+
+ALTER TABLE photos
+DROP COLUMN project_id,
+DROP COLUMN alt_text,
+DROP COLUMN caption,
+DROP COLUMN position;
+
+DROP TABLE IF EXISTS photo_projects;
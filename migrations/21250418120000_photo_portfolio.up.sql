@@ -0,0 +1,39 @@
+-- Add up migration script here
+CREATE EXTENSION IF NOT EXISTS "uuid-ossp";
+
+-- Portfolio entries grouping several photos of one job, like a kitchen built
+-- for a customer
+CREATE TABLE IF NOT EXISTS photo_projects (
+    id UUID PRIMARY KEY NOT NULL DEFAULT (uuid_generate_v4()),
+    profile_id UUID NOT NULL REFERENCES profiles(id) ON DELETE CASCADE,
+    title VARCHAR(100) NOT NULL,
+    description VARCHAR(1000),
+    position SMALLINT NOT NULL DEFAULT 0,
+    version SMALLINT NOT NULL DEFAULT 1,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_photo_projects_profile_id ON photo_projects (profile_id, position);
+
+ALTER TABLE photos
+ADD COLUMN position SMALLINT NOT NULL DEFAULT 0,
+ADD COLUMN caption VARCHAR(200),
+ADD COLUMN alt_text VARCHAR(300),
+ADD COLUMN project_id UUID REFERENCES photo_projects(id) ON DELETE SET NULL;
+
+-- Existing photos keep their upload order
+UPDATE photos
+SET position = ordered.position
+FROM (
+    SELECT id, ROW_NUMBER() OVER (PARTITION BY profile_id ORDER BY created_at, id) - 1 AS position
+    FROM photos
+) ordered
+WHERE photos.id = ordered.id;
+
+CREATE INDEX idx_photos_profile_position ON photos (profile_id, position);
+CREATE INDEX idx_photos_project_id ON photos (project_id);
+
+-- Shown in lists and search results; the first photo when not set
+ALTER TABLE profiles
+ADD COLUMN cover_photo_id UUID REFERENCES photos(id) ON DELETE SET NULL;
@@ -0,0 +1,9 @@
+-- Add down migration script here
+INSERT INTO handwerkskammern (name, number_pattern, example) VALUES
+    ('Handwerkskammer Berlin', '^[0-9]{5,7}$', '1234567'),
+    ('Handwerkskammer Hamburg', '^[0-9]{6}$', '123456'),
+    ('Handwerkskammer für München und Oberbayern', '^[0-9]{5,8}$', '12345678'),
+    ('Handwerkskammer zu Köln', '^[0-9]{5,7}$', '123456'),
+    ('Handwerkskammer Frankfurt-Rhein-Main', '^[0-9]{4,6}/[0-9]{2}$', '12345/01'),
+    ('Handwerkskammer Region Stuttgart', '^[A-Z]?[0-9]{5,7}$', 'S123456')
+ON CONFLICT (name) DO NOTHING;
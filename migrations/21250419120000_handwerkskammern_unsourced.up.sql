@@ -0,0 +1,20 @@
+-- Add up migration script here
+CREATE EXTENSION IF NOT EXISTS "uuid-ossp";
+
+-- The number formats seeded with the handwerkskarten migration have no
+-- source. Admins add each chamber with the format it confirmed instead;
+-- chambers that already have submissions stay so that admins can correct
+-- their pattern.
+DELETE FROM handwerkskammern h
+WHERE (h.name, h.number_pattern, h.example) IN (
+        ('Handwerkskammer Berlin', '^[0-9]{5,7}$', '1234567'),
+        ('Handwerkskammer Hamburg', '^[0-9]{6}$', '123456'),
+        ('Handwerkskammer für München und Oberbayern', '^[0-9]{5,8}$', '12345678'),
+        ('Handwerkskammer zu Köln', '^[0-9]{5,7}$', '123456'),
+        ('Handwerkskammer Frankfurt-Rhein-Main', '^[0-9]{4,6}/[0-9]{2}$', '12345/01'),
+        ('Handwerkskammer Region Stuttgart', '^[A-Z]?[0-9]{5,7}$', 'S123456')
+    )
+    AND NOT EXISTS (
+        SELECT 1 FROM handwerkskarten_verifications hv
+        WHERE hv.handwerkskammer_id = h.id
+    );
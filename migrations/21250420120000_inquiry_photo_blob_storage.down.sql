@@ -0,0 +1,10 @@
+-- Add down migration script here
+DROP TRIGGER IF EXISTS trigger_queue_inquiry_photo_blob_deletions ON inquiry_photos;
+
+DROP INDEX IF EXISTS idx_inquiry_photos_storage_key;
+-- Fails while inquiry photos exist only in the blob store
+ALTER TABLE inquiry_photos
+DROP CONSTRAINT IF EXISTS inquiry_photos_blob_location,
+DROP COLUMN IF EXISTS byte_size,
+DROP COLUMN IF EXISTS storage_key,
+ALTER COLUMN photo_data SET NOT NULL;
@@ -0,0 +1,19 @@
+-- Add up migration script here
+CREATE EXTENSION IF NOT EXISTS "uuid-ossp";
+
+-- Inquiry photos move to the blob store like profile photos; photo_data only
+-- remains for rows that `mano migrate-photo-blobs` has not moved yet
+ALTER TABLE inquiry_photos
+ADD COLUMN storage_key VARCHAR(300),
+ADD COLUMN byte_size INTEGER,
+ALTER COLUMN photo_data DROP NOT NULL,
+ADD CONSTRAINT inquiry_photos_blob_location CHECK (storage_key IS NOT NULL OR photo_data IS NOT NULL);
+
+CREATE UNIQUE INDEX idx_inquiry_photos_storage_key ON inquiry_photos (storage_key);
+
+-- Also covers photos deleted with their inquiry
+CREATE TRIGGER trigger_queue_inquiry_photo_blob_deletions
+AFTER DELETE ON inquiry_photos
+REFERENCING OLD TABLE AS deleted_photos
+FOR EACH STATEMENT
+EXECUTE FUNCTION queue_photo_blob_deletions();
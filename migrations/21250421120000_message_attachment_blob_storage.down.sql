@@ -0,0 +1,10 @@
+-- Add down migration script here
+DROP TRIGGER IF EXISTS trigger_queue_message_attachment_blob_deletions ON message_attachments;
+
+DROP INDEX IF EXISTS idx_message_attachments_storage_key;
+-- Fails while attachments exist only in the blob store
+ALTER TABLE message_attachments
+DROP CONSTRAINT IF EXISTS message_attachments_blob_location,
+DROP COLUMN IF EXISTS byte_size,
+DROP COLUMN IF EXISTS storage_key,
+ALTER COLUMN photo_data SET NOT NULL;
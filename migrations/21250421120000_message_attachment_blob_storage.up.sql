@@ -0,0 +1,20 @@
+-- Add up migration script here
+CREATE EXTENSION IF NOT EXISTS "uuid-ossp";
+
+-- Message attachments move to the blob store like profile photos;
+-- photo_data only remains for rows that `mano migrate-photo-blobs` has not
+-- moved yet
+ALTER TABLE message_attachments
+ADD COLUMN storage_key VARCHAR(300),
+ADD COLUMN byte_size INTEGER,
+ALTER COLUMN photo_data DROP NOT NULL,
+ADD CONSTRAINT message_attachments_blob_location CHECK (storage_key IS NOT NULL OR photo_data IS NOT NULL);
+
+CREATE UNIQUE INDEX idx_message_attachments_storage_key ON message_attachments (storage_key);
+
+-- Also covers attachments deleted with their message or thread
+CREATE TRIGGER trigger_queue_message_attachment_blob_deletions
+AFTER DELETE ON message_attachments
+REFERENCING OLD TABLE AS deleted_photos
+FOR EACH STATEMENT
+EXECUTE FUNCTION queue_photo_blob_deletions();
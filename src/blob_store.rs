@@ -0,0 +1,827 @@
+use std::{
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use chrono::Utc;
+use futures::future::BoxFuture;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use sqlx::{Pool, Postgres};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::AppState;
+
+#[derive(Error, Debug)]
+pub enum BlobStoreError {
+    #[error("Blob not found")]
+    NotFound,
+    #[error("Invalid blob key: {0}")]
+    InvalidKey(String),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Blob store configuration error: {0}")]
+    Config(String),
+}
+
+// Storage for binary data that does not belong into Postgres. Keys are
+// relative paths like `photos/<profile_id>/<photo_id>`.
+pub trait BlobStore: Send + Sync {
+    fn put<'a>(
+        &'a self,
+        key: &'a str,
+        content_type: &'a str,
+        data: Vec<u8>,
+    ) -> BoxFuture<'a, Result<(), BlobStoreError>>;
+
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Vec<u8>, BlobStoreError>>;
+
+    // Deleting a missing blob is not an error
+    fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<(), BlobStoreError>>;
+}
+
+pub fn photo_key(profile_id: Uuid, photo_id: Uuid) -> String {
+    format!("photos/{}/{}", profile_id, photo_id)
+}
+
+pub fn inquiry_photo_key(inquiry_id: Uuid, photo_id: Uuid) -> String {
+    format!("inquiries/{}/{}", inquiry_id, photo_id)
+}
+
+pub fn message_attachment_key(message_id: Uuid, attachment_id: Uuid) -> String {
+    format!("messages/{}/{}", message_id, attachment_id)
+}
+
+// Rejects keys that could leave the store's root or bucket
+fn check_key(key: &str) -> Result<(), BlobStoreError> {
+    let valid = !key.is_empty()
+        && Path::new(key)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        && key
+            .split('/')
+            .all(|segment| !matches!(segment, "" | "." | ".."));
+    if !valid {
+        return Err(BlobStoreError::InvalidKey(key.to_string()));
+    }
+    Ok(())
+}
+
+// Blobs as files below a directory
+pub struct LocalBlobStore {
+    root: PathBuf,
+}
+
+impl LocalBlobStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        LocalBlobStore { root: root.into() }
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf, BlobStoreError> {
+        check_key(key)?;
+        Ok(self.root.join(key))
+    }
+}
+
+impl BlobStore for LocalBlobStore {
+    fn put<'a>(
+        &'a self,
+        key: &'a str,
+        _content_type: &'a str,
+        data: Vec<u8>,
+    ) -> BoxFuture<'a, Result<(), BlobStoreError>> {
+        Box::pin(async move {
+            let path = self.path(key)?;
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            // Readers never see a half written file
+            let tmp_path = path.with_extension(format!("tmp-{}", Uuid::new_v4()));
+            tokio::fs::write(&tmp_path, data).await?;
+            if let Err(e) = tokio::fs::rename(&tmp_path, &path).await {
+                let _ = tokio::fs::remove_file(&tmp_path).await;
+                return Err(e.into());
+            }
+            Ok(())
+        })
+    }
+
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Vec<u8>, BlobStoreError>> {
+        Box::pin(async move {
+            match tokio::fs::read(self.path(key)?).await {
+                Ok(data) => Ok(data),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(BlobStoreError::NotFound),
+                Err(e) => Err(e.into()),
+            }
+        })
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<(), BlobStoreError>> {
+        Box::pin(async move {
+            match tokio::fs::remove_file(self.path(key)?).await {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            }
+        })
+    }
+}
+
+// Any S3 compatible service, addressed path style (`<endpoint>/<bucket>/<key>`)
+// so that MinIO and other self-hosted stores work without DNS setup. Requests
+// are signed with AWS Signature Version 4.
+pub struct S3BlobStore {
+    client: reqwest::Client,
+    endpoint: reqwest::Url,
+    bucket: String,
+    region: String,
+    access_key_id: String,
+    secret_access_key: String,
+}
+
+impl S3BlobStore {
+    pub fn new(
+        endpoint: &str,
+        bucket: &str,
+        region: &str,
+        access_key_id: &str,
+        secret_access_key: &str,
+    ) -> Result<Self, BlobStoreError> {
+        let endpoint = reqwest::Url::parse(endpoint)
+            .map_err(|e| BlobStoreError::Config(format!("S3_ENDPOINT: {}", e)))?;
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()?;
+        Ok(S3BlobStore {
+            client,
+            endpoint,
+            bucket: bucket.to_string(),
+            region: region.to_string(),
+            access_key_id: access_key_id.to_string(),
+            secret_access_key: secret_access_key.to_string(),
+        })
+    }
+
+    fn request(
+        &self,
+        method: reqwest::Method,
+        key: &str,
+        content_type: Option<&str>,
+        body: Vec<u8>,
+    ) -> Result<reqwest::RequestBuilder, BlobStoreError> {
+        check_key(key)?;
+
+        let path = std::iter::once(self.bucket.as_str())
+            .chain(key.split('/'))
+            .map(|segment| urlencoding::encode(segment).into_owned())
+            .collect::<Vec<_>>()
+            .join("/");
+        let canonical_uri = format!("{}/{}", self.endpoint.path().trim_end_matches('/'), path);
+        let mut url = self.endpoint.clone();
+        url.set_path(&canonical_uri);
+
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_string(),
+            (None, _) => return Err(BlobStoreError::Config("S3_ENDPOINT has no host".into())),
+        };
+
+        let now = Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let payload_hash = hex::encode(Sha256::digest(&body));
+
+        let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+        let canonical_request = format!(
+            "{}\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+            method, canonical_uri, host, payload_hash, amz_date, signed_headers, payload_hash
+        );
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex::encode(Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let signing_key = [self.region.as_str(), "s3", "aws4_request"].iter().fold(
+            hmac_sha256(
+                format!("AWS4{}", self.secret_access_key).as_bytes(),
+                date.as_bytes(),
+            ),
+            |key, part| hmac_sha256(&key, part.as_bytes()),
+        );
+        let signature = hex::encode(hmac_sha256(&signing_key, string_to_sign.as_bytes()));
+
+        let mut request = self
+            .client
+            .request(method, url)
+            .header("x-amz-date", amz_date)
+            .header("x-amz-content-sha256", payload_hash)
+            .header(
+                "Authorization",
+                format!(
+                    "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+                    self.access_key_id, scope, signed_headers, signature
+                ),
+            );
+        if let Some(content_type) = content_type {
+            request = request.header("Content-Type", content_type);
+        }
+        if !body.is_empty() {
+            request = request.body(body);
+        }
+        Ok(request)
+    }
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+impl BlobStore for S3BlobStore {
+    fn put<'a>(
+        &'a self,
+        key: &'a str,
+        content_type: &'a str,
+        data: Vec<u8>,
+    ) -> BoxFuture<'a, Result<(), BlobStoreError>> {
+        Box::pin(async move {
+            self.request(reqwest::Method::PUT, key, Some(content_type), data)?
+                .send()
+                .await?
+                .error_for_status()?;
+            Ok(())
+        })
+    }
+
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Vec<u8>, BlobStoreError>> {
+        Box::pin(async move {
+            let response = self
+                .request(reqwest::Method::GET, key, None, Vec::new())?
+                .send()
+                .await?;
+            if response.status() == reqwest::StatusCode::NOT_FOUND {
+                return Err(BlobStoreError::NotFound);
+            }
+            Ok(response.error_for_status()?.bytes().await?.to_vec())
+        })
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<(), BlobStoreError>> {
+        Box::pin(async move {
+            let response = self
+                .request(reqwest::Method::DELETE, key, None, Vec::new())?
+                .send()
+                .await?;
+            if response.status() != reqwest::StatusCode::NOT_FOUND {
+                response.error_for_status()?;
+            }
+            Ok(())
+        })
+    }
+}
+
+// BLOB_STORE=s3 uses the S3_* variables, anything else the local directory
+// BLOB_STORE_PATH
+pub fn blob_store_from_env() -> Result<Arc<dyn BlobStore>, BlobStoreError> {
+    let var = |name: &str| {
+        std::env::var(name).map_err(|_| BlobStoreError::Config(format!("{} must be set", name)))
+    };
+
+    match std::env::var("BLOB_STORE").as_deref() {
+        Ok("s3") => {
+            let region = std::env::var("S3_REGION").unwrap_or_else(|_| "us-east-1".to_string());
+            Ok(Arc::new(S3BlobStore::new(
+                &var("S3_ENDPOINT")?,
+                &var("S3_BUCKET")?,
+                &region,
+                &var("S3_ACCESS_KEY_ID")?,
+                &var("S3_SECRET_ACCESS_KEY")?,
+            )?))
+        }
+        _ => {
+            let root = std::env::var("BLOB_STORE_PATH").unwrap_or_else(|_| "blobs".to_string());
+            Ok(Arc::new(LocalBlobStore::new(root)))
+        }
+    }
+}
+
+// Moves photos, inquiry photos and message attachments still stored in their
+// `photo_data` column into the blob store. Safe to run while the server is up
+// and to run again after a failure; rows are only cleared in the database once
+// their blob is written.
+pub async fn migrate_photo_blobs(
+    db: &Pool<Postgres>,
+    store: &dyn BlobStore,
+    batch_size: i64,
+) -> Result<(u64, u64), sqlx::Error> {
+    let mut migrated = 0;
+    let mut failed = 0;
+    let mut last_id = Uuid::nil();
+
+    loop {
+        let photos = sqlx::query!(
+            r#"
+            SELECT id, profile_id, content_type, photo_data AS "photo_data!"
+            FROM photos
+            WHERE storage_key IS NULL AND photo_data IS NOT NULL AND id > $1
+            ORDER BY id
+            LIMIT $2
+            "#,
+            last_id,
+            batch_size
+        )
+        .fetch_all(db)
+        .await?;
+
+        let Some(last) = photos.last() else {
+            break;
+        };
+        last_id = last.id;
+
+        for photo in photos {
+            let key = photo_key(photo.profile_id, photo.id);
+            let byte_size = photo.photo_data.len() as i32;
+            if let Err(e) = store.put(&key, &photo.content_type, photo.photo_data).await {
+                eprintln!("migrate_photo_blobs: photo {} failed: {}", photo.id, e);
+                failed += 1;
+                continue;
+            }
+
+            sqlx::query!(
+                r#"
+                UPDATE photos
+                SET storage_key = $2, byte_size = $3, photo_data = NULL
+                WHERE id = $1 AND storage_key IS NULL
+                "#,
+                photo.id,
+                key,
+                byte_size
+            )
+            .execute(db)
+            .await?;
+            migrated += 1;
+        }
+        println!("migrate_photo_blobs: {} moved, {} failed", migrated, failed);
+    }
+
+    let (inquiry_migrated, inquiry_failed) =
+        migrate_inquiry_photo_blobs(db, store, batch_size).await?;
+    let (attachments_migrated, attachments_failed) =
+        migrate_message_attachment_blobs(db, store, batch_size).await?;
+    Ok((
+        migrated + inquiry_migrated + attachments_migrated,
+        failed + inquiry_failed + attachments_failed,
+    ))
+}
+
+async fn migrate_inquiry_photo_blobs(
+    db: &Pool<Postgres>,
+    store: &dyn BlobStore,
+    batch_size: i64,
+) -> Result<(u64, u64), sqlx::Error> {
+    let mut migrated = 0;
+    let mut failed = 0;
+    let mut last_id = Uuid::nil();
+
+    loop {
+        let photos = sqlx::query!(
+            r#"
+            SELECT id, inquiry_id, content_type, photo_data AS "photo_data!"
+            FROM inquiry_photos
+            WHERE storage_key IS NULL AND photo_data IS NOT NULL AND id > $1
+            ORDER BY id
+            LIMIT $2
+            "#,
+            last_id,
+            batch_size
+        )
+        .fetch_all(db)
+        .await?;
+
+        let Some(last) = photos.last() else {
+            break;
+        };
+        last_id = last.id;
+
+        for photo in photos {
+            let key = inquiry_photo_key(photo.inquiry_id, photo.id);
+            let byte_size = photo.photo_data.len() as i32;
+            if let Err(e) = store.put(&key, &photo.content_type, photo.photo_data).await {
+                eprintln!(
+                    "migrate_photo_blobs: inquiry photo {} failed: {}",
+                    photo.id, e
+                );
+                failed += 1;
+                continue;
+            }
+
+            sqlx::query!(
+                r#"
+                UPDATE inquiry_photos
+                SET storage_key = $2, byte_size = $3, photo_data = NULL
+                WHERE id = $1 AND storage_key IS NULL
+                "#,
+                photo.id,
+                key,
+                byte_size
+            )
+            .execute(db)
+            .await?;
+            migrated += 1;
+        }
+        println!(
+            "migrate_photo_blobs: {} inquiry photos moved, {} failed",
+            migrated, failed
+        );
+    }
+
+    Ok((migrated, failed))
+}
+
+async fn migrate_message_attachment_blobs(
+    db: &Pool<Postgres>,
+    store: &dyn BlobStore,
+    batch_size: i64,
+) -> Result<(u64, u64), sqlx::Error> {
+    let mut migrated = 0;
+    let mut failed = 0;
+    let mut last_id = Uuid::nil();
+
+    loop {
+        let attachments = sqlx::query!(
+            r#"
+            SELECT id, message_id, content_type, photo_data AS "photo_data!"
+            FROM message_attachments
+            WHERE storage_key IS NULL AND photo_data IS NOT NULL AND id > $1
+            ORDER BY id
+            LIMIT $2
+            "#,
+            last_id,
+            batch_size
+        )
+        .fetch_all(db)
+        .await?;
+
+        let Some(last) = attachments.last() else {
+            break;
+        };
+        last_id = last.id;
+
+        for attachment in attachments {
+            let key = message_attachment_key(attachment.message_id, attachment.id);
+            let byte_size = attachment.photo_data.len() as i32;
+            if let Err(e) = store
+                .put(&key, &attachment.content_type, attachment.photo_data)
+                .await
+            {
+                eprintln!(
+                    "migrate_photo_blobs: attachment {} failed: {}",
+                    attachment.id, e
+                );
+                failed += 1;
+                continue;
+            }
+
+            sqlx::query!(
+                r#"
+                UPDATE message_attachments
+                SET storage_key = $2, byte_size = $3, photo_data = NULL
+                WHERE id = $1 AND storage_key IS NULL
+                "#,
+                attachment.id,
+                key,
+                byte_size
+            )
+            .execute(db)
+            .await?;
+            migrated += 1;
+        }
+        println!(
+            "migrate_photo_blobs: {} attachments moved, {} failed",
+            migrated, failed
+        );
+    }
+
+    Ok((migrated, failed))
+}
+
+// Removes the blobs queued by the delete triggers on photos, renditions,
+// inquiry photos and message attachments
+async fn purge_deleted_photo_blobs(
+    db: &Pool<Postgres>,
+    store: &dyn BlobStore,
+) -> Result<u64, sqlx::Error> {
+    let keys = sqlx::query_scalar!(
+        "SELECT storage_key FROM photo_blob_deletions ORDER BY created_at LIMIT 500"
+    )
+    .fetch_all(db)
+    .await?;
+
+    let mut purged = Vec::with_capacity(keys.len());
+    for key in keys {
+        match store.delete(&key).await {
+            Ok(()) => purged.push(key),
+            Err(e) => eprintln!("purge_deleted_photo_blobs {}: {}", key, e),
+        }
+    }
+
+    let result = sqlx::query!(
+        "DELETE FROM photo_blob_deletions WHERE storage_key = ANY($1)",
+        &purged
+    )
+    .execute(db)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+// Background job started from main
+pub async fn run_photo_blob_cleanup(data: Arc<AppState>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(10 * 60));
+    loop {
+        interval.tick().await;
+        match purge_deleted_photo_blobs(&data.db, data.blob_store.as_ref()).await {
+            Ok(0) => {}
+            Ok(purged) => println!("run_photo_blob_cleanup: removed {} blobs", purged),
+            Err(e) => eprintln!("run_photo_blob_cleanup error: {:?}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Local stand-in for an S3 compatible store like MinIO, enough for the blob
+    // store: path style `PUT`, `GET` and `DELETE /:bucket/*key` with AWS Signature
+    // Version 4 checked against the given credentials. Objects are kept in memory.
+    mod s3_mock {
+        use std::{
+            collections::HashMap,
+            sync::{Arc, Mutex},
+        };
+
+        use axum::{
+            body::Bytes,
+            extract::{Path, State},
+            http::{HeaderMap, Method, StatusCode, Uri},
+            response::{IntoResponse, Response},
+            routing::put,
+            Router,
+        };
+        use hmac::{Hmac, Mac};
+        use sha2::{Digest, Sha256};
+
+        struct Store {
+            access_key_id: String,
+            secret_access_key: String,
+            objects: Mutex<HashMap<(String, String), (String, Bytes)>>,
+        }
+
+        fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+            let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+            mac.update(data);
+            mac.finalize().into_bytes().to_vec()
+        }
+
+        fn header<'a>(headers: &'a HeaderMap, name: &str) -> &'a str {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default()
+        }
+
+        // Recomputes the signature from the request; returns the reason on mismatch
+        fn verify_signature(
+            store: &Store,
+            method: &Method,
+            uri: &Uri,
+            headers: &HeaderMap,
+            body: &[u8],
+        ) -> Result<(), String> {
+            let authorization = header(headers, "authorization");
+            let fields: HashMap<&str, &str> = authorization
+                .strip_prefix("AWS4-HMAC-SHA256 ")
+                .ok_or("missing AWS4-HMAC-SHA256 authorization")?
+                .split(", ")
+                .filter_map(|field| field.split_once('='))
+                .collect();
+
+            let credential = fields.get("Credential").ok_or("missing Credential")?;
+            let (access_key_id, scope) = credential.split_once('/').ok_or("invalid Credential")?;
+            if access_key_id != store.access_key_id {
+                return Err("unknown access key".to_string());
+            }
+            let mut scope_parts = scope.split('/');
+            let (Some(date), Some(region)) = (scope_parts.next(), scope_parts.next()) else {
+                return Err("invalid credential scope".to_string());
+            };
+
+            let payload_hash = header(headers, "x-amz-content-sha256");
+            if payload_hash != hex::encode(Sha256::digest(body)) {
+                return Err("payload hash mismatch".to_string());
+            }
+
+            let signed_headers = fields.get("SignedHeaders").ok_or("missing SignedHeaders")?;
+            let canonical_headers: String = signed_headers
+                .split(';')
+                .map(|name| format!("{}:{}\n", name, header(headers, name).trim()))
+                .collect();
+            let canonical_request = format!(
+                "{}\n{}\n{}\n{}\n{}\n{}",
+                method,
+                uri.path(),
+                uri.query().unwrap_or_default(),
+                canonical_headers,
+                signed_headers,
+                payload_hash
+            );
+            let string_to_sign = format!(
+                "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+                header(headers, "x-amz-date"),
+                scope,
+                hex::encode(Sha256::digest(canonical_request.as_bytes()))
+            );
+
+            let mut signing_key = hmac_sha256(
+                format!("AWS4{}", store.secret_access_key).as_bytes(),
+                date.as_bytes(),
+            );
+            for part in [region, "s3", "aws4_request"] {
+                signing_key = hmac_sha256(&signing_key, part.as_bytes());
+            }
+            let signature = hex::encode(hmac_sha256(&signing_key, string_to_sign.as_bytes()));
+
+            if fields.get("Signature") != Some(&signature.as_str()) {
+                return Err("signature mismatch".to_string());
+            }
+            Ok(())
+        }
+
+        fn s3_error(status: StatusCode, code: &str, message: &str) -> Response {
+            (
+                status,
+                [("Content-Type", "application/xml")],
+                format!(
+                    "<?xml version=\"1.0\" encoding=\"UTF-8\"?><Error><Code>{}</Code><Message>{}</Message></Error>",
+                    code, message
+                ),
+            )
+                .into_response()
+        }
+
+        async fn object(
+            State(store): State<Arc<Store>>,
+            Path((bucket, key)): Path<(String, String)>,
+            method: Method,
+            uri: Uri,
+            headers: HeaderMap,
+            body: Bytes,
+        ) -> Response {
+            if let Err(reason) = verify_signature(&store, &method, &uri, &headers, &body) {
+                return s3_error(StatusCode::FORBIDDEN, "SignatureDoesNotMatch", &reason);
+            }
+
+            let mut objects = store.objects.lock().unwrap();
+            let id = (bucket, key);
+            match method {
+                Method::PUT => {
+                    let content_type = header(&headers, "content-type").to_string();
+                    objects.insert(id, (content_type, body));
+                    StatusCode::OK.into_response()
+                }
+                Method::GET => match objects.get(&id) {
+                    Some((content_type, data)) => {
+                        ([("Content-Type", content_type.clone())], data.clone()).into_response()
+                    }
+                    None => s3_error(
+                        StatusCode::NOT_FOUND,
+                        "NoSuchKey",
+                        "The specified key does not exist.",
+                    ),
+                },
+                _ => {
+                    objects.remove(&id);
+                    StatusCode::NO_CONTENT.into_response()
+                }
+            }
+        }
+
+        pub fn app(access_key_id: &str, secret_access_key: &str) -> Router {
+            let store = Store {
+                access_key_id: access_key_id.to_string(),
+                secret_access_key: secret_access_key.to_string(),
+                objects: Mutex::new(HashMap::new()),
+            };
+            Router::new()
+                .route("/:bucket/*key", put(object).get(object).delete(object))
+                .with_state(Arc::new(store))
+        }
+    }
+
+    // Serves `app` on a free local port until the test's runtime shuts down
+    async fn spawn(app: axum::Router) -> std::net::SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        addr
+    }
+
+    const ACCESS_KEY_ID: &str = "minio";
+    const SECRET_ACCESS_KEY: &str = "minio123";
+
+    async fn s3_mock() -> String {
+        let addr = spawn(s3_mock::app(ACCESS_KEY_ID, SECRET_ACCESS_KEY)).await;
+        format!("http://{}", addr)
+    }
+
+    fn s3_store(endpoint: &str, secret_access_key: &str) -> S3BlobStore {
+        S3BlobStore::new(
+            endpoint,
+            "mano",
+            "eu-central-1",
+            ACCESS_KEY_ID,
+            secret_access_key,
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn s3_put_get_delete() {
+        let store = s3_store(&s3_mock().await, SECRET_ACCESS_KEY);
+        let key = photo_key(Uuid::new_v4(), Uuid::new_v4());
+
+        store
+            .put(&key, "image/jpeg", b"jpeg data".to_vec())
+            .await
+            .unwrap();
+        assert_eq!(store.get(&key).await.unwrap(), b"jpeg data");
+
+        store
+            .put(&key, "image/jpeg", b"replaced".to_vec())
+            .await
+            .unwrap();
+        assert_eq!(store.get(&key).await.unwrap(), b"replaced");
+
+        store.delete(&key).await.unwrap();
+        assert!(matches!(
+            store.get(&key).await,
+            Err(BlobStoreError::NotFound)
+        ));
+        // Deleting again is fine
+        store.delete(&key).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn s3_signs_escaped_keys_and_empty_bodies() {
+        let store = s3_store(&s3_mock().await, SECRET_ACCESS_KEY);
+        let key = "photos/Übersicht 1/bild (2).jpg";
+
+        store.put(key, "image/jpeg", Vec::new()).await.unwrap();
+        assert_eq!(store.get(key).await.unwrap(), b"");
+        store.delete(key).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn s3_rejects_wrong_signature() {
+        let endpoint = s3_mock().await;
+        let store = s3_store(&endpoint, "not-the-secret");
+        let key = "photos/a/b";
+
+        for result in [
+            store.put(key, "image/jpeg", b"data".to_vec()).await,
+            store.get(key).await.map(|_| ()),
+            store.delete(key).await,
+        ] {
+            assert!(matches!(
+                result,
+                Err(BlobStoreError::Http(e)) if e.status() == Some(reqwest::StatusCode::FORBIDDEN)
+            ));
+        }
+
+        // Nothing was written
+        let store = s3_store(&endpoint, SECRET_ACCESS_KEY);
+        assert!(matches!(
+            store.get(key).await,
+            Err(BlobStoreError::NotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn s3_checks_keys_before_sending() {
+        let store = s3_store(&s3_mock().await, SECRET_ACCESS_KEY);
+        for key in ["", "../etc/passwd", "/photos/a", "photos//a", "photos/./a"] {
+            assert!(matches!(
+                store.get(key).await,
+                Err(BlobStoreError::InvalidKey(_))
+            ));
+        }
+    }
+}
@@ -279,4 +279,205 @@ impl EmailManager {
         self.smtp_transport.send(&email)?;
         Ok(())
     }
+
+    pub fn send_profile_status_email(
+        &self,
+        email: &str,
+        profile_link: &str,
+        recipient_name: &str,
+        subject: &str,
+        message: &str,
+        reason: Option<&str>,
+    ) -> Result<(), EmailManagerError> {
+        let reason_paragraph = match reason {
+            Some(reason) => format!(
+                "<p><strong>Begründung:</strong> {}</p>",
+                escape_html(reason)
+            ),
+            None => String::new(),
+        };
+
+        self.send_notification_email(
+            email,
+            subject,
+            recipient_name,
+            &format!("<p>{}</p>{}", message, reason_paragraph),
+            profile_link,
+            "Zum Profil",
+        )
+    }
+
+    pub fn send_inquiry_email(
+        &self,
+        email: &str,
+        inbox_link: &str,
+        recipient_name: &str,
+        customer_name: &str,
+        craft: Option<&str>,
+        message: &str,
+    ) -> Result<(), EmailManagerError> {
+        let craft_paragraph = match craft {
+            Some(craft) => format!("<p><strong>Gewerk:</strong> {}</p>", escape_html(craft)),
+            None => String::new(),
+        };
+
+        self.send_notification_email(
+            email,
+            "Neue Anfrage über Mano",
+            recipient_name,
+            &format!(
+                "<p>{} hat dir eine Anfrage geschickt:</p>{}<p><em>{}</em></p>",
+                escape_html(customer_name),
+                craft_paragraph,
+                escape_html(message)
+            ),
+            inbox_link,
+            "Zur Anfrage",
+        )
+    }
+
+    // One email listing every conversation with unread messages
+    pub fn send_message_digest_email(
+        &self,
+        email: &str,
+        inbox_link: &str,
+        recipient_name: &str,
+        threads: &[(String, i64)],
+    ) -> Result<(), EmailManagerError> {
+        let items: String = threads
+            .iter()
+            .map(|(name, unread)| {
+                format!(
+                    "<li>{}: {} {}</li>",
+                    escape_html(name),
+                    unread,
+                    if *unread == 1 {
+                        "neue Nachricht"
+                    } else {
+                        "neue Nachrichten"
+                    }
+                )
+            })
+            .collect();
+
+        self.send_notification_email(
+            email,
+            "Du hast ungelesene Nachrichten auf Mano",
+            recipient_name,
+            &format!("<p>Du hast ungelesene Nachrichten:</p><ul>{}</ul>", items),
+            inbox_link,
+            "Zu den Nachrichten",
+        )
+    }
+
+    // Shared layout of the notification emails; `content` is trusted HTML
+    fn send_notification_email(
+        &self,
+        email: &str,
+        subject: &str,
+        recipient_name: &str,
+        content: &str,
+        button_link: &str,
+        button_label: &str,
+    ) -> Result<(), EmailManagerError> {
+        let from_address: Address = self.email.parse()?;
+        let to_address: Address = email.parse()?;
+
+        let email_body = format!(
+            r#"<!DOCTYPE html>
+            <html>
+              <head>
+                <style>
+                  /* General Styles */
+                  body {{
+                    font-family: Arial, sans-serif;
+                    background-color: #f4f4f4;
+                    margin: 0;
+                    padding: 0;
+                  }}
+
+                  .email-container {{
+                    background-color: white;
+                    margin: 0;
+                    padding: 20px;
+                    max-width: 600px;
+                    box-shadow: 0 0 10px rgba(0, 0, 0, 0.1);
+                  }}
+
+                  /* Logo Section */
+                  .header {{
+                    text-align: left;
+                    font-size: 1.25rem;
+                    font-weight: 600;
+                  }}
+
+                  /* Main content */
+                  .content {{
+                    margin-top: 20px;
+                  }}
+
+                  .content p {{
+                    font-size: 16px;
+                    color: #444;
+                    line-height: 1.6;
+                  }}
+
+                  /* Action Button */
+                  .reset-button {{
+                    display: inline-block;
+                    background-color: #ff5a5f;
+                    color: #fff !important;
+                    padding: 15px 20px;
+                    text-decoration: none;
+                    font-size: 16px;
+                    border-radius: 4px;
+                    margin-top: 20px;
+                    margin-bottom: 10px;
+                  }}
+                </style>
+              </head>
+              <body>
+                <div class="email-container">
+                  <!-- Header with Logo -->
+                  <div class="header">
+                    Mano
+                  </div>
+
+                  <!-- Email Content -->
+                  <div class="content">
+                    <p>Hey {recipient_name},</p>
+                    {content}
+
+                    <!-- Action Button -->
+                    <a href="{button_link}" class="reset-button">{button_label}</a>
+
+                    <p>Danke,<br>Das Mano Team</p>
+                  </div>
+                </div>
+              </body>
+            </html>"#,
+            recipient_name = escape_html(recipient_name),
+            content = content,
+            button_link = button_link,
+            button_label = button_label
+        );
+
+        let email = Message::builder()
+            .from(from_address.into())
+            .to(to_address.into())
+            .subject(subject)
+            .header(header::ContentType::TEXT_HTML)
+            .body(email_body)?;
+
+        self.smtp_transport.send(&email)?;
+        Ok(())
+    }
+}
+
+// Admin- and user-supplied text ends up in the HTML body
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
 }
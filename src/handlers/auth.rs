@@ -303,7 +303,7 @@ pub async fn register(
     //Set verified to true
     let query_result = sqlx::query_as!(
         ViewerModel,
-        "UPDATE viewers SET verified = TRUE WHERE email = $1 RETURNING *",
+        "UPDATE viewers SET verified = TRUE, version = version % 32767 + 1 WHERE email = $1 RETURNING *",
         body.email
     )
     .fetch_one(&data.db)
@@ -321,7 +321,7 @@ pub async fn register(
     //Set was_used to true
     let query_result = sqlx::query_as!(
         PreRegisteredModel,
-        "UPDATE pre_registered SET was_used = TRUE WHERE id = $1 RETURNING *",
+        "UPDATE pre_registered SET was_used = TRUE, version = version % 32767 + 1 WHERE id = $1 RETURNING *",
         pre_registered_entry.id
     )
     .fetch_one(&data.db)
@@ -543,9 +543,10 @@ pub async fn reset_password(
     // Update viewer row
     let query_result = sqlx::query_as!(
         ViewerModel,
-        "UPDATE viewers SET hashed = $1, salt = $2 RETURNING *",
+        "UPDATE viewers SET hashed = $1, salt = $2, version = version % 32767 + 1 WHERE id = $3 RETURNING *",
         hashed_password,
-        salt
+        salt,
+        viewer_id
     )
     .fetch_one(&data.db)
     .await;
@@ -562,7 +563,7 @@ pub async fn reset_password(
     //Set was_used to true
     let query_result = sqlx::query_as!(
         ResetPasswordModel,
-        "UPDATE reset_password SET was_used = TRUE WHERE id = $1 RETURNING *",
+        "UPDATE reset_password SET was_used = TRUE, version = version % 32767 + 1 WHERE id = $1 RETURNING *",
         reset_password_entry.id
     )
     .fetch_one(&data.db)
@@ -578,7 +579,7 @@ pub async fn reset_password(
     }
 
     let update_result = sqlx::query!(
-        "UPDATE viewers SET updated_at = NOW() WHERE id = $1",
+        "UPDATE viewers SET updated_at = NOW(), version = version % 32767 + 1 WHERE id = $1",
         viewer_id
     )
     .execute(&data.db)
@@ -0,0 +1,246 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{NaiveDate, NaiveTime};
+use serde_json::json;
+use sqlx::{Pool, Postgres};
+use uuid::Uuid;
+
+use crate::{
+    model::ProfileStatus,
+    schema::AvailabilitySchema,
+    validation::{validation_error_response, FieldError},
+    AppState,
+};
+
+use super::{
+    auth::AuthenticatedViewer,
+    moderation::notify_status_change,
+    profile::{ensure_can_edit_profile, lock_profile_snapshot},
+    revision::record_revision,
+};
+
+fn internal_error(e: sqlx::Error) -> (StatusCode, Json<serde_json::Value>) {
+    eprintln!("availability error: {:?}", e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(json!({ "status": "fail", "message": "Internal Server Error" })),
+    )
+}
+
+// Status, weekly opening hours, upcoming closures and the computed next
+// available date of a profile. None if the profile does not exist.
+pub async fn load_availability(
+    db: &Pool<Postgres>,
+    profile_id: Uuid,
+) -> Result<Option<serde_json::Value>, sqlx::Error> {
+    let Some(profile) = sqlx::query!(
+        r#"
+        SELECT availability_status, available_from,
+            profile_next_available_date(id) AS next_available
+        FROM profiles
+        WHERE id = $1
+        "#,
+        profile_id
+    )
+    .fetch_optional(db)
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    let opening_hours = sqlx::query!(
+        r#"
+        SELECT weekday, opens_at, closes_at
+        FROM profile_opening_hours
+        WHERE profile_id = $1
+        ORDER BY weekday, opens_at
+        "#,
+        profile_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    let closures = sqlx::query!(
+        r#"
+        SELECT starts_on, ends_on, note
+        FROM profile_closures
+        WHERE profile_id = $1 AND ends_on >= CURRENT_DATE
+        ORDER BY starts_on, ends_on
+        "#,
+        profile_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(Some(json!({
+        "status": profile.availability_status,
+        "available_from": profile.available_from,
+        "next_available": profile.next_available,
+        "opening_hours": opening_hours
+            .iter()
+            .map(|hours| json!({
+                "weekday": hours.weekday,
+                "opens": hours.opens_at.format("%H:%M").to_string(),
+                "closes": hours.closes_at.format("%H:%M").to_string()
+            }))
+            .collect::<Vec<_>>(),
+        "closures": closures
+            .iter()
+            .map(|closure| json!({
+                "starts_on": closure.starts_on,
+                "ends_on": closure.ends_on,
+                "note": closure.note
+            }))
+            .collect::<Vec<_>>()
+    })))
+}
+
+pub async fn get_availability(
+    State(data): State<Arc<AppState>>,
+    Path(profile_id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let availability = load_availability(&data.db, profile_id)
+        .await
+        .map_err(internal_error)?
+        .ok_or((
+            StatusCode::NOT_FOUND,
+            Json(json!({ "status": "fail", "message": "Profile not found" })),
+        ))?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "data": availability
+    })))
+}
+
+// Owner replaces status, opening hours and closures in one go
+pub async fn update_availability(
+    State(data): State<Arc<AppState>>,
+    Path(profile_id): Path<Uuid>,
+    AuthenticatedViewer {
+        viewer_id,
+        is_admin,
+    }: AuthenticatedViewer,
+    Json(body): Json<AvailabilitySchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    ensure_can_edit_profile(&data.db, profile_id, viewer_id, is_admin).await?;
+
+    let errors: Vec<FieldError> = body.validate();
+    if !errors.is_empty() {
+        return Err(validation_error_response(errors));
+    }
+
+    let mut tx = data.db.begin().await.map_err(internal_error)?;
+
+    let before = lock_profile_snapshot(&mut tx, profile_id).await?;
+
+    sqlx::query!(
+        r#"
+        UPDATE profiles
+        SET availability_status = $2, available_from = $3,
+            updated_at = NOW(), version = version % 32767 + 1
+        WHERE id = $1
+        "#,
+        profile_id,
+        body.status,
+        body.available_from
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+
+    sqlx::query!(
+        "DELETE FROM profile_opening_hours WHERE profile_id = $1",
+        profile_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+
+    let weekdays: Vec<i16> = body.opening_hours.iter().map(|h| h.weekday).collect();
+    let opens: Vec<NaiveTime> = body.opening_hours.iter().map(|h| h.opens).collect();
+    let closes: Vec<NaiveTime> = body.opening_hours.iter().map(|h| h.closes).collect();
+    sqlx::query!(
+        r#"
+        INSERT INTO profile_opening_hours (profile_id, weekday, opens_at, closes_at)
+        SELECT $1, t.weekday, t.opens_at, t.closes_at
+        FROM UNNEST($2::smallint[], $3::time[], $4::time[]) AS t(weekday, opens_at, closes_at)
+        "#,
+        profile_id,
+        &weekdays,
+        &opens,
+        &closes
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+
+    sqlx::query!(
+        "DELETE FROM profile_closures WHERE profile_id = $1",
+        profile_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+
+    let starts: Vec<NaiveDate> = body.closures.iter().map(|c| c.starts_on).collect();
+    let ends: Vec<NaiveDate> = body.closures.iter().map(|c| c.ends_on).collect();
+    let notes: Vec<Option<String>> = body
+        .closures
+        .iter()
+        .map(|c| {
+            c.note
+                .as_deref()
+                .map(str::trim)
+                .filter(|n| !n.is_empty())
+                .map(str::to_string)
+        })
+        .collect();
+    sqlx::query!(
+        r#"
+        INSERT INTO profile_closures (profile_id, starts_on, ends_on, note)
+        SELECT $1, t.starts_on, t.ends_on, t.note
+        FROM UNNEST($2::date[], $3::date[], $4::varchar[]) AS t(starts_on, ends_on, note)
+        "#,
+        profile_id,
+        &starts,
+        &ends,
+        &notes as &[Option<String>]
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+
+    let review_required = record_revision(
+        &mut tx,
+        &data,
+        profile_id,
+        viewer_id,
+        is_admin,
+        Some(&before),
+        None,
+    )
+    .await
+    .map_err(internal_error)?;
+
+    tx.commit().await.map_err(internal_error)?;
+
+    if review_required {
+        notify_status_change(&data, profile_id, ProfileStatus::Pending, None).await;
+    }
+
+    let availability = load_availability(&data.db, profile_id)
+        .await
+        .map_err(internal_error)?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "data": availability
+    })))
+}
@@ -23,6 +23,7 @@ pub async fn get_crafts(
         .fetch_all(&data.db)
         .await
         .map_err(|e| {
+            eprintln!("Error getting crafts: {:?}", e);
             (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 Json(json!({
@@ -128,7 +129,7 @@ pub async fn update_craft(
     }
     let updated_craft = sqlx::query!(
         r#"
-        UPDATE crafts SET updated_at = NOW(), name = $1
+        UPDATE crafts SET updated_at = NOW(), version = version % 32767 + 1, name = $1
         WHERE name = $2
         RETURNING id, name;
         "#,
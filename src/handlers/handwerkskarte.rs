@@ -0,0 +1,680 @@
+use std::sync::Arc;
+
+use axum::{
+    body::Bytes,
+    extract::{multipart::Field, Multipart, Path, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    media::{media_error_response, MediaError},
+    schema::{HandwerkskammerSchema, ModerationSchema},
+    validation::{validation_error_response, FieldError},
+    AppState,
+};
+
+use super::{
+    auth::AuthenticatedViewer, moderation::required_reason, profile::ensure_can_edit_profile,
+};
+
+const SCAN_MAX_SIZE: usize = 10 * 1024 * 1024;
+// Enough to tell all allowed formats apart
+const SCAN_SNIFF_LEN: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ScanFormat {
+    Jpeg,
+    Png,
+    Pdf,
+}
+
+impl ScanFormat {
+    // By magic bytes, whatever the client claims the content type to be
+    fn sniff(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(ScanFormat::Jpeg)
+        } else if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(ScanFormat::Png)
+        } else if bytes.starts_with(b"%PDF-") {
+            Some(ScanFormat::Pdf)
+        } else {
+            None
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            ScanFormat::Jpeg => "image/jpeg",
+            ScanFormat::Png => "image/png",
+            ScanFormat::Pdf => "application/pdf",
+        }
+    }
+}
+
+fn unsupported_scan() -> (StatusCode, Json<serde_json::Value>) {
+    (
+        StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        Json(json!({
+            "status": "fail",
+            "message": "Unsupported scan format, allowed are JPEG, PNG and PDF"
+        })),
+    )
+}
+
+// Reads the scan chunk by chunk and aborts as soon as it is over the size
+// limit or its first bytes are not an allowed format
+async fn read_scan_field(
+    mut field: Field<'_>,
+) -> Result<(ScanFormat, Bytes), (StatusCode, Json<serde_json::Value>)> {
+    let mut data = Vec::new();
+    let mut format = None;
+    while let Some(chunk) = field
+        .chunk()
+        .await
+        .map_err(|e| media_error_response(MediaError::Multipart(e)))?
+    {
+        if data.len() + chunk.len() > SCAN_MAX_SIZE {
+            return Err((
+                StatusCode::PAYLOAD_TOO_LARGE,
+                Json(json!({
+                    "status": "fail",
+                    "message": "Scan is too large"
+                })),
+            ));
+        }
+        data.extend_from_slice(&chunk);
+        if format.is_none() && data.len() >= SCAN_SNIFF_LEN {
+            format = Some(ScanFormat::sniff(&data).ok_or_else(unsupported_scan)?);
+        }
+    }
+    let format = match format {
+        Some(format) => format,
+        None => ScanFormat::sniff(&data).ok_or_else(unsupported_scan)?,
+    };
+
+    Ok((format, Bytes::from(data)))
+}
+
+fn internal_error(e: impl std::fmt::Debug) -> (StatusCode, Json<serde_json::Value>) {
+    eprintln!("handwerkskarte error: {:?}", e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(json!({ "status": "fail", "message": "Internal Server Error" })),
+    )
+}
+
+pub async fn get_handwerkskammern(
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let rows = sqlx::query!("SELECT id, name, example FROM handwerkskammern ORDER BY name")
+        .fetch_all(&data.db)
+        .await
+        .map_err(internal_error)?;
+
+    let response_data: Vec<serde_json::Value> = rows
+        .into_iter()
+        .map(|row| json!({ "id": row.id, "name": row.name, "example": row.example }))
+        .collect();
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "Cache-Control",
+        HeaderValue::from_static("public, max-age=60"),
+    );
+
+    Ok((
+        headers,
+        Json(json!({
+            "status": "success",
+            "data": response_data
+        })),
+    ))
+}
+
+fn only_admins_manage_handwerkskammern() -> (StatusCode, Json<serde_json::Value>) {
+    (
+        StatusCode::UNAUTHORIZED,
+        Json(json!({
+            "status": "fail",
+            "message": "Only admins can manage Handwerkskammern."
+        })),
+    )
+}
+
+fn handwerkskammer_exists() -> (StatusCode, Json<serde_json::Value>) {
+    (
+        StatusCode::CONFLICT,
+        Json(json!({
+            "status": "fail",
+            "message": "Handwerkskammer already exists"
+        })),
+    )
+}
+
+fn handwerkskammer_not_found() -> (StatusCode, Json<serde_json::Value>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({
+            "status": "fail",
+            "message": "Handwerkskammer not found"
+        })),
+    )
+}
+
+// Validates the fields and lets Postgres compile the pattern and match the
+// example against it, as the submission check does
+async fn validate_handwerkskammer(
+    data: &AppState,
+    body: &HandwerkskammerSchema,
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    let errors = body.validate();
+    if !errors.is_empty() {
+        return Err(validation_error_response(errors));
+    }
+
+    let matches = sqlx::query_scalar!(
+        r#"SELECT $1::text ~ $2::text AS "matches!""#,
+        body.example.trim(),
+        body.number_pattern.trim()
+    )
+    .fetch_one(&data.db)
+    .await;
+
+    match matches {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(validation_error_response(vec![FieldError::invalid_format(
+            "example",
+            "a number matching number_pattern",
+        )])),
+        // invalid_regular_expression
+        Err(sqlx::Error::Database(e)) if e.code().as_deref() == Some("2201B") => {
+            Err(validation_error_response(vec![FieldError::invalid_format(
+                "number_pattern",
+                "a POSIX regular expression",
+            )]))
+        }
+        Err(e) => Err(internal_error(e)),
+    }
+}
+
+pub async fn create_handwerkskammer(
+    State(data): State<Arc<AppState>>,
+    AuthenticatedViewer { is_admin, .. }: AuthenticatedViewer,
+    Json(body): Json<HandwerkskammerSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    if !is_admin {
+        return Err(only_admins_manage_handwerkskammern());
+    }
+
+    validate_handwerkskammer(&data, &body).await?;
+
+    let handwerkskammer = sqlx::query!(
+        r#"
+        INSERT INTO handwerkskammern (name, number_pattern, example)
+        VALUES ($1, $2, $3)
+        RETURNING id, name, number_pattern, example
+        "#,
+        body.name.trim(),
+        body.number_pattern.trim(),
+        body.example.trim()
+    )
+    .fetch_one(&data.db)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(e) if e.is_unique_violation() => handwerkskammer_exists(),
+        e => internal_error(e),
+    })?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(json!({
+            "status": "success",
+            "data": {
+                "id": handwerkskammer.id,
+                "name": handwerkskammer.name,
+                "number_pattern": handwerkskammer.number_pattern,
+                "example": handwerkskammer.example
+            }
+        })),
+    ))
+}
+
+// Pending submissions are checked against the new pattern when an admin
+// reviews them; verified ones stay verified
+pub async fn update_handwerkskammer(
+    State(data): State<Arc<AppState>>,
+    AuthenticatedViewer { is_admin, .. }: AuthenticatedViewer,
+    Path(handwerkskammer_id): Path<Uuid>,
+    Json(body): Json<HandwerkskammerSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    if !is_admin {
+        return Err(only_admins_manage_handwerkskammern());
+    }
+
+    validate_handwerkskammer(&data, &body).await?;
+
+    let handwerkskammer = sqlx::query!(
+        r#"
+        UPDATE handwerkskammern
+        SET name = $2, number_pattern = $3, example = $4,
+            updated_at = NOW(), version = version % 32767 + 1
+        WHERE id = $1
+        RETURNING id, name, number_pattern, example
+        "#,
+        handwerkskammer_id,
+        body.name.trim(),
+        body.number_pattern.trim(),
+        body.example.trim()
+    )
+    .fetch_optional(&data.db)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(e) if e.is_unique_violation() => handwerkskammer_exists(),
+        e => internal_error(e),
+    })?
+    .ok_or_else(handwerkskammer_not_found)?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "data": {
+            "id": handwerkskammer.id,
+            "name": handwerkskammer.name,
+            "number_pattern": handwerkskammer.number_pattern,
+            "example": handwerkskammer.example
+        }
+    })))
+}
+
+// Chambers with submissions are kept so that their verifications stay
+// traceable
+pub async fn delete_handwerkskammer(
+    State(data): State<Arc<AppState>>,
+    AuthenticatedViewer { is_admin, .. }: AuthenticatedViewer,
+    Path(handwerkskammer_id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    if !is_admin {
+        return Err(only_admins_manage_handwerkskammern());
+    }
+
+    let result = sqlx::query!(
+        "DELETE FROM handwerkskammern WHERE id = $1",
+        handwerkskammer_id
+    )
+    .execute(&data.db)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(e) if e.is_foreign_key_violation() => (
+            StatusCode::CONFLICT,
+            Json(json!({
+                "status": "fail",
+                "message": "Handwerkskammer has Handwerkskarte submissions"
+            })),
+        ),
+        e => internal_error(e),
+    })?;
+
+    if result.rows_affected() == 0 {
+        return Err(handwerkskammer_not_found());
+    }
+
+    Ok(Json(json!({
+        "status": "success",
+        "message": "Handwerkskammer deleted."
+    })))
+}
+
+// Owner submits a scan of the Handwerkskarte for the number currently stored
+// on the profile. Earlier pending submissions are replaced.
+pub async fn submit_handwerkskarte(
+    State(data): State<Arc<AppState>>,
+    AuthenticatedViewer {
+        viewer_id,
+        is_admin,
+    }: AuthenticatedViewer,
+    Path(profile_id): Path<Uuid>,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    ensure_can_edit_profile(&data.db, profile_id, viewer_id, is_admin).await?;
+
+    let mut handwerkskammer = None;
+    let mut scan: Option<(ScanFormat, Bytes)> = None;
+
+    while let Some(field) = multipart.next_field().await.map_err(internal_error)? {
+        match field.name().unwrap_or_default() {
+            "handwerkskammer" => {
+                handwerkskammer = Some(field.text().await.map_err(internal_error)?);
+            }
+            "scan" => {
+                scan = Some(read_scan_field(field).await?);
+            }
+            other => {
+                return Err(validation_error_response(vec![FieldError::unknown_field(
+                    other,
+                )]));
+            }
+        }
+    }
+
+    let mut errors = Vec::new();
+    let handwerkskammer = handwerkskammer
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty());
+    if handwerkskammer.is_none() {
+        errors.push(FieldError::required("handwerkskammer"));
+    }
+    if scan.is_none() {
+        errors.push(FieldError::required("scan"));
+    }
+    let (Some(handwerkskammer), Some((scan_format, scan_data))) = (handwerkskammer, scan) else {
+        return Err(validation_error_response(errors));
+    };
+
+    let check = sqlx::query!(
+        r#"
+        SELECT h.id, h.example, TRIM(p.handwerks_karten_nummer) AS "nummer!",
+            TRIM(p.handwerks_karten_nummer) ~ h.number_pattern AS "valid!"
+        FROM handwerkskammern h, profiles p
+        WHERE h.name = $1 AND p.id = $2
+        "#,
+        handwerkskammer,
+        profile_id
+    )
+    .fetch_optional(&data.db)
+    .await
+    .map_err(internal_error)?
+    .ok_or_else(|| {
+        validation_error_response(vec![FieldError::unknown(
+            "handwerkskammer",
+            &handwerkskammer,
+        )])
+    })?;
+
+    if !check.valid {
+        return Err(validation_error_response(vec![FieldError::invalid_format(
+            "handwerks_karten_nummer",
+            &format!("a number like {}", check.example),
+        )]));
+    }
+
+    let mut tx = data.db.begin().await.map_err(internal_error)?;
+
+    sqlx::query!(
+        "DELETE FROM handwerkskarten_verifications WHERE profile_id = $1 AND status = 'pending'",
+        profile_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+
+    let verification = sqlx::query!(
+        r#"
+        INSERT INTO handwerkskarten_verifications (profile_id, handwerkskammer_id, nummer, scan_data, scan_content_type)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id
+        "#,
+        profile_id,
+        check.id,
+        check.nummer,
+        scan_data.to_vec(),
+        scan_format.content_type()
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+
+    tx.commit().await.map_err(internal_error)?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(json!({
+            "status": "success",
+            "data": {
+                "id": verification.id,
+                "verification_status": "pending"
+            }
+        })),
+    ))
+}
+
+// Latest submission and badge for the owner
+pub async fn get_handwerkskarte(
+    State(data): State<Arc<AppState>>,
+    AuthenticatedViewer {
+        viewer_id,
+        is_admin,
+    }: AuthenticatedViewer,
+    Path(profile_id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    ensure_can_edit_profile(&data.db, profile_id, viewer_id, is_admin).await?;
+
+    let verification = sqlx::query!(
+        r#"
+        SELECT hv.id, h.name AS handwerkskammer, hv.nummer, hv.status, hv.reason, hv.reviewed_at, hv.created_at,
+            EXISTS (
+                SELECT 1 FROM handwerkskarten_verifications v
+                WHERE v.profile_id = p.id AND v.status = 'verified'
+                    AND v.nummer = TRIM(p.handwerks_karten_nummer)
+            ) AS "verified_craftsman!"
+        FROM handwerkskarten_verifications hv
+        JOIN handwerkskammern h ON hv.handwerkskammer_id = h.id
+        JOIN profiles p ON hv.profile_id = p.id
+        WHERE hv.profile_id = $1
+        ORDER BY hv.created_at DESC
+        LIMIT 1
+        "#,
+        profile_id
+    )
+    .fetch_optional(&data.db)
+    .await
+    .map_err(internal_error)?;
+
+    let response_data = match verification {
+        Some(verification) => json!({
+            "id": verification.id,
+            "handwerkskammer": verification.handwerkskammer,
+            "nummer": verification.nummer,
+            "verification_status": verification.status,
+            "reason": verification.reason,
+            "reviewedAt": verification.reviewed_at,
+            "createdAt": verification.created_at,
+            "verified_craftsman": verification.verified_craftsman,
+            "_links": {
+                "scan": format!("{}/api/handwerkskarten/{}/scan", data.url, verification.id)
+            }
+        }),
+        None => json!({ "verified_craftsman": false }),
+    };
+
+    Ok(Json(json!({
+        "status": "success",
+        "data": response_data
+    })))
+}
+
+// Scans are only served to the owner and admins, never cached
+pub async fn get_handwerkskarte_scan(
+    State(data): State<Arc<AppState>>,
+    AuthenticatedViewer {
+        viewer_id,
+        is_admin,
+    }: AuthenticatedViewer,
+    Path(verification_id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let scan = sqlx::query!(
+        r#"
+        SELECT hv.profile_id, hv.scan_data, hv.scan_content_type
+        FROM handwerkskarten_verifications hv
+        WHERE hv.id = $1
+        "#,
+        verification_id
+    )
+    .fetch_optional(&data.db)
+    .await
+    .map_err(internal_error)?
+    .ok_or((
+        StatusCode::NOT_FOUND,
+        Json(json!({ "status": "fail", "message": "Scan not found" })),
+    ))?;
+
+    ensure_can_edit_profile(&data.db, scan.profile_id, viewer_id, is_admin).await?;
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_str(&scan.scan_content_type)
+            .unwrap_or(HeaderValue::from_static("application/octet-stream")),
+    );
+    headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static("private, no-store"),
+    );
+
+    Ok((headers, scan.scan_data))
+}
+
+pub async fn get_pending_handwerkskarten(
+    State(data): State<Arc<AppState>>,
+    AuthenticatedViewer { is_admin, .. }: AuthenticatedViewer,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    if !is_admin {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            Json(json!({
+                "status": "fail",
+                "message": "Forbidden."
+            })),
+        ));
+    }
+
+    let rows = sqlx::query!(
+        r#"
+        SELECT hv.id, hv.profile_id, p.name AS profile_name, h.name AS handwerkskammer, hv.nummer, hv.created_at
+        FROM handwerkskarten_verifications hv
+        JOIN handwerkskammern h ON hv.handwerkskammer_id = h.id
+        JOIN profiles p ON hv.profile_id = p.id
+        WHERE hv.status = 'pending'
+        ORDER BY hv.created_at
+        "#
+    )
+    .fetch_all(&data.db)
+    .await
+    .map_err(internal_error)?;
+
+    let response_data: Vec<serde_json::Value> = rows
+        .into_iter()
+        .map(|row| {
+            json!({
+                "id": row.id,
+                "profile_id": row.profile_id,
+                "profile_name": row.profile_name,
+                "handwerkskammer": row.handwerkskammer,
+                "nummer": row.nummer,
+                "createdAt": row.created_at,
+                "_links": {
+                    "profile": format!("{}/api/profile/{}", data.url, row.profile_id),
+                    "scan": format!("{}/api/handwerkskarten/{}/scan", data.url, row.id)
+                }
+            })
+        })
+        .collect();
+
+    Ok(Json(json!({
+        "status": "success",
+        "data": response_data
+    })))
+}
+
+async fn review_handwerkskarte(
+    data: &AppState,
+    verification_id: Uuid,
+    reviewer_id: Uuid,
+    status: &str,
+    reason: Option<&str>,
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE handwerkskarten_verifications
+        SET status = $2, reason = $3, reviewed_by = $4, reviewed_at = NOW(),
+            updated_at = NOW(), version = version % 32767 + 1
+        WHERE id = $1 AND status = 'pending'
+        "#,
+        verification_id,
+        status,
+        reason,
+        reviewer_id
+    )
+    .execute(&data.db)
+    .await
+    .map_err(internal_error)?;
+
+    if result.rows_affected() == 0 {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({
+                "status": "fail",
+                "message": "No pending verification found"
+            })),
+        ));
+    }
+
+    Ok(())
+}
+
+pub async fn verify_handwerkskarte(
+    State(data): State<Arc<AppState>>,
+    AuthenticatedViewer {
+        viewer_id,
+        is_admin,
+    }: AuthenticatedViewer,
+    Path(verification_id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    if !is_admin {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            Json(json!({
+                "status": "fail",
+                "message": "Only admins can verify Handwerkskarten."
+            })),
+        ));
+    }
+
+    review_handwerkskarte(&data, verification_id, viewer_id, "verified", None).await?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "message": "Handwerkskarte verified."
+    })))
+}
+
+pub async fn reject_handwerkskarte(
+    State(data): State<Arc<AppState>>,
+    AuthenticatedViewer {
+        viewer_id,
+        is_admin,
+    }: AuthenticatedViewer,
+    Path(verification_id): Path<Uuid>,
+    Json(body): Json<ModerationSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    if !is_admin {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            Json(json!({
+                "status": "fail",
+                "message": "Only admins can reject Handwerkskarten."
+            })),
+        ));
+    }
+
+    let reason = required_reason(&body)?;
+
+    review_handwerkskarte(&data, verification_id, viewer_id, "rejected", Some(reason)).await?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "message": "Handwerkskarte rejected."
+    })))
+}
@@ -0,0 +1,563 @@
+use std::sync::Arc;
+
+use axum::{
+    body::Bytes,
+    extract::{Multipart, Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde_json::json;
+use sqlx::{Pool, Postgres};
+use uuid::Uuid;
+
+use crate::{
+    blob_store::{inquiry_photo_key, BlobStoreError},
+    media::{self, media_error_response},
+    model::{InquiryModel, InquiryStatus},
+    schema::{InquiryInput, InquiryQuery, InquiryStatusSchema},
+    validation::{validation_error_response, FieldError},
+    AppState,
+};
+
+use super::{
+    auth::AuthenticatedViewer,
+    photo::{delete_blobs, put_blobs},
+    profile::ensure_can_edit_profile,
+    response_time::{record_contact, record_first_response},
+};
+
+const INQUIRY_PHOTOS_MAX: usize = 5;
+
+// Source name of inquiries in profile_contacts
+const INQUIRY_SOURCE: &str = "inquiry";
+
+fn internal_error(e: impl std::fmt::Debug) -> (StatusCode, Json<serde_json::Value>) {
+    eprintln!("inquiry error: {:?}", e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(json!({ "status": "fail", "message": "Internal Server Error" })),
+    )
+}
+
+fn inquiry_not_found() -> (StatusCode, Json<serde_json::Value>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({ "status": "fail", "message": "Inquiry not found" })),
+    )
+}
+
+#[derive(Default)]
+struct InquiryFilter {
+    id: Option<Uuid>,
+    profile_id: Option<Uuid>,
+    viewer_id: Option<Uuid>,
+    status: Option<String>,
+}
+
+async fn load_inquiries(
+    db: &Pool<Postgres>,
+    filter: InquiryFilter,
+) -> Result<Vec<InquiryModel>, sqlx::Error> {
+    sqlx::query_as!(
+        InquiryModel,
+        r#"
+        SELECT i.id, i.profile_id, p.name AS profile_name, v.first_name AS customer_name,
+            c.name AS "craft?", i.message, i.location, i.timeframe, i.status,
+            COALESCE(
+                (SELECT array_agg(ip.id ORDER BY ip.created_at, ip.id)
+                 FROM inquiry_photos ip
+                 WHERE ip.inquiry_id = i.id),
+                '{}'
+            ) AS "photos!",
+            i.created_at, i.status_changed_at
+        FROM inquiries i
+        JOIN profiles p ON p.id = i.profile_id
+        JOIN viewers v ON v.id = i.viewer_id
+        LEFT JOIN crafts c ON c.id = i.craft_id
+        WHERE ($1::uuid IS NULL OR i.id = $1)
+            AND ($2::uuid IS NULL OR i.profile_id = $2)
+            AND ($3::uuid IS NULL OR i.viewer_id = $3)
+            AND ($4::text IS NULL OR i.status = $4)
+        ORDER BY i.created_at DESC
+        "#,
+        filter.id,
+        filter.profile_id,
+        filter.viewer_id,
+        filter.status
+    )
+    .fetch_all(db)
+    .await
+}
+
+// The customer who sent the inquiry and the owner of the profile it went to
+// may see it; only the owner (or an admin) may change its status. Returns
+// whether the viewer acts as the owner.
+async fn inquiry_access(
+    db: &Pool<Postgres>,
+    inquiry_id: Uuid,
+    viewer_id: Uuid,
+    is_admin: bool,
+) -> Result<bool, (StatusCode, Json<serde_json::Value>)> {
+    let inquiry = sqlx::query!(
+        r#"
+        SELECT i.viewer_id AS customer_id, p.viewer_id AS owner_id
+        FROM inquiries i
+        JOIN profiles p ON p.id = i.profile_id
+        WHERE i.id = $1
+        "#,
+        inquiry_id
+    )
+    .fetch_optional(db)
+    .await
+    .map_err(internal_error)?
+    .ok_or_else(inquiry_not_found)?;
+
+    let is_owner = is_admin || inquiry.owner_id == Some(viewer_id);
+    if !is_owner && inquiry.customer_id != viewer_id {
+        return Err(inquiry_not_found());
+    }
+
+    Ok(is_owner)
+}
+
+fn parse_status_filter(
+    query: &InquiryQuery,
+) -> Result<Option<String>, (StatusCode, Json<serde_json::Value>)> {
+    match query.status.as_deref() {
+        None => Ok(None),
+        Some(status) => match InquiryStatus::parse(status) {
+            Some(status) => Ok(Some(status.as_str().to_string())),
+            None => Err(validation_error_response(vec![FieldError::invalid_format(
+                "status",
+                "one of new, answered, declined, done",
+            )])),
+        },
+    }
+}
+
+pub async fn create_inquiry(
+    State(data): State<Arc<AppState>>,
+    AuthenticatedViewer { viewer_id, .. }: AuthenticatedViewer,
+    Path(profile_id): Path<Uuid>,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let mut input = InquiryInput::default();
+    let mut photos: Vec<(String, Bytes)> = Vec::new();
+    let mut errors = Vec::new();
+
+    while let Some(field) = multipart.next_field().await.map_err(internal_error)? {
+        let name = field.name().unwrap_or_default().to_string();
+        match name.as_str() {
+            "photos" => {
+                let (file_name, _format, bytes) =
+                    media::read_image_field(field, &data.upload_limits)
+                        .await
+                        .map_err(media_error_response)?;
+                let file_name = file_name.unwrap_or_else(|| "photo".to_string());
+                photos.push((file_name, bytes));
+            }
+            "message" | "craft" | "location" | "timeframe" => {
+                let text = Some(field.text().await.map_err(internal_error)?);
+                match name.as_str() {
+                    "message" => input.message = text,
+                    "craft" => input.craft = text,
+                    "location" => input.location = text,
+                    _ => input.timeframe = text,
+                }
+            }
+            other => errors.push(FieldError::unknown_field(other)),
+        }
+    }
+
+    errors.extend(input.validate());
+    if photos.len() > INQUIRY_PHOTOS_MAX {
+        errors.push(FieldError::new(
+            "photos",
+            "too_many",
+            format!("at most {} photos are allowed", INQUIRY_PHOTOS_MAX),
+        ));
+    }
+
+    let profile = sqlx::query!(
+        r#"
+        SELECT p.viewer_id, COALESCE(v.email, p.email) AS "email!",
+            COALESCE(v.first_name, p.name) AS "owner_name!"
+        FROM profiles p
+        LEFT JOIN viewers v ON v.id = p.viewer_id
+        WHERE p.id = $1 AND p.status = 'approved'
+        "#,
+        profile_id
+    )
+    .fetch_optional(&data.db)
+    .await
+    .map_err(internal_error)?
+    .ok_or((
+        StatusCode::NOT_FOUND,
+        Json(json!({ "status": "fail", "message": "Profile not found" })),
+    ))?;
+
+    if profile.viewer_id == Some(viewer_id) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "status": "fail",
+                "message": "You cannot send an inquiry to your own profile."
+            })),
+        ));
+    }
+
+    let craft = input
+        .craft
+        .as_deref()
+        .map(str::trim)
+        .filter(|craft| !craft.is_empty());
+    let craft_id = match craft {
+        None => None,
+        Some(craft) => {
+            let craft_id = sqlx::query_scalar!("SELECT id FROM crafts WHERE name = $1", craft)
+                .fetch_optional(&data.db)
+                .await
+                .map_err(internal_error)?;
+            if craft_id.is_none() {
+                errors.push(FieldError::unknown("craft", craft));
+            }
+            craft_id
+        }
+    };
+
+    if !errors.is_empty() {
+        return Err(validation_error_response(errors));
+    }
+
+    // The photos go to the blob store first; their keys need the inquiry id
+    let inquiry_id = Uuid::new_v4();
+    let policy = data.image_policy;
+    let mut inquiry_photos = Vec::with_capacity(photos.len());
+    let mut blobs = Vec::with_capacity(photos.len());
+    for (file_name, bytes) in photos {
+        let compressed = data
+            .image_workers
+            .run(move || media::compress(&bytes, &policy))
+            .await
+            .map_err(media_error_response)?;
+        let photo_id = Uuid::new_v4();
+        let storage_key = inquiry_photo_key(inquiry_id, photo_id);
+        inquiry_photos.push((
+            photo_id,
+            file_name,
+            storage_key.clone(),
+            compressed.len() as i32,
+        ));
+        blobs.push((storage_key, "image/jpeg", compressed));
+    }
+    let written = put_blobs(data.blob_store.as_ref(), blobs)
+        .await
+        .map_err(internal_error)?;
+
+    let message = input.message.unwrap_or_default().trim().to_string();
+    let location = input.location.unwrap_or_default().trim().to_string();
+    let timeframe = input.timeframe.unwrap_or_default().trim().to_string();
+
+    let result = async {
+        let mut tx = data.db.begin().await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO inquiries (id, profile_id, viewer_id, craft_id, message, location, timeframe)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+            inquiry_id,
+            profile_id,
+            viewer_id,
+            craft_id,
+            message,
+            location,
+            timeframe
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        for (photo_id, file_name, storage_key, byte_size) in &inquiry_photos {
+            sqlx::query!(
+                r#"
+                INSERT INTO inquiry_photos (id, inquiry_id, file_name, content_type, storage_key, byte_size)
+                VALUES ($1, $2, $3, 'image/jpeg', $4, $5)
+                "#,
+                photo_id,
+                inquiry_id,
+                file_name,
+                storage_key,
+                byte_size
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        record_contact(&mut tx, profile_id, viewer_id, INQUIRY_SOURCE, inquiry_id).await?;
+
+        tx.commit().await
+    }
+    .await;
+    if let Err(e) = result {
+        delete_blobs(data.blob_store.as_ref(), &written).await;
+        return Err(internal_error(e));
+    }
+
+    let customer_name =
+        sqlx::query_scalar!("SELECT first_name FROM viewers WHERE id = $1", viewer_id)
+            .fetch_one(&data.db)
+            .await
+            .map_err(internal_error)?;
+
+    let email_result = data.email_manager.send_inquiry_email(
+        &profile.email,
+        &format!("{}/inquiries/{}", data.url, inquiry_id),
+        &profile.owner_name,
+        &customer_name,
+        craft,
+        &message,
+    );
+    if let Err(e) = email_result {
+        println!("create_inquiry: E-Mail failed: {:?}", e);
+    }
+
+    let inquiry = load_inquiries(
+        &data.db,
+        InquiryFilter {
+            id: Some(inquiry_id),
+            ..Default::default()
+        },
+    )
+    .await
+    .map_err(internal_error)?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(json!({
+            "status": "success",
+            "data": inquiry.first()
+        })),
+    ))
+}
+
+// Inbox of the profile owner, newest first
+pub async fn get_profile_inquiries(
+    State(data): State<Arc<AppState>>,
+    AuthenticatedViewer {
+        viewer_id,
+        is_admin,
+    }: AuthenticatedViewer,
+    Path(profile_id): Path<Uuid>,
+    Query(query): Query<InquiryQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    ensure_can_edit_profile(&data.db, profile_id, viewer_id, is_admin).await?;
+
+    let inquiries = load_inquiries(
+        &data.db,
+        InquiryFilter {
+            profile_id: Some(profile_id),
+            status: parse_status_filter(&query)?,
+            ..Default::default()
+        },
+    )
+    .await
+    .map_err(internal_error)?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "data": inquiries
+    })))
+}
+
+// Inquiries the viewer sent as a customer, newest first
+pub async fn get_sent_inquiries(
+    State(data): State<Arc<AppState>>,
+    AuthenticatedViewer { viewer_id, .. }: AuthenticatedViewer,
+    Query(query): Query<InquiryQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let inquiries = load_inquiries(
+        &data.db,
+        InquiryFilter {
+            viewer_id: Some(viewer_id),
+            status: parse_status_filter(&query)?,
+            ..Default::default()
+        },
+    )
+    .await
+    .map_err(internal_error)?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "data": inquiries
+    })))
+}
+
+pub async fn get_inquiry(
+    State(data): State<Arc<AppState>>,
+    AuthenticatedViewer {
+        viewer_id,
+        is_admin,
+    }: AuthenticatedViewer,
+    Path(inquiry_id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    inquiry_access(&data.db, inquiry_id, viewer_id, is_admin).await?;
+
+    let inquiry = load_inquiries(
+        &data.db,
+        InquiryFilter {
+            id: Some(inquiry_id),
+            ..Default::default()
+        },
+    )
+    .await
+    .map_err(internal_error)?
+    .pop()
+    .ok_or_else(inquiry_not_found)?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "data": inquiry
+    })))
+}
+
+// Owner marks an inquiry answered, declined or done. Answering or declining
+// counts as the profile's first response.
+pub async fn update_inquiry_status(
+    State(data): State<Arc<AppState>>,
+    AuthenticatedViewer {
+        viewer_id,
+        is_admin,
+    }: AuthenticatedViewer,
+    Path(inquiry_id): Path<Uuid>,
+    Json(body): Json<InquiryStatusSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let is_owner = inquiry_access(&data.db, inquiry_id, viewer_id, is_admin).await?;
+    if !is_owner {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "status": "fail",
+                "message": "Only the profile owner can change the inquiry status."
+            })),
+        ));
+    }
+
+    let status = InquiryStatus::parse(&body.status)
+        .filter(|status| *status != InquiryStatus::New)
+        .ok_or_else(|| {
+            validation_error_response(vec![FieldError::invalid_format(
+                "status",
+                "one of answered, declined, done",
+            )])
+        })?;
+    let allowed_from: Vec<String> = status
+        .allowed_from()
+        .iter()
+        .map(|status| status.as_str().to_string())
+        .collect();
+
+    let mut tx = data.db.begin().await.map_err(internal_error)?;
+
+    let previous = sqlx::query_scalar!(
+        r#"
+        UPDATE inquiries i
+        SET status = $2, status_changed_at = NOW(), updated_at = NOW(),
+            version = i.version % 32767 + 1
+        FROM (SELECT id, status FROM inquiries WHERE id = $1 FOR UPDATE) old
+        WHERE i.id = old.id AND old.status = ANY($3)
+        RETURNING old.status
+        "#,
+        inquiry_id,
+        status.as_str(),
+        &allowed_from
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+
+    if previous.is_none() {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(json!({
+                "status": "fail",
+                "message": format!("Inquiry cannot change to {}.", status.as_str())
+            })),
+        ));
+    }
+
+    if matches!(status, InquiryStatus::Answered | InquiryStatus::Declined) {
+        record_first_response(&mut tx, INQUIRY_SOURCE, inquiry_id)
+            .await
+            .map_err(internal_error)?;
+    }
+
+    tx.commit().await.map_err(internal_error)?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "data": { "inquiry_status": status.as_str() }
+    })))
+}
+
+// Photos are private to the two parties of the inquiry
+pub async fn get_inquiry_photo(
+    State(data): State<Arc<AppState>>,
+    AuthenticatedViewer {
+        viewer_id,
+        is_admin,
+    }: AuthenticatedViewer,
+    Path((inquiry_id, photo_id)): Path<(Uuid, Uuid)>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    inquiry_access(&data.db, inquiry_id, viewer_id, is_admin).await?;
+
+    let photo = sqlx::query!(
+        r#"
+        SELECT file_name, content_type, storage_key, photo_data
+        FROM inquiry_photos
+        WHERE id = $1 AND inquiry_id = $2
+        "#,
+        photo_id,
+        inquiry_id
+    )
+    .fetch_optional(&data.db)
+    .await
+    .map_err(internal_error)?
+    .ok_or((
+        StatusCode::NOT_FOUND,
+        Json(json!({ "status": "fail", "message": "Photo not found" })),
+    ))?;
+
+    let photo_data = match (&photo.storage_key, photo.photo_data) {
+        (Some(storage_key), _) => match data.blob_store.get(storage_key).await {
+            Ok(photo_data) => photo_data,
+            Err(BlobStoreError::NotFound) => {
+                return Err((
+                    StatusCode::NOT_FOUND,
+                    Json(json!({ "status": "fail", "message": "Photo not found" })),
+                ))
+            }
+            Err(e) => return Err(internal_error(e)),
+        },
+        // Not yet moved by `mano migrate-photo-blobs`
+        (None, Some(photo_data)) => photo_data,
+        (None, None) => return Err(internal_error("inquiry photo without data")),
+    };
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_str(&photo.content_type).map_err(internal_error)?,
+    );
+    headers.insert(
+        header::CONTENT_DISPOSITION,
+        HeaderValue::from_str(&format!("inline; filename=\"{}\"", photo.file_name))
+            .unwrap_or(HeaderValue::from_static("inline")),
+    );
+    headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static("private, no-store"),
+    );
+
+    Ok((headers, photo_data))
+}
@@ -0,0 +1,948 @@
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
+
+use axum::{
+    body::Bytes,
+    extract::{Multipart, Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use sqlx::{PgConnection, Pool, Postgres};
+use uuid::Uuid;
+
+use crate::{
+    blob_store::{message_attachment_key, BlobStoreError},
+    media::{self, media_error_response},
+    model::ThreadSummaryModel,
+    schema::{ContactShareSchema, PageQuery},
+    validation::{validation_error_response, FieldError},
+    AppState,
+};
+
+use super::{
+    auth::AuthenticatedViewer,
+    photo::{delete_blobs, put_blobs},
+    response_time::{record_contact, record_first_response},
+};
+
+const MESSAGE_BODY_MAX: usize = 4000;
+const MESSAGE_ATTACHMENTS_MAX: usize = 5;
+const PAGE_LIMIT_DEFAULT: i64 = 20;
+const PAGE_LIMIT_MAX: i64 = 100;
+
+// Unread messages are emailed once they are this old, so that people who
+// are chatting live do not get a digest for every message
+const DIGEST_GRACE_MINUTES: i32 = 10;
+const DIGEST_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+// Source name of threads in profile_contacts
+const THREAD_SOURCE: &str = "thread";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ThreadRole {
+    Customer,
+    Owner,
+}
+
+impl ThreadRole {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ThreadRole::Customer => "customer",
+            ThreadRole::Owner => "owner",
+        }
+    }
+}
+
+struct ThreadAccess {
+    role: ThreadRole,
+    inquiry_id: Option<Uuid>,
+}
+
+fn internal_error(e: impl std::fmt::Debug) -> (StatusCode, Json<serde_json::Value>) {
+    eprintln!("messages error: {:?}", e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(json!({ "status": "fail", "message": "Internal Server Error" })),
+    )
+}
+
+fn thread_not_found() -> (StatusCode, Json<serde_json::Value>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({ "status": "fail", "message": "Thread not found" })),
+    )
+}
+
+// Limit and offset for `?page=&limit=`, pages start at 1
+fn page_bounds(query: &PageQuery) -> (i64, i64) {
+    let limit = query
+        .limit
+        .unwrap_or(PAGE_LIMIT_DEFAULT)
+        .clamp(1, PAGE_LIMIT_MAX);
+    let page = query.page.unwrap_or(1).max(1);
+    (limit, (page - 1) * limit)
+}
+
+// Only the two participants may read a thread. Admins act as the owner of
+// profiles that no viewer owns, but cannot read other people's messages.
+async fn thread_access(
+    db: &Pool<Postgres>,
+    thread_id: Uuid,
+    viewer_id: Uuid,
+    is_admin: bool,
+) -> Result<ThreadAccess, (StatusCode, Json<serde_json::Value>)> {
+    let thread = sqlx::query!(
+        r#"
+        SELECT t.customer_id, t.inquiry_id, p.viewer_id AS owner_id
+        FROM message_threads t
+        JOIN profiles p ON p.id = t.profile_id
+        WHERE t.id = $1
+        "#,
+        thread_id
+    )
+    .fetch_optional(db)
+    .await
+    .map_err(internal_error)?
+    .ok_or_else(thread_not_found)?;
+
+    let role = if thread.customer_id == viewer_id {
+        ThreadRole::Customer
+    } else if thread.owner_id == Some(viewer_id) || (thread.owner_id.is_none() && is_admin) {
+        ThreadRole::Owner
+    } else {
+        return Err(thread_not_found());
+    };
+
+    Ok(ThreadAccess {
+        role,
+        inquiry_id: thread.inquiry_id,
+    })
+}
+
+// Whether the viewer may see the profile's email and phone number: its owner,
+// admins, and customers who agreed with the owner to share contact details
+pub async fn can_see_contact(
+    db: &Pool<Postgres>,
+    profile_id: Uuid,
+    viewer: Option<&AuthenticatedViewer>,
+) -> Result<bool, sqlx::Error> {
+    let Some(viewer) = viewer else {
+        return Ok(false);
+    };
+    if viewer.is_admin {
+        return Ok(true);
+    }
+
+    sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS (SELECT 1 FROM profiles WHERE id = $1 AND viewer_id = $2)
+            OR EXISTS (
+                SELECT 1 FROM message_threads
+                WHERE profile_id = $1 AND customer_id = $2
+                    AND customer_shares_contact AND owner_shares_contact
+            ) AS "allowed!"
+        "#,
+        profile_id,
+        viewer.viewer_id
+    )
+    .fetch_one(db)
+    .await
+}
+
+async fn load_threads(
+    db: &Pool<Postgres>,
+    viewer: &AuthenticatedViewer,
+    thread_id: Option<Uuid>,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<ThreadSummaryModel>, sqlx::Error> {
+    sqlx::query_as!(
+        ThreadSummaryModel,
+        r#"
+        SELECT t.id, t.profile_id, p.name AS profile_name, v.first_name AS customer_name,
+            t.inquiry_id, t.customer_id = $1 AS "is_customer!",
+            t.customer_shares_contact, t.owner_shares_contact,
+            (SELECT m.body FROM messages m
+             WHERE m.thread_id = t.id
+             ORDER BY m.created_at DESC
+             LIMIT 1) AS "last_message?",
+            t.last_message_at,
+            (SELECT COUNT(*) FROM messages m
+             WHERE m.thread_id = t.id
+                AND m.sender_role = CASE WHEN t.customer_id = $1 THEN 'owner' ELSE 'customer' END
+                AND m.created_at > COALESCE(
+                    CASE WHEN t.customer_id = $1 THEN t.customer_last_read_at ELSE t.owner_last_read_at END,
+                    '-infinity'::timestamptz
+                )) AS "unread_count!",
+            t.created_at
+        FROM message_threads t
+        JOIN profiles p ON p.id = t.profile_id
+        JOIN viewers v ON v.id = t.customer_id
+        WHERE (t.customer_id = $1 OR p.viewer_id = $1 OR (p.viewer_id IS NULL AND $2))
+            AND ($3::uuid IS NULL OR t.id = $3)
+        ORDER BY t.last_message_at DESC NULLS LAST, t.created_at DESC
+        LIMIT $4 OFFSET $5
+        "#,
+        viewer.viewer_id,
+        viewer.is_admin,
+        thread_id,
+        limit,
+        offset
+    )
+    .fetch_all(db)
+    .await
+}
+
+// A single thread with the other side's contact details once both sides
+// share them
+async fn thread_response(
+    db: &Pool<Postgres>,
+    viewer: &AuthenticatedViewer,
+    thread_id: Uuid,
+) -> Result<serde_json::Value, (StatusCode, Json<serde_json::Value>)> {
+    let thread = load_threads(db, viewer, Some(thread_id), 1, 0)
+        .await
+        .map_err(internal_error)?
+        .pop()
+        .ok_or_else(thread_not_found)?;
+
+    let contact = if thread.customer_shares_contact && thread.owner_shares_contact {
+        let contact = sqlx::query!(
+            r#"
+            SELECT p.email AS profile_email, p.telefon AS profile_telefon,
+                v.email AS customer_email, v.first_name, v.last_name
+            FROM message_threads t
+            JOIN profiles p ON p.id = t.profile_id
+            JOIN viewers v ON v.id = t.customer_id
+            WHERE t.id = $1
+            "#,
+            thread_id
+        )
+        .fetch_one(db)
+        .await
+        .map_err(internal_error)?;
+
+        if thread.is_customer {
+            json!({
+                "email": contact.profile_email.to_lowercase(),
+                "telefon": contact.profile_telefon
+            })
+        } else {
+            json!({
+                "name": format!("{} {}", contact.first_name, contact.last_name),
+                "email": contact.customer_email.to_lowercase()
+            })
+        }
+    } else {
+        serde_json::Value::Null
+    };
+
+    let mut response = json!(thread);
+    response["contact"] = contact;
+    Ok(response)
+}
+
+// Opens the thread between a profile and a customer, or returns the existing
+// one. The bool tells whether it was just created.
+async fn open_thread(
+    conn: &mut PgConnection,
+    profile_id: Uuid,
+    customer_id: Uuid,
+    inquiry_id: Option<Uuid>,
+) -> Result<(Uuid, bool), sqlx::Error> {
+    let thread = sqlx::query!(
+        r#"
+        INSERT INTO message_threads (profile_id, customer_id, inquiry_id)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (profile_id, customer_id) DO UPDATE
+        SET inquiry_id = COALESCE(message_threads.inquiry_id, EXCLUDED.inquiry_id)
+        RETURNING id, (xmax = 0) AS "created!"
+        "#,
+        profile_id,
+        customer_id,
+        inquiry_id
+    )
+    .fetch_one(conn)
+    .await?;
+
+    Ok((thread.id, thread.created))
+}
+
+// Customer starts a conversation with a profile
+pub async fn create_profile_thread(
+    State(data): State<Arc<AppState>>,
+    viewer: AuthenticatedViewer,
+    Path(profile_id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let owner_id = sqlx::query_scalar!(
+        "SELECT viewer_id FROM profiles WHERE id = $1 AND status = 'approved'",
+        profile_id
+    )
+    .fetch_optional(&data.db)
+    .await
+    .map_err(internal_error)?
+    .ok_or((
+        StatusCode::NOT_FOUND,
+        Json(json!({ "status": "fail", "message": "Profile not found" })),
+    ))?;
+
+    if owner_id == Some(viewer.viewer_id) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "status": "fail",
+                "message": "You cannot message your own profile."
+            })),
+        ));
+    }
+
+    // A thread is only counted as a contact together with its creation
+    let mut tx = data.db.begin().await.map_err(internal_error)?;
+
+    let (thread_id, created) = open_thread(&mut tx, profile_id, viewer.viewer_id, None)
+        .await
+        .map_err(internal_error)?;
+
+    if created {
+        record_contact(
+            &mut tx,
+            profile_id,
+            viewer.viewer_id,
+            THREAD_SOURCE,
+            thread_id,
+        )
+        .await
+        .map_err(internal_error)?;
+    }
+
+    tx.commit().await.map_err(internal_error)?;
+
+    Ok((
+        if created {
+            StatusCode::CREATED
+        } else {
+            StatusCode::OK
+        },
+        Json(json!({
+            "status": "success",
+            "data": thread_response(&data.db, &viewer, thread_id).await?
+        })),
+    ))
+}
+
+// Either side of an inquiry continues it as a conversation
+pub async fn create_inquiry_thread(
+    State(data): State<Arc<AppState>>,
+    viewer: AuthenticatedViewer,
+    Path(inquiry_id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let inquiry = sqlx::query!(
+        r#"
+        SELECT i.profile_id, i.viewer_id AS customer_id, p.viewer_id AS owner_id
+        FROM inquiries i
+        JOIN profiles p ON p.id = i.profile_id
+        WHERE i.id = $1
+        "#,
+        inquiry_id
+    )
+    .fetch_optional(&data.db)
+    .await
+    .map_err(internal_error)?
+    .filter(|inquiry| {
+        inquiry.customer_id == viewer.viewer_id
+            || inquiry.owner_id == Some(viewer.viewer_id)
+            || (inquiry.owner_id.is_none() && viewer.is_admin)
+    })
+    .ok_or((
+        StatusCode::NOT_FOUND,
+        Json(json!({ "status": "fail", "message": "Inquiry not found" })),
+    ))?;
+
+    let (thread_id, created) = open_thread(
+        &mut *data.db.acquire().await.map_err(internal_error)?,
+        inquiry.profile_id,
+        inquiry.customer_id,
+        Some(inquiry_id),
+    )
+    .await
+    .map_err(internal_error)?;
+
+    Ok((
+        if created {
+            StatusCode::CREATED
+        } else {
+            StatusCode::OK
+        },
+        Json(json!({
+            "status": "success",
+            "data": thread_response(&data.db, &viewer, thread_id).await?
+        })),
+    ))
+}
+
+// Threads of the viewer as customer and as profile owner, most recent first
+pub async fn get_threads(
+    State(data): State<Arc<AppState>>,
+    viewer: AuthenticatedViewer,
+    Query(query): Query<PageQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let (limit, offset) = page_bounds(&query);
+
+    let threads = load_threads(&data.db, &viewer, None, limit, offset)
+        .await
+        .map_err(internal_error)?;
+
+    let totals = sqlx::query!(
+        r#"
+        SELECT COUNT(*) AS "total!",
+            COALESCE(SUM(
+                (SELECT COUNT(*) FROM messages m
+                 WHERE m.thread_id = t.id
+                    AND m.sender_role = CASE WHEN t.customer_id = $1 THEN 'owner' ELSE 'customer' END
+                    AND m.created_at > COALESCE(
+                        CASE WHEN t.customer_id = $1 THEN t.customer_last_read_at ELSE t.owner_last_read_at END,
+                        '-infinity'::timestamptz
+                    ))
+            ), 0)::BIGINT AS "unread_total!"
+        FROM message_threads t
+        JOIN profiles p ON p.id = t.profile_id
+        WHERE t.customer_id = $1 OR p.viewer_id = $1 OR (p.viewer_id IS NULL AND $2)
+        "#,
+        viewer.viewer_id,
+        viewer.is_admin
+    )
+    .fetch_one(&data.db)
+    .await
+    .map_err(internal_error)?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "data": threads,
+        "page": offset / limit + 1,
+        "limit": limit,
+        "total": totals.total,
+        "unread_total": totals.unread_total
+    })))
+}
+
+pub async fn get_thread(
+    State(data): State<Arc<AppState>>,
+    viewer: AuthenticatedViewer,
+    Path(thread_id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    thread_access(&data.db, thread_id, viewer.viewer_id, viewer.is_admin).await?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "data": thread_response(&data.db, &viewer, thread_id).await?
+    })))
+}
+
+// Messages newest first. `read` tells for own messages whether the other
+// side has seen them and for the other side's messages whether the viewer
+// has.
+pub async fn get_thread_messages(
+    State(data): State<Arc<AppState>>,
+    viewer: AuthenticatedViewer,
+    Path(thread_id): Path<Uuid>,
+    Query(query): Query<PageQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let access = thread_access(&data.db, thread_id, viewer.viewer_id, viewer.is_admin).await?;
+    let (limit, offset) = page_bounds(&query);
+
+    let messages = sqlx::query!(
+        r#"
+        SELECT m.id, m.sender_role, m.body, m.created_at,
+            COALESCE(
+                (SELECT array_agg(a.id ORDER BY a.created_at, a.id)
+                 FROM message_attachments a
+                 WHERE a.message_id = m.id),
+                '{}'
+            ) AS "attachments!",
+            m.created_at <= COALESCE(
+                CASE WHEN m.sender_role = 'customer' THEN t.owner_last_read_at ELSE t.customer_last_read_at END,
+                '-infinity'::timestamptz
+            ) AS "read!"
+        FROM messages m
+        JOIN message_threads t ON t.id = m.thread_id
+        WHERE m.thread_id = $1
+        ORDER BY m.created_at DESC, m.id
+        LIMIT $2 OFFSET $3
+        "#,
+        thread_id,
+        limit,
+        offset
+    )
+    .fetch_all(&data.db)
+    .await
+    .map_err(internal_error)?;
+
+    let total = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) AS "total!" FROM messages WHERE thread_id = $1"#,
+        thread_id
+    )
+    .fetch_one(&data.db)
+    .await
+    .map_err(internal_error)?;
+
+    let messages: Vec<serde_json::Value> = messages
+        .into_iter()
+        .map(|message| {
+            json!({
+                "id": message.id,
+                "sender_role": message.sender_role,
+                "mine": message.sender_role == access.role.as_str(),
+                "body": message.body,
+                "attachments": message.attachments,
+                "read": message.read,
+                "created_at": message.created_at
+            })
+        })
+        .collect();
+
+    Ok(Json(json!({
+        "status": "success",
+        "data": messages,
+        "page": offset / limit + 1,
+        "limit": limit,
+        "total": total
+    })))
+}
+
+// Multipart with a `body` text and up to five image `attachments`
+pub async fn send_message(
+    State(data): State<Arc<AppState>>,
+    viewer: AuthenticatedViewer,
+    Path(thread_id): Path<Uuid>,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let access = thread_access(&data.db, thread_id, viewer.viewer_id, viewer.is_admin).await?;
+
+    let mut body = String::new();
+    let mut attachments: Vec<(String, Bytes)> = Vec::new();
+    let mut errors = Vec::new();
+
+    while let Some(field) = multipart.next_field().await.map_err(internal_error)? {
+        let name = field.name().unwrap_or_default().to_string();
+        match name.as_str() {
+            "body" => body = field.text().await.map_err(internal_error)?,
+            "attachments" => {
+                let (file_name, _format, bytes) =
+                    media::read_image_field(field, &data.upload_limits)
+                        .await
+                        .map_err(media_error_response)?;
+                let file_name = file_name.unwrap_or_else(|| "attachment".to_string());
+                attachments.push((file_name, bytes));
+            }
+            other => errors.push(FieldError::unknown_field(other)),
+        }
+    }
+
+    let body = body.trim().to_string();
+    if body.is_empty() && attachments.is_empty() {
+        errors.push(FieldError::required("body"));
+    }
+    if body.chars().count() > MESSAGE_BODY_MAX {
+        errors.push(FieldError::too_long("body", MESSAGE_BODY_MAX));
+    }
+    if attachments.len() > MESSAGE_ATTACHMENTS_MAX {
+        errors.push(FieldError::new(
+            "attachments",
+            "too_many",
+            format!(
+                "at most {} attachments are allowed",
+                MESSAGE_ATTACHMENTS_MAX
+            ),
+        ));
+    }
+    if !errors.is_empty() {
+        return Err(validation_error_response(errors));
+    }
+
+    // The attachments go to the blob store first; their keys need the
+    // message id
+    let message_id = Uuid::new_v4();
+    let policy = data.image_policy;
+    let mut message_attachments = Vec::with_capacity(attachments.len());
+    let mut blobs = Vec::with_capacity(attachments.len());
+    for (file_name, bytes) in attachments {
+        let compressed = data
+            .image_workers
+            .run(move || media::compress(&bytes, &policy))
+            .await
+            .map_err(media_error_response)?;
+        let attachment_id = Uuid::new_v4();
+        let storage_key = message_attachment_key(message_id, attachment_id);
+        message_attachments.push((
+            attachment_id,
+            file_name,
+            storage_key.clone(),
+            compressed.len() as i32,
+        ));
+        blobs.push((storage_key, "image/jpeg", compressed));
+    }
+    let written = put_blobs(data.blob_store.as_ref(), blobs)
+        .await
+        .map_err(internal_error)?;
+
+    let result = async {
+        let mut tx = data.db.begin().await?;
+
+        let created_at = sqlx::query_scalar!(
+            r#"
+            INSERT INTO messages (id, thread_id, sender_id, sender_role, body)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING created_at
+            "#,
+            message_id,
+            thread_id,
+            viewer.viewer_id,
+            access.role.as_str(),
+            body
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        for (attachment_id, file_name, storage_key, byte_size) in &message_attachments {
+            sqlx::query!(
+                r#"
+                INSERT INTO message_attachments (id, message_id, file_name, content_type, storage_key, byte_size)
+                VALUES ($1, $2, $3, 'image/jpeg', $4, $5)
+                "#,
+                attachment_id,
+                message_id,
+                file_name,
+                storage_key,
+                byte_size
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        // Writing implies having read the thread up to here
+        sqlx::query!(
+            r#"
+            UPDATE message_threads
+            SET last_message_at = $2,
+                customer_last_read_at = CASE WHEN $3 = 'customer' THEN $2 ELSE customer_last_read_at END,
+                owner_last_read_at = CASE WHEN $3 = 'owner' THEN $2 ELSE owner_last_read_at END,
+                updated_at = NOW(),
+                version = version % 32767 + 1
+            WHERE id = $1
+            "#,
+            thread_id,
+            created_at,
+            access.role.as_str()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        // An owner's message answers the thread and the inquiry it came from
+        if access.role == ThreadRole::Owner {
+            record_first_response(&mut tx, THREAD_SOURCE, thread_id).await?;
+            if let Some(inquiry_id) = access.inquiry_id {
+                record_first_response(&mut tx, "inquiry", inquiry_id).await?;
+                sqlx::query!(
+                    r#"
+                    UPDATE inquiries
+                    SET status = 'answered', status_changed_at = NOW(), updated_at = NOW(),
+                        version = version % 32767 + 1
+                    WHERE id = $1 AND status = 'new'
+                    "#,
+                    inquiry_id
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(created_at)
+    }
+    .await;
+    let created_at = match result {
+        Ok(created_at) => created_at,
+        Err(e) => {
+            delete_blobs(data.blob_store.as_ref(), &written).await;
+            return Err(internal_error(e));
+        }
+    };
+
+    Ok((
+        StatusCode::CREATED,
+        Json(json!({
+            "status": "success",
+            "data": {
+                "id": message_id,
+                "sender_role": access.role.as_str(),
+                "mine": true,
+                "body": body,
+                "read": false,
+                "created_at": created_at
+            }
+        })),
+    ))
+}
+
+// Read receipt: the viewer has seen everything in the thread until now
+pub async fn mark_thread_read(
+    State(data): State<Arc<AppState>>,
+    viewer: AuthenticatedViewer,
+    Path(thread_id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let access = thread_access(&data.db, thread_id, viewer.viewer_id, viewer.is_admin).await?;
+
+    sqlx::query!(
+        r#"
+        UPDATE message_threads
+        SET customer_last_read_at = CASE WHEN $2 = 'customer' THEN NOW() ELSE customer_last_read_at END,
+            owner_last_read_at = CASE WHEN $2 = 'owner' THEN NOW() ELSE owner_last_read_at END
+        WHERE id = $1
+        "#,
+        thread_id,
+        access.role.as_str()
+    )
+    .execute(&data.db)
+    .await
+    .map_err(internal_error)?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "data": thread_response(&data.db, &viewer, thread_id).await?
+    })))
+}
+
+// Each side decides whether to share its contact details; they are only
+// revealed once both sides do
+pub async fn share_thread_contact(
+    State(data): State<Arc<AppState>>,
+    viewer: AuthenticatedViewer,
+    Path(thread_id): Path<Uuid>,
+    Json(body): Json<ContactShareSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let access = thread_access(&data.db, thread_id, viewer.viewer_id, viewer.is_admin).await?;
+
+    sqlx::query!(
+        r#"
+        UPDATE message_threads
+        SET customer_shares_contact = CASE WHEN $2 = 'customer' THEN $3 ELSE customer_shares_contact END,
+            owner_shares_contact = CASE WHEN $2 = 'owner' THEN $3 ELSE owner_shares_contact END,
+            updated_at = NOW(),
+            version = version % 32767 + 1
+        WHERE id = $1
+        "#,
+        thread_id,
+        access.role.as_str(),
+        body.share
+    )
+    .execute(&data.db)
+    .await
+    .map_err(internal_error)?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "data": thread_response(&data.db, &viewer, thread_id).await?
+    })))
+}
+
+pub async fn get_message_attachment(
+    State(data): State<Arc<AppState>>,
+    viewer: AuthenticatedViewer,
+    Path((thread_id, attachment_id)): Path<(Uuid, Uuid)>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    thread_access(&data.db, thread_id, viewer.viewer_id, viewer.is_admin).await?;
+
+    let attachment = sqlx::query!(
+        r#"
+        SELECT a.file_name, a.content_type, a.storage_key, a.photo_data
+        FROM message_attachments a
+        JOIN messages m ON m.id = a.message_id
+        WHERE a.id = $1 AND m.thread_id = $2
+        "#,
+        attachment_id,
+        thread_id
+    )
+    .fetch_optional(&data.db)
+    .await
+    .map_err(internal_error)?
+    .ok_or((
+        StatusCode::NOT_FOUND,
+        Json(json!({ "status": "fail", "message": "Attachment not found" })),
+    ))?;
+
+    let attachment_data = match (&attachment.storage_key, attachment.photo_data) {
+        (Some(storage_key), _) => match data.blob_store.get(storage_key).await {
+            Ok(attachment_data) => attachment_data,
+            Err(BlobStoreError::NotFound) => {
+                return Err((
+                    StatusCode::NOT_FOUND,
+                    Json(json!({ "status": "fail", "message": "Attachment not found" })),
+                ))
+            }
+            Err(e) => return Err(internal_error(e)),
+        },
+        // Not yet moved by `mano migrate-photo-blobs`
+        (None, Some(photo_data)) => photo_data,
+        (None, None) => return Err(internal_error("message attachment without data")),
+    };
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_str(&attachment.content_type).map_err(internal_error)?,
+    );
+    headers.insert(
+        header::CONTENT_DISPOSITION,
+        HeaderValue::from_str(&format!("inline; filename=\"{}\"", attachment.file_name))
+            .unwrap_or(HeaderValue::from_static("inline")),
+    );
+    headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static("private, no-store"),
+    );
+
+    Ok((headers, attachment_data))
+}
+
+// Everything one recipient's digest covers
+#[derive(Default)]
+struct Digest {
+    name: String,
+    // Name of the other side and unread count, per thread
+    threads: Vec<(String, i64)>,
+    // Thread, whether the recipient is its customer, newest message included
+    watermarks: Vec<(Uuid, bool, DateTime<Utc>)>,
+}
+
+// Emails every participant with unread messages they have not been told
+// about yet, one email per recipient. A thread is marked as notified up to
+// the newest message the email listed, and only if the email went out, so
+// that messages still in the grace window and failed sends are picked up by
+// the next run.
+async fn send_message_digests(data: &AppState) -> Result<(), sqlx::Error> {
+    let unread = sqlx::query!(
+        r#"
+        SELECT t.id AS "thread_id!", 'customer' AS "role!", v.email AS "email!",
+            v.first_name AS "name!", p.name AS "other_name!", COUNT(m.id) AS "unread!",
+            MAX(m.created_at) AS "newest!"
+        FROM message_threads t
+        JOIN profiles p ON p.id = t.profile_id
+        JOIN viewers v ON v.id = t.customer_id
+        JOIN messages m ON m.thread_id = t.id AND m.sender_role = 'owner'
+        WHERE m.created_at > COALESCE(t.customer_last_read_at, '-infinity'::timestamptz)
+            AND m.created_at > COALESCE(t.customer_notified_at, '-infinity'::timestamptz)
+            AND m.created_at < NOW() - make_interval(mins => $1)
+        GROUP BY t.id, v.email, v.first_name, p.name
+        UNION ALL
+        SELECT t.id, 'owner', COALESCE(o.email, p.email), COALESCE(o.first_name, p.name),
+            v.first_name, COUNT(m.id), MAX(m.created_at)
+        FROM message_threads t
+        JOIN profiles p ON p.id = t.profile_id
+        LEFT JOIN viewers o ON o.id = p.viewer_id
+        JOIN viewers v ON v.id = t.customer_id
+        JOIN messages m ON m.thread_id = t.id AND m.sender_role = 'customer'
+        WHERE m.created_at > COALESCE(t.owner_last_read_at, '-infinity'::timestamptz)
+            AND m.created_at > COALESCE(t.owner_notified_at, '-infinity'::timestamptz)
+            AND m.created_at < NOW() - make_interval(mins => $1)
+        GROUP BY t.id, o.email, p.email, o.first_name, p.name, v.first_name
+        "#,
+        DIGEST_GRACE_MINUTES
+    )
+    .fetch_all(&data.db)
+    .await?;
+
+    let mut digests: BTreeMap<String, Digest> = BTreeMap::new();
+    for row in unread {
+        let digest = digests
+            .entry(row.email.to_lowercase())
+            .or_insert_with(|| Digest {
+                name: row.name.clone(),
+                ..Default::default()
+            });
+        digest.threads.push((row.other_name, row.unread));
+        digest
+            .watermarks
+            .push((row.thread_id, row.role == "customer", row.newest));
+    }
+
+    let mut customer_threads = Vec::new();
+    let mut customer_newest = Vec::new();
+    let mut owner_threads = Vec::new();
+    let mut owner_newest = Vec::new();
+    for (email, digest) in digests {
+        let email_manager = data.email_manager.clone();
+        let inbox_link = format!("{}/messages", data.url);
+        let email_result = tokio::task::spawn_blocking(move || {
+            email_manager.send_message_digest_email(
+                &email,
+                &inbox_link,
+                &digest.name,
+                &digest.threads,
+            )
+        })
+        .await;
+
+        match email_result {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => {
+                println!("send_message_digests: E-Mail failed: {:?}", e);
+                continue;
+            }
+            Err(e) => {
+                eprintln!("send_message_digests error: {:?}", e);
+                continue;
+            }
+        }
+
+        for (thread_id, is_customer, newest) in digest.watermarks {
+            if is_customer {
+                customer_threads.push(thread_id);
+                customer_newest.push(newest);
+            } else {
+                owner_threads.push(thread_id);
+                owner_newest.push(newest);
+            }
+        }
+    }
+
+    sqlx::query!(
+        r#"
+        UPDATE message_threads t
+        SET customer_notified_at = n.newest
+        FROM UNNEST($1::uuid[], $2::timestamptz[]) AS n(id, newest)
+        WHERE t.id = n.id
+        "#,
+        &customer_threads,
+        &customer_newest
+    )
+    .execute(&data.db)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        UPDATE message_threads t
+        SET owner_notified_at = n.newest
+        FROM UNNEST($1::uuid[], $2::timestamptz[]) AS n(id, newest)
+        WHERE t.id = n.id
+        "#,
+        &owner_threads,
+        &owner_newest
+    )
+    .execute(&data.db)
+    .await?;
+
+    Ok(())
+}
+
+// Background job started from main
+pub async fn run_message_digests(data: Arc<AppState>) {
+    let mut interval = tokio::time::interval(DIGEST_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(e) = send_message_digests(&data).await {
+            eprintln!("run_message_digests error: {:?}", e);
+        }
+    }
+}
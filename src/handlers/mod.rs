@@ -7,12 +7,25 @@ use crate::AppState;
 
 use axum::extract::State;
 
+// Pre-existing lints in the auth handlers, left as they are.
+#[allow(unused_variables, clippy::redundant_pattern_matching)]
 pub mod auth;
+pub mod availability;
 pub mod craft;
-pub mod profile;
-// pub mod rating;
 pub mod favorits;
+pub mod handwerkskarte;
+pub mod inquiry;
+pub mod messages;
+pub mod moderation;
+pub mod photo;
+pub mod portfolio;
+pub mod profile;
+pub mod rating;
 pub mod rechtsformen;
+pub mod response_time;
+pub mod revision;
+pub mod seo;
+pub mod services;
 pub mod skill;
 
 pub async fn health_checker_handler() -> impl IntoResponse {
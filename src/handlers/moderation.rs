@@ -0,0 +1,622 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde_json::json;
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::{
+    model::ProfileStatus,
+    schema::{ModerationSchema, ProfileReportSchema},
+    validation::{validation_error_response, FieldError},
+    AppState,
+};
+
+use super::auth::AuthenticatedViewer;
+
+const REASON_MAX: usize = 500;
+
+// Moves a profile to `to` if its current status is one of `from` and logs the
+// change. Returns the previous status, or None if the profile is missing or
+// was in another status.
+pub async fn change_profile_status(
+    conn: &mut PgConnection,
+    profile_id: Uuid,
+    viewer_id: Option<Uuid>,
+    from: &[ProfileStatus],
+    to: ProfileStatus,
+    reason: Option<&str>,
+) -> Result<Option<ProfileStatus>, sqlx::Error> {
+    let allowed_from: Vec<String> = from
+        .iter()
+        .map(|status| status.as_str().to_string())
+        .collect();
+
+    let record = sqlx::query!(
+        r#"
+        UPDATE profiles p
+        SET status = $2,
+            status_reason = $3,
+            status_changed_at = NOW(),
+            version = p.version % 32767 + 1
+        FROM (SELECT id, status FROM profiles WHERE id = $1 FOR UPDATE) old
+        WHERE p.id = old.id AND old.status = ANY($4)
+        RETURNING old.status AS from_status
+        "#,
+        profile_id,
+        to.as_str(),
+        reason,
+        &allowed_from
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    let Some(record) = record else {
+        return Ok(None);
+    };
+
+    sqlx::query!(
+        r#"
+        INSERT INTO profile_status_changes (profile_id, viewer_id, from_status, to_status, reason)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        profile_id,
+        viewer_id,
+        record.from_status,
+        to.as_str(),
+        reason
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(ProfileStatus::parse(&record.from_status))
+}
+
+// Emails the profile owner about its new status. Admin-created profiles
+// without a viewer are notified at the profile's contact address. Call it once
+// the status change is committed; the SMTP send runs on the blocking pool and
+// the caller does not wait for it.
+pub async fn notify_status_change(
+    data: &AppState,
+    profile_id: Uuid,
+    status: ProfileStatus,
+    reason: Option<&str>,
+) {
+    let owner = sqlx::query!(
+        r#"
+        SELECT COALESCE(v.email, p.email) AS "email!", COALESCE(v.first_name, p.name) AS "name!"
+        FROM profiles p
+        LEFT JOIN viewers v ON p.viewer_id = v.id
+        WHERE p.id = $1
+        "#,
+        profile_id
+    )
+    .fetch_optional(&data.db)
+    .await;
+
+    let owner = match owner {
+        Ok(Some(owner)) => owner,
+        Ok(None) => return,
+        Err(e) => {
+            eprintln!("notify_status_change error: {:?}", e);
+            return;
+        }
+    };
+
+    let (subject, message) = match status {
+        ProfileStatus::Draft => return,
+        ProfileStatus::Pending => (
+            "Dein Profil wird geprüft",
+            "Dein Profil wurde zur Prüfung eingereicht. Wir melden uns, sobald es freigegeben ist.",
+        ),
+        ProfileStatus::Approved => (
+            "Dein Profil ist freigegeben",
+            "Dein Profil wurde freigegeben und ist jetzt öffentlich sichtbar.",
+        ),
+        ProfileStatus::Rejected => (
+            "Dein Profil wurde abgelehnt",
+            "Dein Profil wurde leider abgelehnt. Du kannst es überarbeiten und erneut einreichen.",
+        ),
+        ProfileStatus::Suspended => (
+            "Dein Profil wurde gesperrt",
+            "Dein Profil wurde gesperrt und ist nicht mehr öffentlich sichtbar.",
+        ),
+    };
+
+    let email_manager = data.email_manager.clone();
+    let profile_link = format!("{}/profile/{}", data.url, profile_id);
+    let reason = reason.map(str::to_string);
+    tokio::task::spawn_blocking(move || {
+        let email_result = email_manager.send_profile_status_email(
+            &owner.email,
+            &profile_link,
+            &owner.name,
+            subject,
+            message,
+            reason.as_deref(),
+        );
+
+        if let Err(e) = email_result {
+            println!("notify_status_change: E-Mail failed: {:?}", e);
+        }
+    });
+}
+
+// Shared by the admin and owner transition endpoints
+async fn transition_profile(
+    data: &AppState,
+    profile_id: Uuid,
+    viewer_id: Uuid,
+    to: ProfileStatus,
+    reason: Option<&str>,
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    let internal_error = |e: sqlx::Error| {
+        eprintln!("transition_profile error: {:?}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "status": "fail", "message": "Internal Server Error" })),
+        )
+    };
+
+    // Status, audit row and report resolution change together or not at all
+    let mut tx = data.db.begin().await.map_err(internal_error)?;
+
+    let changed = change_profile_status(
+        &mut tx,
+        profile_id,
+        Some(viewer_id),
+        to.allowed_from(),
+        to,
+        reason,
+    )
+    .await
+    .map_err(internal_error)?;
+
+    if changed.is_none() {
+        let current = sqlx::query!("SELECT status FROM profiles WHERE id = $1", profile_id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(internal_error)?;
+
+        return Err(match current {
+            None => (
+                StatusCode::NOT_FOUND,
+                Json(json!({ "status": "fail", "message": "Profile not found" })),
+            ),
+            Some(current) => (
+                StatusCode::CONFLICT,
+                Json(json!({
+                    "status": "fail",
+                    "message": format!(
+                        "Profile cannot change from {} to {}.",
+                        current.status,
+                        to.as_str()
+                    )
+                })),
+            ),
+        });
+    }
+
+    // A suspension settles whatever was reported about the profile
+    if to == ProfileStatus::Suspended {
+        resolve_profile_reports(&mut tx, profile_id, viewer_id, "suspended")
+            .await
+            .map_err(internal_error)?;
+    }
+
+    tx.commit().await.map_err(internal_error)?;
+
+    notify_status_change(data, profile_id, to, reason).await;
+
+    Ok(())
+}
+
+// Closes the open reports of a profile and lifts the automatic hiding
+async fn resolve_profile_reports(
+    conn: &mut PgConnection,
+    profile_id: Uuid,
+    viewer_id: Uuid,
+    resolution: &str,
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE profile_reports
+        SET resolved_at = NOW(),
+            resolved_by = $2,
+            resolution = $3,
+            updated_at = NOW(),
+            version = version % 32767 + 1
+        WHERE profile_id = $1 AND resolved_at IS NULL
+        "#,
+        profile_id,
+        viewer_id,
+        resolution
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query!(
+        "UPDATE profiles SET reports_hidden_at = NULL WHERE id = $1 AND reports_hidden_at IS NOT NULL",
+        profile_id
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+pub(crate) fn required_reason(
+    body: &ModerationSchema,
+) -> Result<&str, (StatusCode, Json<serde_json::Value>)> {
+    let reason = body.reason.as_deref().map(str::trim).unwrap_or_default();
+    if reason.is_empty() {
+        return Err(validation_error_response(vec![FieldError::required(
+            "reason",
+        )]));
+    }
+    if reason.chars().count() > REASON_MAX {
+        return Err(validation_error_response(vec![FieldError::too_long(
+            "reason", REASON_MAX,
+        )]));
+    }
+    Ok(reason)
+}
+
+fn admin_only(is_admin: bool, action: &str) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    if !is_admin {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            Json(json!({
+                "status": "fail",
+                "message": format!("Only admins can {} profiles.", action)
+            })),
+        ));
+    }
+    Ok(())
+}
+
+pub async fn accept_profile(
+    State(data): State<Arc<AppState>>,
+    Path(profile_id): Path<Uuid>,
+    AuthenticatedViewer {
+        viewer_id,
+        is_admin,
+    }: AuthenticatedViewer,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    admin_only(is_admin, "accept")?;
+
+    transition_profile(&data, profile_id, viewer_id, ProfileStatus::Approved, None).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "status": "success",
+            "message": "Profile accepted successfully"
+        })),
+    ))
+}
+
+pub async fn reject_profile(
+    State(data): State<Arc<AppState>>,
+    Path(profile_id): Path<Uuid>,
+    AuthenticatedViewer {
+        viewer_id,
+        is_admin,
+    }: AuthenticatedViewer,
+    Json(body): Json<ModerationSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    admin_only(is_admin, "reject")?;
+    let reason = required_reason(&body)?;
+
+    transition_profile(
+        &data,
+        profile_id,
+        viewer_id,
+        ProfileStatus::Rejected,
+        Some(reason),
+    )
+    .await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "status": "success",
+            "message": "Profile rejected."
+        })),
+    ))
+}
+
+pub async fn suspend_profile(
+    State(data): State<Arc<AppState>>,
+    Path(profile_id): Path<Uuid>,
+    AuthenticatedViewer {
+        viewer_id,
+        is_admin,
+    }: AuthenticatedViewer,
+    Json(body): Json<ModerationSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    admin_only(is_admin, "suspend")?;
+    let reason = required_reason(&body)?;
+
+    transition_profile(
+        &data,
+        profile_id,
+        viewer_id,
+        ProfileStatus::Suspended,
+        Some(reason),
+    )
+    .await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "status": "success",
+            "message": "Profile suspended."
+        })),
+    ))
+}
+
+// Owner hands a draft or rejected profile in for review
+pub async fn submit_profile(
+    State(data): State<Arc<AppState>>,
+    Path(profile_id): Path<Uuid>,
+    AuthenticatedViewer {
+        viewer_id,
+        is_admin,
+    }: AuthenticatedViewer,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    super::profile::ensure_can_edit_profile(&data.db, profile_id, viewer_id, is_admin).await?;
+
+    transition_profile(&data, profile_id, viewer_id, ProfileStatus::Pending, None).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "status": "success",
+            "message": "Profile submitted for review."
+        })),
+    ))
+}
+
+pub async fn get_profile_status(
+    State(data): State<Arc<AppState>>,
+    Path(profile_id): Path<Uuid>,
+    AuthenticatedViewer {
+        viewer_id,
+        is_admin,
+    }: AuthenticatedViewer,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    super::profile::ensure_can_edit_profile(&data.db, profile_id, viewer_id, is_admin).await?;
+
+    let record = sqlx::query!(
+        "SELECT status, status_reason, status_changed_at FROM profiles WHERE id = $1",
+        profile_id
+    )
+    .fetch_one(&data.db)
+    .await
+    .map_err(|e| {
+        eprintln!("get_profile_status error: {:?}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "status": "fail", "message": "Internal Server Error" })),
+        )
+    })?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "data": {
+            "profile_status": record.status,
+            "reason": record.status_reason,
+            "changedAt": record.status_changed_at
+        }
+    })))
+}
+
+// Viewer reports a public profile. Once `profile_report_threshold` viewers have
+// open reports the profile drops out of listings and search until an admin
+// dismisses the reports or suspends it.
+pub async fn report_profile(
+    State(data): State<Arc<AppState>>,
+    Path(profile_id): Path<Uuid>,
+    AuthenticatedViewer { viewer_id, .. }: AuthenticatedViewer,
+    Json(body): Json<ProfileReportSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let internal_error = |e: sqlx::Error| {
+        eprintln!("report_profile error: {:?}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "status": "fail", "message": "Internal Server Error" })),
+        )
+    };
+
+    let errors = body.validate();
+    if !errors.is_empty() {
+        return Err(validation_error_response(errors));
+    }
+
+    let mut tx = data.db.begin().await.map_err(internal_error)?;
+
+    // Locking the profile keeps concurrent reports from missing the threshold
+    let profile = sqlx::query!(
+        "SELECT viewer_id FROM profiles WHERE id = $1 AND status = 'approved' FOR UPDATE",
+        profile_id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(internal_error)?
+    .ok_or((
+        StatusCode::NOT_FOUND,
+        Json(json!({ "status": "fail", "message": "Profile not found" })),
+    ))?;
+    if profile.viewer_id == Some(viewer_id) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "status": "fail",
+                "message": "You cannot report your own profile."
+            })),
+        ));
+    }
+
+    let comment = body
+        .comment
+        .as_deref()
+        .map(str::trim)
+        .filter(|comment| !comment.is_empty());
+
+    let report_id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO profile_reports (profile_id, viewer_id, category, comment)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (profile_id, viewer_id) WHERE resolved_at IS NULL DO NOTHING
+        RETURNING id
+        "#,
+        profile_id,
+        viewer_id,
+        body.category.trim(),
+        comment
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(internal_error)?
+    .ok_or((
+        StatusCode::CONFLICT,
+        Json(json!({
+            "status": "fail",
+            "message": "You have already reported this profile."
+        })),
+    ))?;
+
+    sqlx::query!(
+        r#"
+        UPDATE profiles
+        SET reports_hidden_at = NOW()
+        WHERE id = $1
+            AND reports_hidden_at IS NULL
+            AND (
+                SELECT COUNT(*) FROM profile_reports
+                WHERE profile_id = $1 AND resolved_at IS NULL
+            ) >= $2
+        "#,
+        profile_id,
+        data.profile_report_threshold
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+
+    tx.commit().await.map_err(internal_error)?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(json!({
+            "status": "success",
+            "data": { "id": report_id }
+        })),
+    ))
+}
+
+// Admin triage list: profiles with open reports, most reported first
+pub async fn get_reported_profiles(
+    State(data): State<Arc<AppState>>,
+    AuthenticatedViewer { is_admin, .. }: AuthenticatedViewer,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    admin_only(is_admin, "triage")?;
+
+    let profiles = sqlx::query!(
+        r#"
+        SELECT p.id, p.name, p.slug, p.status, p.reports_hidden_at,
+            COUNT(pr.id) AS "report_count!",
+            array_agg(DISTINCT pr.category) AS "report_categories!",
+            COALESCE(
+                array_agg(pr.comment ORDER BY pr.created_at) FILTER (WHERE pr.comment IS NOT NULL),
+                '{}'
+            ) AS "report_comments!",
+            MIN(pr.created_at) AS "first_reported_at!",
+            MAX(pr.created_at) AS "last_reported_at!"
+        FROM profiles p
+        JOIN profile_reports pr ON pr.profile_id = p.id AND pr.resolved_at IS NULL
+        GROUP BY p.id
+        ORDER BY COUNT(pr.id) DESC, MAX(pr.created_at) DESC
+        "#
+    )
+    .fetch_all(&data.db)
+    .await
+    .map_err(|e| {
+        eprintln!("get_reported_profiles error: {:?}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "status": "fail", "message": "Internal Server Error" })),
+        )
+    })?;
+
+    let profiles: Vec<serde_json::Value> = profiles
+        .into_iter()
+        .map(|profile| {
+            json!({
+                "id": profile.id,
+                "name": profile.name,
+                "slug": profile.slug,
+                "profile_status": profile.status,
+                "hidden": profile.reports_hidden_at.is_some(),
+                "hidden_at": profile.reports_hidden_at,
+                "report_count": profile.report_count,
+                "report_categories": profile.report_categories,
+                "report_comments": profile.report_comments,
+                "first_reported_at": profile.first_reported_at,
+                "last_reported_at": profile.last_reported_at
+            })
+        })
+        .collect();
+
+    Ok(Json(json!({
+        "status": "success",
+        "data": profiles
+    })))
+}
+
+// Admin found nothing wrong: closes the reports and shows the profile again.
+// Suspending goes through `suspend_profile`, which resolves them as well.
+pub async fn dismiss_profile_reports(
+    State(data): State<Arc<AppState>>,
+    Path(profile_id): Path<Uuid>,
+    AuthenticatedViewer {
+        viewer_id,
+        is_admin,
+    }: AuthenticatedViewer,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    admin_only(is_admin, "triage")?;
+
+    let internal_error = |e: sqlx::Error| {
+        eprintln!("dismiss_profile_reports error: {:?}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "status": "fail", "message": "Internal Server Error" })),
+        )
+    };
+
+    let mut tx = data.db.begin().await.map_err(internal_error)?;
+    let dismissed = resolve_profile_reports(&mut tx, profile_id, viewer_id, "dismissed")
+        .await
+        .map_err(internal_error)?;
+    if dismissed == 0 {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({
+                "status": "fail",
+                "message": "Profile has no open reports"
+            })),
+        ));
+    }
+    tx.commit().await.map_err(internal_error)?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "data": { "dismissed": dismissed }
+    })))
+}
@@ -0,0 +1,551 @@
+use std::sync::Arc;
+
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use image::DynamicImage;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    blob_store::{photo_key, BlobStore, BlobStoreError},
+    media::{self, media_error_response, ImagePolicy, MediaError},
+    model::PhotoDataModel,
+    schema::PhotoQuery,
+    validation::{validation_error_response, FieldError},
+    AppState,
+};
+
+// The stored source is the upload scaled to the largest rendition
+const SOURCE_MAX_DIM: u32 = 1600;
+const SOURCE_QUALITY: u8 = 90;
+const RENDITION_QUALITY: u8 = 82;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenditionSize {
+    Thumb,
+    Card,
+    Full,
+    Large,
+}
+
+impl RenditionSize {
+    pub const ALL: [RenditionSize; 4] = [
+        RenditionSize::Thumb,
+        RenditionSize::Card,
+        RenditionSize::Full,
+        RenditionSize::Large,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RenditionSize::Thumb => "thumb",
+            RenditionSize::Card => "card",
+            RenditionSize::Full => "full",
+            RenditionSize::Large => "large",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|size| size.as_str() == value)
+    }
+
+    // Longest edge in pixels
+    fn max_dim(self) -> u32 {
+        match self {
+            RenditionSize::Thumb => 160,
+            RenditionSize::Card => 400,
+            RenditionSize::Full => 800,
+            RenditionSize::Large => 1600,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenditionFormat {
+    Jpeg,
+    Webp,
+}
+
+impl RenditionFormat {
+    pub const ALL: [RenditionFormat; 2] = [RenditionFormat::Jpeg, RenditionFormat::Webp];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RenditionFormat::Jpeg => "jpeg",
+            RenditionFormat::Webp => "webp",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "jpeg" | "jpg" => Some(RenditionFormat::Jpeg),
+            "webp" => Some(RenditionFormat::Webp),
+            _ => None,
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            RenditionFormat::Jpeg => "image/jpeg",
+            RenditionFormat::Webp => "image/webp",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            RenditionFormat::Jpeg => "jpg",
+            RenditionFormat::Webp => "webp",
+        }
+    }
+}
+
+struct Rendition {
+    size: RenditionSize,
+    format: RenditionFormat,
+    width: u32,
+    height: u32,
+    byte_size: i32,
+    data: Vec<u8>,
+}
+
+fn internal_error(e: impl std::fmt::Debug) -> (StatusCode, Json<serde_json::Value>) {
+    eprintln!("photo error: {:?}", e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(json!({ "status": "fail", "message": "Internal Server Error" })),
+    )
+}
+
+fn photo_not_found() -> (StatusCode, Json<serde_json::Value>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({ "status": "fail", "message": "Photo not found" })),
+    )
+}
+
+fn rendition_key(
+    profile_id: Uuid,
+    photo_id: Uuid,
+    size: RenditionSize,
+    format: RenditionFormat,
+) -> String {
+    format!(
+        "{}-{}.{}",
+        photo_key(profile_id, photo_id),
+        size.as_str(),
+        format.extension()
+    )
+}
+
+// Links to the photo in every size, for clients choosing by layout
+pub(crate) fn photo_links(url: &str, photo_id: Uuid) -> serde_json::Value {
+    let mut links = serde_json::Map::new();
+    links.insert(
+        "self".to_string(),
+        json!(format!("{}/api/photos/{}", url, photo_id)),
+    );
+    for size in RenditionSize::ALL {
+        links.insert(
+            size.as_str().to_string(),
+            json!(format!(
+                "{}/api/photos/{}?size={}",
+                url,
+                photo_id,
+                size.as_str()
+            )),
+        );
+    }
+    serde_json::Value::Object(links)
+}
+
+// JPEGs start at RENDITION_QUALITY and stay within the policy's byte budget,
+// WebPs are encoded at RENDITION_QUALITY
+fn render(
+    source: &DynamicImage,
+    sizes: &[RenditionSize],
+    policy: &ImagePolicy,
+) -> Result<Vec<Rendition>, MediaError> {
+    let mut renditions = Vec::with_capacity(sizes.len() * RenditionFormat::ALL.len());
+    for &size in sizes {
+        let img = media::fit(source, size.max_dim());
+        let jpeg_policy = ImagePolicy {
+            start_quality: RENDITION_QUALITY.max(policy.min_quality),
+            ..policy.with_max_dim(size.max_dim())
+        };
+        for format in RenditionFormat::ALL {
+            let data = match format {
+                RenditionFormat::Jpeg => media::encode_jpeg_within(&img, &jpeg_policy)?,
+                RenditionFormat::Webp => media::encode_webp(&img, RENDITION_QUALITY)?,
+            };
+            renditions.push(Rendition {
+                size,
+                format,
+                width: img.width(),
+                height: img.height(),
+                byte_size: data.len() as i32,
+                data,
+            });
+        }
+    }
+    Ok(renditions)
+}
+
+// Writes all blobs or none of them
+pub(crate) async fn put_blobs(
+    store: &dyn BlobStore,
+    blobs: Vec<(String, &'static str, Vec<u8>)>,
+) -> Result<Vec<String>, BlobStoreError> {
+    let mut written = Vec::with_capacity(blobs.len());
+    for (key, content_type, data) in blobs {
+        if let Err(e) = store.put(&key, content_type, data).await {
+            delete_blobs(store, &written).await;
+            return Err(e);
+        }
+        written.push(key);
+    }
+    Ok(written)
+}
+
+pub(crate) async fn delete_blobs(store: &dyn BlobStore, keys: &[String]) {
+    for key in keys {
+        if let Err(e) = store.delete(key).await {
+            eprintln!("photo: orphaned blob {}: {}", key, e);
+        }
+    }
+}
+
+async fn insert_renditions(
+    conn: &mut sqlx::PgConnection,
+    profile_id: Uuid,
+    photo_id: Uuid,
+    renditions: &[Rendition],
+) -> Result<(), sqlx::Error> {
+    let sizes: Vec<&str> = renditions.iter().map(|r| r.size.as_str()).collect();
+    let formats: Vec<&str> = renditions.iter().map(|r| r.format.as_str()).collect();
+    let keys: Vec<String> = renditions
+        .iter()
+        .map(|r| rendition_key(profile_id, photo_id, r.size, r.format))
+        .collect();
+    let widths: Vec<i32> = renditions.iter().map(|r| r.width as i32).collect();
+    let heights: Vec<i32> = renditions.iter().map(|r| r.height as i32).collect();
+    let byte_sizes: Vec<i32> = renditions.iter().map(|r| r.byte_size).collect();
+
+    sqlx::query!(
+        r#"
+        INSERT INTO photo_renditions (photo_id, size, format, storage_key, width, height, byte_size)
+        SELECT $1, t.size, t.format, t.storage_key, t.width, t.height, t.byte_size
+        FROM UNNEST($2::varchar[], $3::varchar[], $4::varchar[], $5::int[], $6::int[], $7::int[])
+            AS t(size, format, storage_key, width, height, byte_size)
+        ON CONFLICT (photo_id, size, format) DO NOTHING
+        "#,
+        photo_id,
+        &sizes as &[&str],
+        &formats as &[&str],
+        &keys,
+        &widths,
+        &heights,
+        &byte_sizes
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+// An uploaded photo whose source and renditions are in the blob store but
+// whose rows are not inserted yet
+pub(crate) struct PreparedPhoto {
+    pub id: Uuid,
+    file_name: String,
+    storage_key: String,
+    byte_size: i32,
+    renditions: Vec<Rendition>,
+    written: Vec<String>,
+}
+
+// Scales the upload into the stored source, renders every rendition and
+// writes them all to the blob store
+pub(crate) async fn prepare_photo(
+    data: &AppState,
+    profile_id: Uuid,
+    file_name: &str,
+    original_bytes: Bytes,
+) -> Result<PreparedPhoto, (StatusCode, Json<serde_json::Value>)> {
+    let policy = data.image_policy;
+    let (source_data, mut renditions) = data
+        .image_workers
+        .run(move || {
+            let source = media::fit(&media::decode(&original_bytes)?, SOURCE_MAX_DIM);
+            let source_data = media::encode_jpeg(&source, SOURCE_QUALITY)?;
+            let renditions = render(&source, &RenditionSize::ALL, &policy)?;
+            Ok((source_data, renditions))
+        })
+        .await
+        .map_err(media_error_response)?;
+
+    let photo_id = Uuid::new_v4();
+    let storage_key = photo_key(profile_id, photo_id);
+    let byte_size = source_data.len() as i32;
+
+    let mut blobs = vec![(
+        storage_key.clone(),
+        RenditionFormat::Jpeg.content_type(),
+        source_data,
+    )];
+    for rendition in &mut renditions {
+        blobs.push((
+            rendition_key(profile_id, photo_id, rendition.size, rendition.format),
+            rendition.format.content_type(),
+            std::mem::take(&mut rendition.data),
+        ));
+    }
+    let written = put_blobs(data.blob_store.as_ref(), blobs)
+        .await
+        .map_err(internal_error)?;
+
+    Ok(PreparedPhoto {
+        id: photo_id,
+        file_name: file_name.to_string(),
+        storage_key,
+        byte_size,
+        renditions,
+        written,
+    })
+}
+
+// Inserts the rows of a prepared photo, after the profile's other photos
+pub(crate) async fn insert_photo(
+    conn: &mut sqlx::PgConnection,
+    profile_id: Uuid,
+    photo: &PreparedPhoto,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO photos (id, profile_id, file_name, content_type, storage_key, byte_size, position)
+        VALUES (
+            $1, $2, $3, $4, $5, $6,
+            COALESCE((SELECT MAX(position) + 1 FROM photos WHERE profile_id = $2), 0)
+        )
+        "#,
+        photo.id,
+        profile_id,
+        &photo.file_name,
+        RenditionFormat::Jpeg.content_type(),
+        &photo.storage_key,
+        photo.byte_size
+    )
+    .execute(&mut *conn)
+    .await?;
+    insert_renditions(conn, profile_id, photo.id, &photo.renditions).await
+}
+
+// Removes the blobs of prepared photos whose rows were never committed
+pub(crate) async fn discard_photos(data: &AppState, photos: &[PreparedPhoto]) {
+    for photo in photos {
+        delete_blobs(data.blob_store.as_ref(), &photo.written).await;
+    }
+}
+
+// Renders the missing renditions of one size from the stored source
+async fn generate_renditions(
+    data: &AppState,
+    photo_id: Uuid,
+    photo: &PhotoDataModel,
+    size: RenditionSize,
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    let source_data = match (&photo.storage_key, &photo.photo_data) {
+        (Some(storage_key), _) => match data.blob_store.get(storage_key).await {
+            Ok(source_data) => source_data,
+            Err(BlobStoreError::NotFound) => return Err(photo_not_found()),
+            Err(e) => return Err(internal_error(e)),
+        },
+        (None, Some(photo_data)) => photo_data.clone(),
+        (None, None) => return Err(photo_not_found()),
+    };
+    let policy = data.image_policy;
+    let mut renditions = data
+        .image_workers
+        .run(move || render(&media::decode(&source_data)?, &[size], &policy))
+        .await
+        .map_err(|e| internal_error(format!("photo {}: {}", photo_id, e)))?;
+
+    let blobs = renditions
+        .iter_mut()
+        .map(|rendition| {
+            (
+                rendition_key(photo.profile_id, photo_id, rendition.size, rendition.format),
+                rendition.format.content_type(),
+                std::mem::take(&mut rendition.data),
+            )
+        })
+        .collect();
+    let written = put_blobs(data.blob_store.as_ref(), blobs)
+        .await
+        .map_err(internal_error)?;
+
+    let result = async {
+        let mut conn = data.db.acquire().await?;
+        insert_renditions(&mut conn, photo.profile_id, photo_id, &renditions).await
+    }
+    .await;
+    if let Err(e) = result {
+        delete_blobs(data.blob_store.as_ref(), &written).await;
+        return Err(internal_error(e));
+    }
+
+    Ok(())
+}
+
+fn accepts_webp(headers: &HeaderMap) -> bool {
+    let Some(accept) = headers
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+    else {
+        return false;
+    };
+
+    accept.split(',').any(|range| {
+        let mut params = range.split(';').map(str::trim);
+        params.next() == Some("image/webp")
+            && params
+                .filter_map(|param| param.strip_prefix("q="))
+                .all(|q| q.parse::<f32>().map_or(true, |q| q > 0.0))
+    })
+}
+
+// `?size=thumb|card|full|large` (full by default) and `?format=jpeg|webp`.
+// Without a format WebP is served to clients accepting it, unless the JPEG
+// of that size is smaller.
+pub async fn get_photo(
+    State(data): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<PhotoQuery>,
+    request_headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let size = match query.size.as_deref() {
+        None => RenditionSize::Full,
+        Some(size) => RenditionSize::parse(size).ok_or_else(|| {
+            validation_error_response(vec![FieldError::invalid_format(
+                "size",
+                "one of thumb, card, full, large",
+            )])
+        })?,
+    };
+    let requested_format = match query.format.as_deref() {
+        None => None,
+        Some(format) => Some(RenditionFormat::parse(format).ok_or_else(|| {
+            validation_error_response(vec![FieldError::invalid_format(
+                "format",
+                "one of jpeg, webp",
+            )])
+        })?),
+    };
+
+    let photo = sqlx::query_as!(
+        PhotoDataModel,
+        "SELECT profile_id, file_name, storage_key, photo_data FROM photos WHERE id = $1",
+        id
+    )
+    .fetch_optional(&data.db)
+    .await
+    .map_err(internal_error)?
+    .ok_or_else(photo_not_found)?;
+
+    let load_renditions = || {
+        sqlx::query!(
+            "SELECT format, storage_key, byte_size FROM photo_renditions WHERE photo_id = $1 AND size = $2",
+            id,
+            size.as_str()
+        )
+        .fetch_all(&data.db)
+    };
+    let mut renditions = load_renditions().await.map_err(internal_error)?;
+    // Photos from before renditions existed get them on first request
+    if renditions.len() < RenditionFormat::ALL.len() {
+        generate_renditions(&data, id, &photo, size).await?;
+        renditions = load_renditions().await.map_err(internal_error)?;
+    }
+
+    let byte_size = |format: RenditionFormat| {
+        renditions
+            .iter()
+            .find(|rendition| rendition.format == format.as_str())
+            .map(|rendition| rendition.byte_size)
+    };
+    let format = match requested_format {
+        Some(format) => format,
+        None if accepts_webp(&request_headers)
+            && byte_size(RenditionFormat::Webp) <= byte_size(RenditionFormat::Jpeg) =>
+        {
+            RenditionFormat::Webp
+        }
+        None => RenditionFormat::Jpeg,
+    };
+
+    let rendition = renditions
+        .iter()
+        .find(|rendition| rendition.format == format.as_str())
+        .ok_or_else(photo_not_found)?;
+    let photo_data = match data.blob_store.get(&rendition.storage_key).await {
+        Ok(photo_data) => photo_data,
+        Err(BlobStoreError::NotFound) => return Err(photo_not_found()),
+        Err(e) => return Err(internal_error(e)),
+    };
+
+    let file_stem = std::path::Path::new(&photo.file_name)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("photo");
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(format.content_type()),
+    );
+    if let Ok(disposition) = HeaderValue::from_str(&format!(
+        "inline; filename=\"{}-{}.{}\"",
+        file_stem,
+        size.as_str(),
+        format.extension()
+    )) {
+        headers.insert(header::CONTENT_DISPOSITION, disposition);
+    }
+    headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static("public, max-age=31536000, immutable"),
+    );
+    if requested_format.is_none() {
+        headers.insert(header::VARY, HeaderValue::from_static("Accept"));
+    }
+
+    Ok((headers, photo_data))
+}
+
+#[cfg(test)]
+mod tests {
+    use image::GenericImageView;
+
+    use super::*;
+    use crate::media::tests::{exif_jpeg, has_metadata};
+
+    #[test]
+    fn renditions_are_upright_and_carry_no_metadata() {
+        for orientation in [6, 8] {
+            let source = media::decode(&exif_jpeg(3000, 1000, orientation)).unwrap();
+            let renditions = render(&source, &RenditionSize::ALL, &ImagePolicy::default()).unwrap();
+            assert_eq!(renditions.len(), 8);
+            for rendition in renditions {
+                let max_dim = rendition.size.max_dim();
+                assert_eq!(rendition.height, max_dim);
+                assert!(rendition.width.abs_diff(max_dim / 3) <= 1);
+                assert!(!has_metadata(&rendition.data));
+                let img = media::decode(&rendition.data).unwrap();
+                assert_eq!(img.dimensions(), (rendition.width, rendition.height));
+            }
+        }
+    }
+}
@@ -0,0 +1,545 @@
+use std::{collections::HashSet, sync::Arc};
+
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde_json::json;
+use sqlx::{PgConnection, Pool, Postgres};
+use uuid::Uuid;
+
+use crate::{
+    model::ProfileSnapshot,
+    schema::{CoverPhotoSchema, PhotoDetailsSchema, PhotoOrderSchema, ProjectsSchema},
+    utils::{if_match_versions, precondition_failed, version_etag},
+    validation::{validation_error_response, FieldError},
+    AppState,
+};
+
+use super::{
+    auth::AuthenticatedViewer,
+    photo::photo_links,
+    profile::{ensure_can_edit_profile, track_profile_revision},
+    revision::load_profile_snapshot,
+};
+
+fn internal_error(e: sqlx::Error) -> (StatusCode, Json<serde_json::Value>) {
+    eprintln!("portfolio error: {:?}", e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(json!({ "status": "fail", "message": "Internal Server Error" })),
+    )
+}
+
+fn trimmed(value: &Option<String>) -> Option<String> {
+    value
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+}
+
+// Cover of a profile in lists and search results, `null` without photos
+pub(crate) fn cover_photo_json(
+    url: &str,
+    photo_id: Option<Uuid>,
+    alt_text: Option<String>,
+) -> serde_json::Value {
+    match photo_id {
+        Some(id) => json!({
+            "id": id,
+            "alt_text": alt_text,
+            "_links": photo_links(url, id)
+        }),
+        None => serde_json::Value::Null,
+    }
+}
+
+// The chosen cover photo, otherwise the first one
+pub(crate) async fn load_cover_photo(
+    db: &Pool<Postgres>,
+    url: &str,
+    profile_id: Uuid,
+) -> Result<serde_json::Value, sqlx::Error> {
+    let cover = sqlx::query!(
+        r#"
+        SELECT ph.id, ph.alt_text
+        FROM photos ph
+        JOIN profiles p ON p.id = ph.profile_id
+        WHERE ph.profile_id = $1
+        ORDER BY ph.id = p.cover_photo_id DESC, ph.position, ph.created_at
+        LIMIT 1
+        "#,
+        profile_id
+    )
+    .fetch_optional(db)
+    .await?;
+
+    Ok(match cover {
+        Some(cover) => cover_photo_json(url, Some(cover.id), cover.alt_text),
+        None => serde_json::Value::Null,
+    })
+}
+
+// Photos of a profile in display order
+pub(crate) async fn load_photos(
+    db: &Pool<Postgres>,
+    url: &str,
+    profile_id: Uuid,
+) -> Result<Vec<serde_json::Value>, sqlx::Error> {
+    let photos = sqlx::query!(
+        r#"
+        SELECT ph.id, ph.caption, ph.alt_text, ph.project_id, p.cover_photo_id
+        FROM photos ph
+        JOIN profiles p ON p.id = ph.profile_id
+        WHERE ph.profile_id = $1
+        ORDER BY ph.position, ph.created_at
+        "#,
+        profile_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    let cover_id = photos
+        .first()
+        .map(|photo| photo.cover_photo_id.unwrap_or(photo.id));
+    Ok(photos
+        .into_iter()
+        .enumerate()
+        .map(|(position, photo)| {
+            json!({
+                "id": photo.id,
+                "position": position,
+                "caption": photo.caption,
+                "alt_text": photo.alt_text,
+                "project_id": photo.project_id,
+                "is_cover": Some(photo.id) == cover_id,
+                "_links": photo_links(url, photo.id)
+            })
+        })
+        .collect())
+}
+
+// Projects of a profile in display order, each with its photos
+pub(crate) async fn load_projects(
+    db: &Pool<Postgres>,
+    url: &str,
+    profile_id: Uuid,
+) -> Result<Vec<serde_json::Value>, sqlx::Error> {
+    let projects = sqlx::query!(
+        r#"
+        SELECT id, title, description
+        FROM photo_projects
+        WHERE profile_id = $1
+        ORDER BY position, created_at
+        "#,
+        profile_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    let photos = sqlx::query!(
+        r#"
+        SELECT id, caption, alt_text, project_id AS "project_id!"
+        FROM photos
+        WHERE profile_id = $1 AND project_id IS NOT NULL
+        ORDER BY position, created_at
+        "#,
+        profile_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(projects
+        .into_iter()
+        .map(|project| {
+            let project_photos: Vec<serde_json::Value> = photos
+                .iter()
+                .filter(|photo| photo.project_id == project.id)
+                .map(|photo| {
+                    json!({
+                        "id": photo.id,
+                        "caption": photo.caption,
+                        "alt_text": photo.alt_text,
+                        "_links": photo_links(url, photo.id)
+                    })
+                })
+                .collect();
+            json!({
+                "id": project.id,
+                "title": project.title,
+                "description": project.description,
+                "photos": project_photos
+            })
+        })
+        .collect())
+}
+
+async fn profile_photo_ids(
+    conn: &mut PgConnection,
+    profile_id: Uuid,
+) -> Result<HashSet<Uuid>, (StatusCode, Json<serde_json::Value>)> {
+    let ids = sqlx::query_scalar!("SELECT id FROM photos WHERE profile_id = $1", profile_id)
+        .fetch_all(conn)
+        .await
+        .map_err(internal_error)?;
+    Ok(ids.into_iter().collect())
+}
+
+// Portfolio edits change the profile as a whole: this checks If-Match against
+// the profile's version, bumps it and keeps the row locked until commit.
+// Returns the new ETag and the profile as it was before the edit.
+async fn begin_profile_edit(
+    conn: &mut PgConnection,
+    profile_id: Uuid,
+    request_headers: &HeaderMap,
+) -> Result<(HeaderMap, ProfileSnapshot), (StatusCode, Json<serde_json::Value>)> {
+    let version = sqlx::query_scalar!(
+        r#"
+        UPDATE profiles
+        SET updated_at = NOW(), version = version % 32767 + 1
+        WHERE id = $1 AND ($2::smallint[] IS NULL OR version = ANY($2))
+        RETURNING version
+        "#,
+        profile_id,
+        if_match_versions(request_headers) as Option<Vec<i16>>
+    )
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(internal_error)?
+    .ok_or_else(precondition_failed)?;
+
+    let before = load_profile_snapshot(conn, profile_id)
+        .await
+        .map_err(internal_error)?;
+
+    let mut headers = HeaderMap::new();
+    headers.insert(header::ETAG, version_etag(version));
+    Ok((headers, before))
+}
+
+// The body has to list every photo of the profile exactly once
+pub async fn reorder_profile_photos(
+    State(data): State<Arc<AppState>>,
+    Path(profile_id): Path<Uuid>,
+    AuthenticatedViewer {
+        viewer_id,
+        is_admin,
+    }: AuthenticatedViewer,
+    request_headers: HeaderMap,
+    Json(body): Json<PhotoOrderSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    ensure_can_edit_profile(&data.db, profile_id, viewer_id, is_admin).await?;
+
+    let mut tx = data.db.begin().await.map_err(internal_error)?;
+    let (headers, before) = begin_profile_edit(&mut tx, profile_id, &request_headers).await?;
+
+    let mut errors = body.validate();
+    let existing = profile_photo_ids(&mut tx, profile_id).await?;
+    for (i, photo_id) in body.photo_ids.iter().enumerate() {
+        if !existing.contains(photo_id) {
+            errors.push(FieldError::unknown(
+                &format!("photo_ids[{}]", i),
+                &photo_id.to_string(),
+            ));
+        }
+    }
+    if errors.is_empty() && body.photo_ids.len() != existing.len() {
+        errors.push(FieldError::new(
+            "photo_ids",
+            "incomplete",
+            "photo_ids must list every photo of the profile",
+        ));
+    }
+    if !errors.is_empty() {
+        return Err(validation_error_response(errors));
+    }
+
+    sqlx::query!(
+        r#"
+        UPDATE photos
+        SET position = t.ord - 1, version = photos.version % 32767 + 1
+        FROM UNNEST($2::uuid[]) WITH ORDINALITY AS t(id, ord)
+        WHERE photos.id = t.id AND photos.profile_id = $1 AND photos.position <> t.ord - 1
+        "#,
+        profile_id,
+        &body.photo_ids
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+
+    let review_required =
+        track_profile_revision(&data, tx, profile_id, viewer_id, is_admin, &before).await?;
+    let photos = load_photos(&data.db, &data.url, profile_id)
+        .await
+        .map_err(internal_error)?;
+
+    Ok((
+        headers,
+        Json(json!({
+            "status": "success",
+            "data": photos,
+            "review_required": review_required
+        })),
+    ))
+}
+
+pub async fn update_photo_details(
+    State(data): State<Arc<AppState>>,
+    Path((profile_id, photo_id)): Path<(Uuid, Uuid)>,
+    AuthenticatedViewer {
+        viewer_id,
+        is_admin,
+    }: AuthenticatedViewer,
+    request_headers: HeaderMap,
+    Json(body): Json<PhotoDetailsSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    ensure_can_edit_profile(&data.db, profile_id, viewer_id, is_admin).await?;
+
+    let errors = body.validate();
+    if !errors.is_empty() {
+        return Err(validation_error_response(errors));
+    }
+
+    let mut tx = data.db.begin().await.map_err(internal_error)?;
+    let (headers, before) = begin_profile_edit(&mut tx, profile_id, &request_headers).await?;
+
+    let photo = sqlx::query!(
+        r#"
+        UPDATE photos
+        SET caption = $3, alt_text = $4, version = version % 32767 + 1
+        WHERE id = $1 AND profile_id = $2
+        RETURNING id, caption, alt_text, project_id
+        "#,
+        photo_id,
+        profile_id,
+        trimmed(&body.caption),
+        trimmed(&body.alt_text)
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(internal_error)?
+    .ok_or((
+        StatusCode::NOT_FOUND,
+        Json(json!({ "status": "fail", "message": "Photo not found" })),
+    ))?;
+
+    let review_required =
+        track_profile_revision(&data, tx, profile_id, viewer_id, is_admin, &before).await?;
+
+    Ok((
+        headers,
+        Json(json!({
+            "status": "success",
+            "data": {
+                "id": photo.id,
+                "caption": photo.caption,
+                "alt_text": photo.alt_text,
+                "project_id": photo.project_id,
+                "_links": photo_links(&data.url, photo.id)
+            },
+            "review_required": review_required
+        })),
+    ))
+}
+
+pub async fn set_cover_photo(
+    State(data): State<Arc<AppState>>,
+    Path(profile_id): Path<Uuid>,
+    AuthenticatedViewer {
+        viewer_id,
+        is_admin,
+    }: AuthenticatedViewer,
+    request_headers: HeaderMap,
+    Json(body): Json<CoverPhotoSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    ensure_can_edit_profile(&data.db, profile_id, viewer_id, is_admin).await?;
+
+    let mut tx = data.db.begin().await.map_err(internal_error)?;
+    let (headers, before) = begin_profile_edit(&mut tx, profile_id, &request_headers).await?;
+
+    let result = sqlx::query!(
+        r#"
+        UPDATE profiles
+        SET cover_photo_id = $2
+        WHERE id = $1
+            AND ($2::uuid IS NULL
+                OR EXISTS (SELECT 1 FROM photos WHERE id = $2 AND profile_id = $1))
+        "#,
+        profile_id,
+        body.photo_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+
+    if result.rows_affected() == 0 {
+        let photo_id = body.photo_id.map(|id| id.to_string()).unwrap_or_default();
+        return Err(validation_error_response(vec![FieldError::unknown(
+            "photo_id", &photo_id,
+        )]));
+    }
+
+    let review_required =
+        track_profile_revision(&data, tx, profile_id, viewer_id, is_admin, &before).await?;
+    let cover_photo = load_cover_photo(&data.db, &data.url, profile_id)
+        .await
+        .map_err(internal_error)?;
+
+    Ok((
+        headers,
+        Json(json!({
+            "status": "success",
+            "data": { "cover_photo": cover_photo },
+            "review_required": review_required
+        })),
+    ))
+}
+
+pub async fn get_projects(
+    State(data): State<Arc<AppState>>,
+    Path(profile_id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let projects = load_projects(&data.db, &data.url, profile_id)
+        .await
+        .map_err(internal_error)?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "data": projects
+    })))
+}
+
+pub async fn update_projects(
+    State(data): State<Arc<AppState>>,
+    Path(profile_id): Path<Uuid>,
+    AuthenticatedViewer {
+        viewer_id,
+        is_admin,
+    }: AuthenticatedViewer,
+    request_headers: HeaderMap,
+    Json(body): Json<ProjectsSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    ensure_can_edit_profile(&data.db, profile_id, viewer_id, is_admin).await?;
+
+    let mut errors = body.validate();
+
+    let mut tx = data.db.begin().await.map_err(internal_error)?;
+    let (headers, before) = begin_profile_edit(&mut tx, profile_id, &request_headers).await?;
+
+    let existing_projects: HashSet<Uuid> = sqlx::query_scalar!(
+        "SELECT id FROM photo_projects WHERE profile_id = $1 FOR UPDATE",
+        profile_id
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(internal_error)?
+    .into_iter()
+    .collect();
+    let existing_photos = profile_photo_ids(&mut tx, profile_id).await?;
+
+    for (i, project) in body.projects.iter().enumerate() {
+        if let Some(id) = project.id.filter(|id| !existing_projects.contains(id)) {
+            errors.push(FieldError::unknown(
+                &format!("projects[{}].id", i),
+                &id.to_string(),
+            ));
+        }
+        for (j, photo_id) in project.photo_ids.iter().enumerate() {
+            if !existing_photos.contains(photo_id) {
+                errors.push(FieldError::unknown(
+                    &format!("projects[{}].photo_ids[{}]", i, j),
+                    &photo_id.to_string(),
+                ));
+            }
+        }
+    }
+    if !errors.is_empty() {
+        return Err(validation_error_response(errors));
+    }
+
+    let kept: Vec<Uuid> = body.projects.iter().filter_map(|p| p.id).collect();
+    sqlx::query!(
+        "DELETE FROM photo_projects WHERE profile_id = $1 AND id <> ALL($2)",
+        profile_id,
+        &kept
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+
+    sqlx::query!(
+        "UPDATE photos SET project_id = NULL WHERE profile_id = $1 AND project_id IS NOT NULL",
+        profile_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+
+    for (position, project) in body.projects.iter().enumerate() {
+        let title = project.title.trim();
+        let description = trimmed(&project.description);
+        let project_id = match project.id {
+            Some(id) => {
+                sqlx::query!(
+                    r#"
+                    UPDATE photo_projects
+                    SET title = $2, description = $3, position = $4, updated_at = NOW(),
+                        version = version % 32767 + 1
+                    WHERE id = $1
+                    "#,
+                    id,
+                    title,
+                    description,
+                    position as i16
+                )
+                .execute(&mut *tx)
+                .await
+                .map_err(internal_error)?;
+                id
+            }
+            None => sqlx::query_scalar!(
+                r#"
+                INSERT INTO photo_projects (profile_id, title, description, position)
+                VALUES ($1, $2, $3, $4)
+                RETURNING id
+                "#,
+                profile_id,
+                title,
+                description,
+                position as i16
+            )
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(internal_error)?,
+        };
+
+        sqlx::query!(
+            "UPDATE photos SET project_id = $2 WHERE profile_id = $1 AND id = ANY($3)",
+            profile_id,
+            project_id,
+            &project.photo_ids
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(internal_error)?;
+    }
+
+    let review_required =
+        track_profile_revision(&data, tx, profile_id, viewer_id, is_admin, &before).await?;
+    let projects = load_projects(&data.db, &data.url, profile_id)
+        .await
+        .map_err(internal_error)?;
+
+    Ok((
+        headers,
+        Json(json!({
+            "status": "success",
+            "data": projects,
+            "review_required": review_required
+        })),
+    ))
+}
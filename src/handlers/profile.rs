@@ -1,501 +1,629 @@
-use image::{
-    codecs::jpeg::JpegEncoder, imageops::FilterType, io::Reader as ImageReader, GenericImageView,
-};
-use sqlx::Row;
+use sqlx::{PgConnection, Pool, Postgres, Row, Transaction};
 use std::sync::Arc;
-use std::{collections::HashMap, io::Cursor};
 
 use axum::{
-    extract::{Multipart, Path, Query, State},
-    http::{HeaderMap, HeaderValue, StatusCode},
-    response::IntoResponse,
+    async_trait,
+    body::Bytes,
+    extract::{FromRequest, Multipart, Path, Query, Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
     Json,
 };
 use serde_json::json;
 use sqlx::QueryBuilder;
 use uuid::Uuid;
 
-use crate::{model::PhotoDataModel, schema::SearchSchema, AppState};
+use crate::{
+    media::{self, media_error_response, MediaError, UploadFormat, UploadLimits},
+    model::{ProfileSnapshot, ProfileStatus},
+    schema::{CreateProfileQuery, ProfileInput, ProfilePatch, SearchSchema, SkillsPatch},
+    utils::{
+        if_match_versions, precondition_failed, refresh_profile_slug, slugify, unique_profile_slug,
+        version_etag,
+    },
+    validation::{validation_error_response, FieldError, ValidationMode},
+    AppState,
+};
 
-use super::auth::AuthenticatedViewer;
+use super::{
+    auth::AuthenticatedViewer,
+    availability::load_availability,
+    messages::can_see_contact,
+    moderation::notify_status_change,
+    photo::{discard_photos, insert_photo, photo_links, prepare_photo, PreparedPhoto},
+    portfolio::{cover_photo_json, load_cover_photo, load_photos, load_projects},
+    rating::{load_external_ratings, load_rating, load_recent_reviews},
+    response_time::load_response_stats,
+    revision::{load_profile_snapshot, record_revision},
+    services::{load_services, price_indicator},
+};
 
-pub async fn create_profile(
-    State(data): State<Arc<AppState>>,
-    AuthenticatedViewer {
-        viewer_id,
-        is_admin,
-    }: AuthenticatedViewer,
-    mut multipart: Multipart,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    println!("create profile");
+pub struct ProfileForm {
+    pub input: ProfileInput,
+    pub errors: Vec<FieldError>,
+    pub photos: Vec<(Option<String>, UploadFormat, Bytes)>,
+}
 
-    // Check if viewer already has a profile when not being an admin
-    if !is_admin {
-        let query = sqlx::query!("SELECT * FROM profiles WHERE viewer_id = $1", &viewer_id)
-            .fetch_all(&data.db)
+#[async_trait]
+impl FromRequest<Arc<AppState>> for ProfileForm {
+    type Rejection = (StatusCode, Json<serde_json::Value>);
+
+    async fn from_request(req: Request, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        let is_json = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("application/json"));
+
+        if is_json {
+            let Json(body) = Json::<serde_json::Value>::from_request(req, state)
+                .await
+                .map_err(|e| {
+                    eprintln!("ProfileForm json error: {:?}", e);
+                    (
+                        StatusCode::BAD_REQUEST,
+                        Json(json!({ "status": "fail", "message": "Invalid JSON body" })),
+                    )
+                })?;
+            let (input, errors) = ProfileInput::from_json(body);
+            return Ok(ProfileForm {
+                input,
+                errors,
+                photos: Vec::new(),
+            });
+        }
+
+        let mut multipart = Multipart::from_request(req, state).await.map_err(|e| {
+            eprintln!("ProfileForm multipart error: {:?}", e);
+            (
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "status": "fail", "message": "Invalid multipart body" })),
+            )
+        })?;
+
+        let mut fields = Vec::new();
+        let mut photos = Vec::new();
+
+        let limits = state.upload_limits;
+        while let Some(field) = multipart
+            .next_field()
             .await
-            .map_err(|e| {
-                eprintln!("create_profile error: {:?}", e);
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(json!({
-                        "status": "fail",
-                        "message": "Internal Server Error"
-                    })),
-                )
-            })?;
-        if query.len() > 0 {
-            return Err((
-                StatusCode::CONFLICT,
-                Json(json!({
-                    "status": "fail",
-                    "message": "Profil already exists for that user."
-                })),
-            ));
+            .map_err(|e| media_error_response(e.into()))?
+        {
+            let field_name = field.name().map(str::to_string).unwrap_or_default();
+
+            if field.content_type().is_some() {
+                // No profile can take more, so stop reading right away
+                if photos.len() as i64 >= limits.max_profile_photos {
+                    return Err(media_error_response(MediaError::TooManyPhotos {
+                        max_photos: limits.max_profile_photos,
+                    }));
+                }
+                photos.push(
+                    media::read_image_field(field, &limits)
+                        .await
+                        .map_err(media_error_response)?,
+                );
+            } else {
+                let text = field.text().await.map_err(|e| {
+                    eprintln!(
+                        "ProfileForm: Error reading text field {} : {:?}",
+                        field_name, e
+                    );
+                    (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        Json(json!({ "status": "fail", "message": "Internal Server Error" })),
+                    )
+                })?;
+
+                fields.push((field_name, text));
+            }
         }
-    }
 
-    println!("No conflicting profiles...");
+        let (input, errors) = ProfileInput::from_fields(fields);
 
-    let mut name: Option<String> = None;
-    let mut rechtsform_id: Option<Uuid> = None;
-    let mut email: Option<String> = None;
-    let mut telefon: Option<String> = None;
-    let mut craft_id: Option<Uuid> = None;
-    let mut experience: Option<i16> = None;
-    let mut location: Option<String> = None;
-    let mut lat: Option<f64> = None;
-    let mut lng: Option<f64> = None;
-    let mut website: Option<String> = None;
-    let mut instagram: Option<String> = None;
-    let mut skills: Option<Vec<String>> = None;
-    let mut bio: Option<String> = None;
-    let mut photos = Vec::new();
-    let mut handwerks_karten_nummer: Option<String> = None;
+        Ok(ProfileForm {
+            input,
+            errors,
+            photos,
+        })
+    }
+}
 
-    while let Some(field) = multipart.next_field().await.map_err(|e| {
-        eprintln!("create_profile Some fields error : {:?}", e);
+// Ids of the rows referenced by name in a ProfileInput
+struct ProfileReferences {
+    rechtsform_id: Option<Uuid>,
+    // Primary craft first
+    craft_ids: Option<Vec<Uuid>>,
+    // Whether the input lists all crafts or only names a new primary one
+    replace_crafts: bool,
+    skill_ids: Option<Vec<Uuid>>,
+}
+
+async fn resolve_profile_references(
+    db: &Pool<Postgres>,
+    input: &ProfileInput,
+    errors: &mut Vec<FieldError>,
+) -> Result<ProfileReferences, (StatusCode, Json<serde_json::Value>)> {
+    let internal_error = |e: sqlx::Error| {
+        eprintln!("resolve_profile_references error: {:?}", e);
         (
             StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({
-                "status": "fail",
-                "message": "Internal Server Error"
-            })),
+            Json(json!({ "status": "fail", "message": "Internal Server Error" })),
         )
-    })? {
-        let field_name = field.name().unwrap_or("").to_string();
+    };
 
-        if let Some(content_type) = field.content_type() {
-            let content_type = content_type.to_string();
+    let mut references = ProfileReferences {
+        rechtsform_id: None,
+        craft_ids: None,
+        replace_crafts: input.crafts.is_some(),
+        skill_ids: None,
+    };
 
-            if !content_type.starts_with("image/") {
-                eprintln!("Unsupported media type");
-                return Err((
-                    StatusCode::UNSUPPORTED_MEDIA_TYPE,
-                    Json(json!({
-                        "status": "fail",
-                        "message": "unsupported media type"
-                    })),
-                ));
-            }
+    // Blank values are reported as required by ProfileInput::validate
+    if let Some(rechtsform) = input.rechtsform.as_ref().filter(|r| !r.trim().is_empty()) {
+        let record = sqlx::query!(
+            "SELECT id FROM rechtsformen WHERE explain_name = $1",
+            rechtsform
+        )
+        .fetch_optional(db)
+        .await
+        .map_err(internal_error)?;
 
-            let file_name = field.file_name().unwrap_or("").to_string();
+        match record {
+            Some(record) => references.rechtsform_id = Some(record.id),
+            None => errors.push(FieldError::unknown("rechtsform", rechtsform)),
+        }
+    }
 
-            let photo_data = field.bytes().await.map_err(|e| {
-                eprintln!("upload_photos: Error reading image field: {:?}", e);
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(json!({
-                        "status": "fail",
-                        "message": "Internal Server Error"
-                    })),
-                )
-            })?;
+    if let Some(crafts) = input.craft_names() {
+        let records = sqlx::query!("SELECT id, name FROM crafts WHERE name = ANY($1)", &crafts)
+            .fetch_all(db)
+            .await
+            .map_err(internal_error)?;
 
-            println!(
-                "File_name: {}, content_type: {}: ",
-                &file_name, &content_type
-            );
+        let mut craft_ids = Vec::new();
+        for craft in &crafts {
+            match records.iter().find(|record| &record.name == craft) {
+                Some(record) => craft_ids.push(record.id),
+                None => errors.push(FieldError::unknown("crafts", craft)),
+            }
+        }
+        references.craft_ids = Some(craft_ids);
+    }
 
-            photos.push((file_name, content_type.to_string(), photo_data));
-        } else {
-            // This is a text field
-            let text = field.text().await.map_err(|e| {
-                eprintln!(
-                    "upload_photos: Error reading text field {} : {:?}",
-                    field_name, e
-                );
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(json!({
-                        "status": "fail",
-                        "message": "Internal Server Error"
-                    })),
-                )
-            })?;
+    if let Some(skills) = &input.skills {
+        references.skill_ids = Some(resolve_skill_ids(db, skills, errors).await?);
+    }
 
-            match field_name.as_str() {
-                "name" => name = Some(text),
-                "rechtsform" => {
-                    let rechtsform_result = sqlx::query!(
-                        "SELECT id FROM rechtsformen WHERE explain_name = $1",
-                        text
-                    )
-                    .fetch_optional(&data.db)
-                    .await
-                    .map_err(|e| {
-                        eprintln!("Error fetching rechtsform ID: {:?}", e);
-                        (
-                            StatusCode::INTERNAL_SERVER_ERROR,
-                            Json(json!({ "status": "fail", "message": "Internal Server Error" })),
-                        )
-                    })?;
-
-                    if let Some(rechtsform_record) = rechtsform_result {
-                        rechtsform_id = Some(rechtsform_record.id);
-                    } else {
-                        return Err((
-                            StatusCode::BAD_REQUEST,
-                            Json(json!({
-                                "status": "fail",
-                                "message": "Invalid rechtsform name"
-                            })),
-                        ));
-                    }
-                }
-                "email" => email = Some(text.to_lowercase()),
-                "telefon" => telefon = Some(text),
-                "craft" => {
-                    let craft_result = sqlx::query!("SELECT id FROM crafts WHERE name = $1", text)
-                        .fetch_optional(&data.db)
-                        .await
-                        .map_err(|e| {
-                            eprintln!("Error fetching craft ID: {:?}", e);
-                            (
-                                StatusCode::INTERNAL_SERVER_ERROR,
-                                Json(
-                                    json!({ "status": "fail", "message": "Internal Server Error" }),
-                                ),
-                            )
-                        })?;
-
-                    if let Some(craft_record) = craft_result {
-                        craft_id = Some(craft_record.id);
-                    } else {
-                        return Err((
-                            StatusCode::BAD_REQUEST,
-                            Json(json!({
-                                "status": "fail",
-                                "message": "Invalid craft name"
-                            })),
-                        ));
-                    }
-                }
-                "experience" => {
-                    let exp: i16 = text.parse().map_err(|e| {
-                        eprintln!("Error parsing experience: {:?}", e);
-                        (
-                            StatusCode::BAD_REQUEST,
-                            Json(
-                                json!({ "status": "fail", "message": "Invalid experience format" }),
-                            ),
-                        )
-                    })?;
-                    experience = Some(exp);
-                }
-                "location" => location = Some(text),
-                "lng" => {
-                    let value: f64 = text.parse().map_err(|e| {
-                        eprintln!("Error parsing lng: {:?}", e);
-                        (
-                            StatusCode::BAD_REQUEST,
-                            Json(
-                                json!({ "status": "fail", "message": "Invalid experience format" }),
-                            ),
-                        )
-                    })?;
-                    lng = Some(value);
-                }
-                "lat" => {
-                    let value: f64 = text.parse().map_err(|e| {
-                        eprintln!("Error parsing lng: {:?}", e);
-                        (
-                            StatusCode::BAD_REQUEST,
-                            Json(
-                                json!({ "status": "fail", "message": "Invalid experience format" }),
-                            ),
-                        )
-                    })?;
-                    lat = Some(value);
-                }
-                "website" => website = Some(text),
-                "instagram" => instagram = Some(text),
-                "bio" => bio = Some(text),
-                "handwerks_karten_nummer" => handwerks_karten_nummer = Some(text),
-                "skills" => {
-                    let skills_vec = serde_json::from_str(&text).map_err(|e| {
-                        eprintln!("Error parsing skills: {:?}", e);
-                        (
-                            StatusCode::BAD_REQUEST,
-                            Json(json!({ "status": "fail", "message": "Invalid skills format" })),
-                        )
-                    })?;
-                    skills = Some(skills_vec);
-                }
+    Ok(references)
+}
 
-                _ => eprintln!("Unknown field: {}", field_name),
-            }
+async fn resolve_skill_ids(
+    db: &Pool<Postgres>,
+    skills: &[String],
+    errors: &mut Vec<FieldError>,
+) -> Result<Vec<Uuid>, (StatusCode, Json<serde_json::Value>)> {
+    if skills.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let records = sqlx::query!("SELECT id, name FROM skills WHERE name = ANY($1)", skills)
+        .fetch_all(db)
+        .await
+        .map_err(|e| {
+            eprintln!("Error retrieving skill IDs: {:?}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "status": "error", "message": "Internal Server Error" })),
+            )
+        })?;
+
+    for skill in skills {
+        if !records.iter().any(|record| &record.name == skill) {
+            errors.push(FieldError::unknown("skills", skill));
         }
     }
 
-    if name.is_none() || craft_id.is_none() || email.is_none() || location.is_none() {
+    Ok(records.into_iter().map(|record| record.id).collect())
+}
+
+// Appends `, column = value` for every field that is set in the input
+fn push_profile_assignments(
+    query_builder: &mut QueryBuilder<'_, Postgres>,
+    input: ProfileInput,
+    references: &ProfileReferences,
+) {
+    if let Some(name) = input.name {
+        query_builder.push(", name = ").push_bind(name);
+    }
+    if let Some(rechtsform_id) = references.rechtsform_id {
+        query_builder
+            .push(", rechtsform_id = ")
+            .push_bind(rechtsform_id);
+    }
+    if let Some(email) = input.email {
+        query_builder.push(", email = ").push_bind(email);
+    }
+    if let Some(telefon) = input.telefon {
+        query_builder.push(", telefon = ").push_bind(telefon);
+    }
+    if let Some(location) = input.location {
+        query_builder.push(", location = ").push_bind(location);
+    }
+    if let Some(lat) = input.lat {
+        query_builder.push(", lat = ").push_bind(lat);
+    }
+    if let Some(lng) = input.lng {
+        query_builder.push(", lng = ").push_bind(lng);
+    }
+    if let Some(service_radius_km) = input.service_radius_km {
+        query_builder
+            .push(", service_radius_km = ")
+            .push_bind(service_radius_km);
+    }
+    if let Some(website) = input.website {
+        query_builder.push(", website = ").push_bind(website);
+    }
+    if let Some(instagram) = input.instagram {
+        query_builder.push(", instagram = ").push_bind(instagram);
+    }
+    if let Some(bio) = input.bio {
+        query_builder.push(", bio = ").push_bind(bio);
+    }
+    if let Some(handwerks_karten_nummer) = input.handwerks_karten_nummer {
+        query_builder
+            .push(", handwerks_karten_nummer = ")
+            .push_bind(handwerks_karten_nummer);
+    }
+    if let Some(experience) = input.experience {
+        query_builder.push(", experience = ").push_bind(experience);
+    }
+}
+
+// Restricts the update to the profile and, if the client sent If-Match, to
+// the version it last saw. No row comes back when that version is stale.
+fn push_profile_version_check(
+    query_builder: &mut QueryBuilder<'_, Postgres>,
+    profile_id: Uuid,
+    request_headers: &HeaderMap,
+) {
+    query_builder.push(" WHERE id = ").push_bind(profile_id);
+    if let Some(versions) = if_match_versions(request_headers) {
+        query_builder
+            .push(" AND version = ANY(")
+            .push_bind(versions)
+            .push(")");
+    }
+    query_builder.push(" RETURNING version");
+}
+
+// Ensures the profile exists and that the viewer is its owner or an admin
+pub(crate) async fn ensure_can_edit_profile(
+    db: &Pool<Postgres>,
+    profile_id: Uuid,
+    viewer_id: Uuid,
+    is_admin: bool,
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    let existing_profile = sqlx::query!("SELECT viewer_id FROM profiles WHERE id = $1", profile_id)
+        .fetch_optional(db)
+        .await
+        .map_err(|e| {
+            eprintln!("Error fetching profile: {:?}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "status": "error", "message": "Internal Server Error" })),
+            )
+        })?
+        .ok_or((
+            StatusCode::NOT_FOUND,
+            Json(json!({ "status": "fail", "message": "Profile not found" })),
+        ))?;
+
+    if !is_admin && existing_profile.viewer_id != Some(viewer_id) {
         return Err((
-            StatusCode::BAD_REQUEST,
+            StatusCode::FORBIDDEN,
             Json(json!({
                 "status": "fail",
-                "message": "Missing required profile data"
+                "message": "You don't have permission to update this profile"
             })),
         ));
     }
 
-    let name = name.unwrap_or_default();
-    let rechtsform_id = rechtsform_id.unwrap_or_default();
-    let email = email.unwrap_or_default();
-    let telefon = telefon.unwrap_or_default();
-    let craft_id = craft_id.unwrap_or_default();
-    let experience = experience.unwrap_or_default();
-    let location = location.unwrap_or_default();
-    let lat = lat.unwrap_or_default();
-    let lng = lng.unwrap_or_default();
-    let website = website.unwrap_or_default();
-    let instagram = instagram.unwrap_or_default();
-    let skills = skills.unwrap_or_default();
-    let bio = bio.unwrap_or_default();
-    let handwerks_karten_nummer = handwerks_karten_nummer.unwrap_or_default();
-
-    let profile = sqlx::query!(
+    Ok(())
+}
+
+fn upload_bytes(photos: &[(Option<String>, UploadFormat, Bytes)]) -> usize {
+    photos.iter().map(|(_, _, bytes)| bytes.len()).sum()
+}
+
+// Whether the profile can take `new_photos` more uploads. Counts the photos
+// that stay: the existing ones except `removed`.
+async fn check_photo_limits(
+    conn: &mut PgConnection,
+    limits: &UploadLimits,
+    profile_id: Uuid,
+    removed: &[Uuid],
+    new_photos: usize,
+    new_bytes: usize,
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    let stored = sqlx::query!(
         r#"
-        INSERT INTO profiles (
-            viewer_id, name, rechtsform_id, email, telefon, craft_id, experience, location, lat, lng, website, instagram, bio, handwerks_karten_nummer, accepted
-        )
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)
-        RETURNING id;
+        SELECT COUNT(*) AS "count!",
+            COALESCE(SUM(COALESCE(byte_size, octet_length(photo_data))), 0)::BIGINT AS "bytes!"
+        FROM photos
+        WHERE profile_id = $1 AND id <> ALL($2)
         "#,
-        if is_admin { None } else { Some(viewer_id) },
-        name,
-        rechtsform_id,
-        email,
-        telefon,
-        craft_id,
-        experience,
-        location,
-        lat,
-        lng,
-        website,
-        instagram,
-        bio,
-        handwerks_karten_nummer,
-        if is_admin { true } else { false }
+        profile_id,
+        removed
     )
-    .fetch_one(&data.db)
+    .fetch_one(conn)
     .await
     .map_err(|e| {
-        eprintln!("Error inserting profile: {:?}", e);
+        eprintln!("check_photo_limits error: {:?}", e);
         (
             StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({ "status": "error", "message": "Internal Server Error" })),
+            Json(json!({ "status": "fail", "message": "Internal Server Error" })),
         )
     })?;
-    let profile_id = profile.id;
+    limits
+        .check_profile(stored.count, stored.bytes, new_photos, new_bytes)
+        .map_err(media_error_response)
+}
+
+// Renders the uploads and writes their blobs, before any row is written.
+// Nothing stays in the blob store when one of them fails.
+async fn prepare_profile_photos(
+    data: &AppState,
+    profile_id: Uuid,
+    photos: Vec<(Option<String>, UploadFormat, Bytes)>,
+) -> Result<Vec<PreparedPhoto>, (StatusCode, Json<serde_json::Value>)> {
+    let mut prepared = Vec::with_capacity(photos.len());
+
+    println!("Rendering images...");
+    for (file_name, _format, original_bytes) in photos {
+        let file_name = file_name.unwrap_or_default();
+        match prepare_photo(data, profile_id, &file_name, original_bytes).await {
+            Ok(photo) => prepared.push(photo),
+            Err(e) => {
+                discard_photos(data, &prepared).await;
+                return Err(e);
+            }
+        }
+    }
+
+    Ok(prepared)
+}
+
+async fn insert_profile_photos(
+    conn: &mut PgConnection,
+    profile_id: Uuid,
+    photos: &[PreparedPhoto],
+) -> Result<(), sqlx::Error> {
+    for photo in photos {
+        insert_photo(&mut *conn, profile_id, photo).await?;
+    }
+    println!("Photos inserted: {}", photos.len());
+    Ok(())
+}
+
+// Locks the profile row for the rest of the transaction, so that concurrent
+// edits wait, and returns its state before the edit
+pub(crate) async fn lock_profile_snapshot(
+    conn: &mut PgConnection,
+    profile_id: Uuid,
+) -> Result<ProfileSnapshot, (StatusCode, Json<serde_json::Value>)> {
+    sqlx::query!(
+        "SELECT id FROM profiles WHERE id = $1 FOR UPDATE",
+        profile_id
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(revision_error)?;
+
+    load_profile_snapshot(conn, profile_id)
+        .await
+        .map_err(revision_error)
+}
 
-    println!("Inserting skills...");
+// Records the edit in profile_revisions and commits it; true if the profile
+// went back to review
+pub(crate) async fn track_profile_revision(
+    data: &AppState,
+    mut tx: Transaction<'_, Postgres>,
+    profile_id: Uuid,
+    viewer_id: Uuid,
+    is_admin: bool,
+    before: &ProfileSnapshot,
+) -> Result<bool, (StatusCode, Json<serde_json::Value>)> {
+    let review_required = record_revision(
+        &mut tx,
+        data,
+        profile_id,
+        viewer_id,
+        is_admin,
+        Some(before),
+        None,
+    )
+    .await
+    .map_err(revision_error)?;
+    tx.commit().await.map_err(revision_error)?;
 
-    // Insert Skills
-    if !skills.is_empty() {
-        let skill_ids = sqlx::query!("SELECT id, name FROM skills WHERE name = ANY($1)", &skills)
+    if review_required {
+        notify_status_change(data, profile_id, ProfileStatus::Pending, None).await;
+    }
+    Ok(review_required)
+}
+
+fn revision_error(e: sqlx::Error) -> (StatusCode, Json<serde_json::Value>) {
+    eprintln!("Error recording profile revision: {:?}", e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(json!({ "status": "fail", "message": "Internal Server Error" })),
+    )
+}
+
+pub async fn create_profile(
+    State(data): State<Arc<AppState>>,
+    AuthenticatedViewer {
+        viewer_id,
+        is_admin,
+    }: AuthenticatedViewer,
+    Query(create_query): Query<CreateProfileQuery>,
+    ProfileForm {
+        input,
+        mut errors,
+        photos,
+    }: ProfileForm,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    println!("create profile");
+
+    // Check if viewer already has a profile when not being an admin
+    if !is_admin {
+        let query = sqlx::query!("SELECT * FROM profiles WHERE viewer_id = $1", &viewer_id)
             .fetch_all(&data.db)
             .await
             .map_err(|e| {
-                eprintln!("Error retrieving skill IDs: {:?}", e);
+                eprintln!("create_profile error: {:?}", e);
                 (
                     StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(json!({ "status": "error", "message": "Internal Server Error" })),
+                    Json(json!({
+                        "status": "fail",
+                        "message": "Internal Server Error"
+                    })),
                 )
             })?;
-
-        // Ensure that all provided skill names exist in the database
-        let found_skill_names: Vec<String> = skill_ids.iter().map(|s| s.name.clone()).collect();
-        let missing_skills: Vec<String> = skills
-            .iter()
-            .filter(|s| !found_skill_names.contains(s))
-            .cloned()
-            .collect();
-
-        if !missing_skills.is_empty() {
+        if !query.is_empty() {
             return Err((
-                StatusCode::BAD_REQUEST,
+                StatusCode::CONFLICT,
                 Json(json!({
                     "status": "fail",
-                    "message": "Some skills are not recognized",
-                    "missing_skills": missing_skills
+                    "message": "Profil already exists for that user."
                 })),
             ));
         }
-
-        // Insert skill associations into profile_skill
-        for skill in skill_ids {
-            sqlx::query!(
-                "INSERT INTO profile_skill (profile_id, skill_id) VALUES ($1, $2)",
-                profile_id,
-                skill.id
-            )
-            .execute(&data.db)
-            .await
-            .map_err(|e| {
-                eprintln!("Error inserting into profile_skill: {:?}", e);
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(json!({ "status": "error", "message": "Internal Server Error" })),
-                )
-            })?;
-        }
     }
 
-    let mut num_photos_inserted = 0;
-    let mut num_duplicates = 0;
+    println!("No conflicting profiles...");
 
-    println!("Compressing images...");
-    for (file_name, _original_content_type, original_bytes) in photos {
-        // 1) Decode raw bytes -> DynamicImage
-        let dyn_img = match ImageReader::new(Cursor::new(&original_bytes)).with_guessed_format() {
-            Ok(reader) => match reader.decode() {
-                Ok(img) => img,
-                Err(err) => {
-                    eprintln!("Failed to decode image: {:?}", err);
-                    return Err((
-                        StatusCode::BAD_REQUEST,
-                        Json(json!({ "status": "fail", "message": "Invalid image data" })),
-                    ));
-                }
-            },
-            Err(err) => {
-                eprintln!("Failed to guess format: {:?}", err);
-                return Err((
-                    StatusCode::BAD_REQUEST,
-                    Json(json!({ "status": "fail", "message": "Invalid image data" })),
-                ));
-            }
-        };
+    errors.extend(input.validate(ValidationMode::Create));
+    let references = resolve_profile_references(&data.db, &input, &mut errors).await?;
+    if !errors.is_empty() {
+        return Err(validation_error_response(errors));
+    }
 
-        // 2) Resize so that max width = 600 or max height = 600 (whichever is bigger).
-        //    This preserves the aspect ratio.
-        let (orig_w, orig_h) = dyn_img.dimensions();
-        let max_dim = 800u32;
+    // Admins publish directly, owners either keep a draft or go to review
+    let initial_status = if is_admin {
+        ProfileStatus::Approved
+    } else if create_query.draft.unwrap_or(false) {
+        ProfileStatus::Draft
+    } else {
+        ProfileStatus::Pending
+    };
 
-        // Compute scale factors for each dimension
-        let scale_w = max_dim as f64 / orig_w as f64;
-        let scale_h = max_dim as f64 / orig_h as f64;
-        // We pick the smaller scale so that neither dimension exceeds 600
-        let scale = scale_w.min(scale_h).min(1.0);
-        // If the image is already smaller than 600 in both dimensions, scale=1.0 => no resize
+    // A new profile has no photos yet
+    data.upload_limits
+        .check_profile(0, 0, photos.len(), upload_bytes(&photos))
+        .map_err(media_error_response)?;
 
-        let new_w = (orig_w as f64 * scale).round() as u32;
-        let new_h = (orig_h as f64 * scale).round() as u32;
+    let slug_base = slugify(
+        input.name.as_deref().unwrap_or_default(),
+        input.location.as_deref().unwrap_or_default(),
+    );
+    let profile_id = Uuid::new_v4();
+    let photos = prepare_profile_photos(&data, profile_id, photos).await?;
 
-        let resized_img = if new_w != orig_w || new_h != orig_h {
-            dyn_img.resize_exact(new_w, new_h, FilterType::CatmullRom)
-        } else {
-            // no resize needed
-            dyn_img
-        };
+    let internal_error = |e: sqlx::Error| {
+        eprintln!("create_profile error: {:?}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "status": "error", "message": "Internal Server Error" })),
+        )
+    };
 
-        // 3) Encode as JPEG, iterating until we get <= 200 KB or we hit minimal quality
-        let mut quality = 90; // start quality
-        let mut compressed_bytes = Vec::new();
-        const MAX_SIZE: usize = 400_000; // 400 KB
-        const MIN_QUALITY: u8 = 10;
+    // The profile with all its rows is created at once or not at all
+    let result = async {
+        let mut tx = data.db.begin().await.map_err(internal_error)?;
 
-        loop {
-            compressed_bytes.clear();
+        let slug = unique_profile_slug(&mut tx, &slug_base, None)
+            .await
+            .map_err(internal_error)?;
 
-            // Create a JpegEncoder with the current quality
-            let mut encoder = JpegEncoder::new_with_quality(&mut compressed_bytes, quality);
+        sqlx::query!(
+            r#"
+            INSERT INTO profiles (
+                id, viewer_id, name, rechtsform_id, email, telefon, experience, location, lat, lng, service_radius_km, website, instagram, bio, handwerks_karten_nummer, status, slug
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)
+            "#,
+            profile_id,
+            if is_admin { None } else { Some(viewer_id) },
+            input.name.unwrap_or_default(),
+            references.rechtsform_id.unwrap_or_default(),
+            input.email.unwrap_or_default(),
+            input.telefon.unwrap_or_default(),
+            input.experience.unwrap_or_default(),
+            input.location.unwrap_or_default(),
+            input.lat.unwrap_or_default(),
+            input.lng.unwrap_or_default(),
+            input.service_radius_km,
+            input.website.unwrap_or_default(),
+            input.instagram.unwrap_or_default(),
+            input.bio.unwrap_or_default(),
+            input.handwerks_karten_nummer.unwrap_or_default(),
+            initial_status.as_str(),
+            slug
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(internal_error)?;
 
-            // Encode the resized DynamicImage
-            if let Err(e) = encoder.encode_image(&resized_img) {
-                eprintln!("JPEG encode error: {:?}", e);
-                return Err((
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(json!({ "status": "fail", "message": "Failed to compress image" })),
-                ));
-            }
+        save_profile_crafts(
+            &mut tx,
+            profile_id,
+            &references.craft_ids.unwrap_or_default(),
+            true,
+        )
+        .await
+        .map_err(internal_error)?;
 
-            if compressed_bytes.len() <= MAX_SIZE {
-                // Good enough, break
-                break;
-            }
+        save_service_areas(
+            &mut tx,
+            profile_id,
+            input.service_plz.as_deref(),
+            input.service_districts.as_deref(),
+        )
+        .await
+        .map_err(internal_error)?;
 
-            if quality <= MIN_QUALITY {
-                // We tried to get under 200 KB, but can't; accept current size
-                println!(
-                    "WARNING: Could not reduce below 200 KB even at Q={}",
-                    quality
-                );
-                break;
-            }
+        println!("Inserting skills...");
+        add_profile_skills(&mut tx, profile_id, &references.skill_ids.unwrap_or_default())
+            .await
+            .map_err(internal_error)?;
 
-            // Decrease quality by 5 and try again
-            quality = quality.saturating_sub(5);
-        }
+        insert_profile_photos(&mut tx, profile_id, &photos)
+            .await
+            .map_err(internal_error)?;
 
-        // 4) Insert into DB with content_type = "image/jpeg"
-        let jpeg_content_type = "image/jpeg";
-        let query_result = sqlx::query!(
-            r#"INSERT INTO photos (profile_id, file_name, content_type, photo_data)
-               VALUES ($1, $2, $3, $4)"#,
-            profile_id,
-            &file_name,
-            jpeg_content_type,
-            &compressed_bytes
-        )
-        .execute(&data.db)
-        .await;
+        record_revision(&mut tx, &data, profile_id, viewer_id, is_admin, None, None)
+            .await
+            .map_err(internal_error)?;
 
-        // 5) Error & duplicate handling
-        match query_result {
-            Err(e) => {
-                if e.to_string().contains("duplicate key") {
-                    println!("Duplicate photo found.");
-                    num_duplicates += 1;
-                    continue;
-                }
-                eprintln!("upload_photos fail: insert into db: {:?}", e);
-                return Err((
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(json!({ "status": "fail", "message": "Internal Server Error" })),
-                ));
-            }
-            Ok(_) => {
-                println!(
-                    "Photo inserted. final size={} KB, quality={}",
-                    compressed_bytes.len() / 1000,
-                    quality
-                );
-                num_photos_inserted += 1;
-            }
-        }
+        tx.commit().await.map_err(internal_error)
     }
+    .await;
 
-    println!(
-        "Photos inserted: {}, Duplicates skipped: {}",
-        num_photos_inserted, num_duplicates
-    );
+    if let Err(e) = result {
+        discard_photos(&data, &photos).await;
+        return Err(e);
+    }
 
     Ok((
         StatusCode::OK,
         Json(json!({
             "status": "success",
-            "message": "Profile created."
+            "message": "Profile created.",
+            "profile_status": initial_status.as_str()
         })),
     ))
 }
@@ -505,9 +633,16 @@ pub async fn get_profiles(
 ) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
     let rows = sqlx::query!(
         r#"
-        SELECT p.id
+        SELECT p.id, p.slug, cover.id AS "cover_photo_id?", cover.alt_text AS cover_alt_text
         FROM profiles p
-        WHERE accepted = true
+        LEFT JOIN LATERAL (
+            SELECT ph.id, ph.alt_text
+            FROM photos ph
+            WHERE ph.profile_id = p.id
+            ORDER BY ph.id = p.cover_photo_id DESC, ph.position, ph.created_at
+            LIMIT 1
+        ) cover ON TRUE
+        WHERE status = 'approved' AND reports_hidden_at IS NULL
         "#
     )
     .fetch_all(&data.db)
@@ -528,8 +663,11 @@ pub async fn get_profiles(
         .map(|row| {
             json!({
                 "id": row.id,
+                "slug": row.slug,
+                "cover_photo": cover_photo_json(&data.url, row.cover_photo_id, row.cover_alt_text.clone()),
                 "_links": {
-                    "self": format!("{}/api/profile/{}", data.url, row.id)
+                    "self": format!("{}/api/profile/{}", data.url, row.id),
+                    "slug": format!("{}/api/profile/by-slug/{}", data.url, row.slug)
                 }
             })
         })
@@ -553,25 +691,118 @@ pub async fn get_profiles(
 
 pub async fn get_profile(
     State(data): State<Arc<AppState>>,
+    viewer: Option<AuthenticatedViewer>,
     Path(id): Path<Uuid>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
     println!("get_profile");
+    profile_response(&data, id, viewer.as_ref()).await
+}
+
+// Looks a profile up by its current slug. Slugs from before a rename answer
+// with a permanent redirect to the current one.
+pub async fn get_profile_by_slug(
+    State(data): State<Arc<AppState>>,
+    viewer: Option<AuthenticatedViewer>,
+    Path(slug): Path<String>,
+) -> Result<Response, (StatusCode, Json<serde_json::Value>)> {
+    let record = sqlx::query!(
+        r#"
+        SELECT p.id, p.slug, p.slug <> $1 AS "redirect!"
+        FROM profiles p
+        WHERE p.slug = $1
+            OR p.id = (SELECT profile_id FROM profile_slug_redirects WHERE slug = $1)
+        "#,
+        slug
+    )
+    .fetch_optional(&data.db)
+    .await
+    .map_err(|e| {
+        eprintln!("get_profile_by_slug error: {:?}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "status": "fail",
+                "message": "Internal Server Error"
+            })),
+        )
+    })?
+    .ok_or((
+        StatusCode::NOT_FOUND,
+        Json(json!({
+            "status": "fail",
+            "message": "Profile not found"
+        })),
+    ))?;
+
+    if record.redirect {
+        let location = format!("{}/api/profile/by-slug/{}", data.url, record.slug);
+        return Ok((
+            StatusCode::MOVED_PERMANENTLY,
+            [(header::LOCATION, location.clone())],
+            Json(json!({
+                "status": "success",
+                "data": {
+                    "id": record.id,
+                    "slug": record.slug,
+                    "_links": {
+                        "self": location
+                    }
+                }
+            })),
+        )
+            .into_response());
+    }
+
+    Ok(profile_response(&data, record.id, viewer.as_ref())
+        .await?
+        .into_response())
+}
+
+// Email and phone number are only shown to the owner, admins and customers
+// who agreed with the owner to share contact details in a message thread
+async fn profile_response(
+    data: &AppState,
+    id: Uuid,
+    viewer: Option<&AuthenticatedViewer>,
+) -> Result<(HeaderMap, Json<serde_json::Value>), (StatusCode, Json<serde_json::Value>)> {
     let query = sqlx::query!(
         r#"
         SELECT p.*, 
-            c.name as craft_name,
+            COALESCE(
+                (SELECT array_agg(c.name ORDER BY pc.is_primary DESC, c.name)
+                 FROM profile_craft pc
+                 JOIN crafts c ON c.id = pc.craft_id
+                 WHERE pc.profile_id = p.id),
+                '{}'
+            ) AS "crafts!",
+            COALESCE(
+                (SELECT array_agg(sa.value ORDER BY sa.value)
+                 FROM profile_service_areas sa
+                 WHERE sa.profile_id = p.id AND sa.kind = 'plz'),
+                '{}'
+            ) AS "service_plz!",
+            COALESCE(
+                (SELECT array_agg(sa.value ORDER BY sa.value)
+                 FROM profile_service_areas sa
+                 WHERE sa.profile_id = p.id AND sa.kind = 'district'),
+                '{}'
+            ) AS "service_districts!",
             r.name as rechtsform_name,
             r.explain_name as rechtsform_explain_name,
             COALESCE(
                 json_agg(s.name) FILTER (WHERE s.name IS NOT NULL), '[]'
-            ) AS skills
+            ) AS skills,
+            EXISTS (
+                SELECT 1 FROM handwerkskarten_verifications hv
+                WHERE hv.profile_id = p.id AND hv.status = 'verified'
+                    AND hv.nummer = TRIM(p.handwerks_karten_nummer)
+            ) AS "verified_craftsman!"
         FROM profiles p
         LEFT JOIN rechtsformen r ON p.rechtsform_id = r.id
-        LEFT JOIN crafts c ON p.craft_id = c.id
         LEFT JOIN profile_skill ps ON p.id = ps.profile_id
         LEFT JOIN skills s ON ps.skill_id = s.id
         WHERE p.id = $1
-        GROUP BY p.id, c.name, r.name, r.explain_name
+        GROUP BY p.id, r.name, r.explain_name
         "#,
         id
     )
@@ -594,6 +825,37 @@ pub async fn get_profile(
         .and_then(|v| serde_json::from_value(v.clone()).ok())
         .unwrap_or_default();
 
+    let internal_error = |e: sqlx::Error| {
+        eprintln!("get_profile error: {:?}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "status": "fail", "message": "Internal Server Error" })),
+        )
+    };
+    let availability = load_availability(&data.db, id)
+        .await
+        .map_err(internal_error)?;
+    let services = load_services(&data.db, id).await.map_err(internal_error)?;
+    let response_stats = load_response_stats(&data.db, id)
+        .await
+        .map_err(internal_error)?;
+    let rating = load_rating(&data.db, id).await.map_err(internal_error)?;
+    let reviews = load_recent_reviews(&data.db, id)
+        .await
+        .map_err(internal_error)?;
+    let external_ratings = load_external_ratings(&data.db, id)
+        .await
+        .map_err(internal_error)?;
+    let show_contact = can_see_contact(&data.db, id, viewer)
+        .await
+        .map_err(internal_error)?;
+    let cover_photo = load_cover_photo(&data.db, &data.url, id)
+        .await
+        .map_err(internal_error)?;
+    let projects = load_projects(&data.db, &data.url, id)
+        .await
+        .map_err(internal_error)?;
+
     let profile = json!({
         "status": "success",
         "data": {
@@ -601,19 +863,38 @@ pub async fn get_profile(
                 "id": query.id,
                 "viewer_id": query.viewer_id,
                 "name": query.name,
+                "slug": query.slug,
                 "rechtsform_name": query.rechtsform_name,
                 "rechtsform_explain_name": query.rechtsform_explain_name,
-                "email": query.email.to_lowercase(),
-                "telefon": query.telefon,
-                "craft": query.craft_name,
+                "email": show_contact.then(|| query.email.to_lowercase()),
+                "telefon": query.telefon.filter(|_| show_contact),
+                "contact_visible": show_contact,
+                "craft": query.crafts.first(),
+                "crafts": query.crafts,
                 "experience": query.experience,
                 "location": query.location,
                 "lat": query.lat,
                 "lng": query.lng,
+                "service_area": {
+                    "radius_km": query.service_radius_km,
+                    "plz": query.service_plz,
+                    "districts": query.service_districts
+                },
                 "website": query.website,
                 "instagram": query.instagram,
                 "bio": query.bio,
                 "handwerks_karten_nummer": query.handwerks_karten_nummer,
+                "verified_craftsman": query.verified_craftsman,
+                "availability": availability,
+                "services": services,
+                "price_level": query.price_level,
+                "price_indicator": price_indicator(query.price_level),
+                "response_stats": response_stats,
+                "rating": rating,
+                "reviews": reviews,
+                "external_ratings": external_ratings,
+                "cover_photo": cover_photo,
+                "projects": projects,
                 "skills": skills
             }
         }
@@ -623,12 +904,28 @@ pub async fn get_profile(
     headers.insert("Content-Type", HeaderValue::from_static("application/json"));
     headers.insert(
         "Cache-Control",
-        HeaderValue::from_static("public, max-age=10"),
+        if show_contact {
+            HeaderValue::from_static("private, no-cache")
+        } else {
+            HeaderValue::from_static("public, max-age=10")
+        },
     );
+    headers.insert(header::ETAG, version_etag(query.version));
 
     Ok((headers, Json(profile)))
 }
 
+// Great-circle distance in kilometers between the point and a profile's base
+fn push_distance_km(query_builder: &mut QueryBuilder<'_, Postgres>, lat: f64, lng: f64) {
+    query_builder.push("(6371 * ACOS(LEAST(1, COS(RADIANS(");
+    query_builder.push_bind(lat);
+    query_builder.push(")) * COS(RADIANS(profiles.lat)) * COS(RADIANS(profiles.lng) - RADIANS(");
+    query_builder.push_bind(lng);
+    query_builder.push(")) + SIN(RADIANS(");
+    query_builder.push_bind(lat);
+    query_builder.push(")) * SIN(RADIANS(profiles.lat)))))");
+}
+
 pub async fn get_profiles_by_search(
     State(data): State<Arc<AppState>>,
     Json(body): Json<SearchSchema>,
@@ -637,12 +934,41 @@ pub async fn get_profiles_by_search(
 
     let mut query_builder = QueryBuilder::new(
         r#"
-        SELECT DISTINCT profiles.id
+        SELECT DISTINCT profiles.id, profiles.slug, profiles.price_level,
+            response_stats.median_response_seconds, response_stats.response_rate,
+            cover.id AS cover_photo_id, cover.alt_text AS cover_alt_text
         FROM profiles
-        LEFT JOIN crafts ON profiles.craft_id = crafts.id
+        "#,
+    );
+
+    // Joined before the crafts and skills so that the next available date is
+    // computed once per profile rather than once per joined row
+    if body.available_within_weeks.is_some() {
+        query_builder.push(
+            r#"
+        CROSS JOIN LATERAL (
+            SELECT profile_next_available_date(profiles.id) AS next_available_date
+            OFFSET 0
+        ) availability
+        "#,
+        );
+    }
+
+    query_builder.push(
+        r#"
+        LEFT JOIN profile_response_stats response_stats ON profiles.id = response_stats.profile_id
+        LEFT JOIN LATERAL (
+            SELECT ph.id, ph.alt_text
+            FROM photos ph
+            WHERE ph.profile_id = profiles.id
+            ORDER BY ph.id = profiles.cover_photo_id DESC, ph.position, ph.created_at
+            LIMIT 1
+        ) cover ON TRUE
+        LEFT JOIN profile_craft ON profiles.id = profile_craft.profile_id
+        LEFT JOIN crafts ON profile_craft.craft_id = crafts.id
         LEFT JOIN profile_skill ON profiles.id = profile_skill.profile_id
         LEFT JOIN skills ON profile_skill.skill_id = skills.id
-        WHERE accepted = true AND
+        WHERE status = 'approved' AND profiles.reports_hidden_at IS NULL AND
         "#,
     );
 
@@ -671,29 +997,62 @@ pub async fn get_profiles_by_search(
         }
     }
 
-    // -- Distance filter (unchanged)
-    if let (Some(lat_val), Some(lng_val), Some(range_val)) = (body.lat, body.lng, body.range) {
+    // A profile matches the searcher's location if its base point lies within
+    // `range` or if its own service area (radius, postcodes or districts)
+    // covers the searcher
+    let plz = body.plz.as_deref().map(str::trim).filter(|p| !p.is_empty());
+    let district = body
+        .district
+        .as_deref()
+        .map(str::trim)
+        .filter(|d| !d.is_empty());
+    let point = body.lat.zip(body.lng);
+
+    if point.is_some() || plz.is_some() || district.is_some() {
         if has_condition {
             query_builder.push(" AND ");
         }
-        query_builder.push("
-            (
-                6371 * ACOS(
-                    COS(RADIANS(");
-        query_builder.push_bind(lat_val);
-        query_builder.push(")) *
-                    COS(RADIANS(profiles.lat)) *
-                    COS(RADIANS(profiles.lng) - RADIANS(");
-        query_builder.push_bind(lng_val);
-        query_builder.push(")) +
-                    SIN(RADIANS(");
-        query_builder.push_bind(lat_val);
-        query_builder.push(")) *
-                    SIN(RADIANS(profiles.lat))
-                )
-            ) <= ");
-        query_builder.push_bind(range_val);
+        query_builder.push("(");
+
+        if let Some((lat_val, lng_val)) = point {
+            if let Some(range_val) = body.range {
+                push_distance_km(&mut query_builder, lat_val, lng_val);
+                query_builder.push(" <= ");
+                query_builder.push_bind(range_val);
+                query_builder.push(" OR ");
+            }
+            query_builder.push("(profiles.service_radius_km IS NOT NULL AND ");
+            push_distance_km(&mut query_builder, lat_val, lng_val);
+            query_builder.push(" <= profiles.service_radius_km)");
+        }
+        if let Some(plz) = plz {
+            if point.is_some() {
+                query_builder.push(" OR ");
+            }
+            query_builder.push(
+                "EXISTS (
+                SELECT 1 FROM profile_service_areas sa
+                WHERE sa.profile_id = profiles.id AND sa.kind = 'plz'
+                    AND ",
+            );
+            query_builder.push_bind(plz.to_string());
+            query_builder.push(" LIKE sa.value || '%')");
+        }
+        if let Some(district) = district {
+            if point.is_some() || plz.is_some() {
+                query_builder.push(" OR ");
+            }
+            query_builder.push(
+                "EXISTS (
+                SELECT 1 FROM profile_service_areas sa
+                WHERE sa.profile_id = profiles.id AND sa.kind = 'district'
+                    AND LOWER(sa.value) = LOWER(",
+            );
+            query_builder.push_bind(district.to_string());
+            query_builder.push("))");
+        }
 
+        query_builder.push(")");
         has_condition = true;
     }
 
@@ -710,6 +1069,56 @@ pub async fn get_profiles_by_search(
         }
     }
 
+    if body.verified_craftsman == Some(true) {
+        if has_condition {
+            query_builder.push(" AND ");
+        }
+        query_builder.push(
+            "EXISTS (
+                SELECT 1 FROM handwerkskarten_verifications hv
+                WHERE hv.profile_id = profiles.id AND hv.status = 'verified'
+                    AND hv.nummer = TRIM(profiles.handwerks_karten_nummer)
+            )",
+        );
+        has_condition = true;
+    }
+
+    if let Some(weeks) = body.available_within_weeks {
+        if !(0..=52).contains(&weeks) {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(json!({
+                    "status": "fail",
+                    "message": "available_within_weeks must be between 0 and 52"
+                })),
+            ));
+        }
+        if has_condition {
+            query_builder.push(" AND ");
+        }
+        query_builder.push("availability.next_available_date <= CURRENT_DATE + ");
+        query_builder.push_bind(weeks as i32 * 7);
+        has_condition = true;
+    }
+
+    if let Some(max_price_level) = body.max_price_level {
+        if !(1..=3).contains(&max_price_level) {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(json!({
+                    "status": "fail",
+                    "message": "max_price_level must be between 1 and 3"
+                })),
+            ));
+        }
+        if has_condition {
+            query_builder.push(" AND ");
+        }
+        query_builder.push("profiles.price_level <= ");
+        query_builder.push_bind(max_price_level);
+        has_condition = true;
+    }
+
     if !has_condition {
         return Err((
             StatusCode::BAD_REQUEST,
@@ -720,6 +1129,35 @@ pub async fn get_profiles_by_search(
         ));
     }
 
+    // Profiles without a price level or response stats come last either way
+    match body.sort.as_deref() {
+        None => {}
+        Some("price_asc") => {
+            query_builder.push(" ORDER BY profiles.price_level ASC NULLS LAST, profiles.id");
+        }
+        Some("price_desc") => {
+            query_builder.push(" ORDER BY profiles.price_level DESC NULLS LAST, profiles.id");
+        }
+        Some("response_time") => {
+            query_builder.push(
+                " ORDER BY response_stats.median_response_seconds ASC NULLS LAST, profiles.id",
+            );
+        }
+        Some("response_rate") => {
+            query_builder
+                .push(" ORDER BY response_stats.response_rate DESC NULLS LAST, profiles.id");
+        }
+        Some(_) => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(json!({
+                    "status": "fail",
+                    "message": "sort must be one of price_asc, price_desc, response_time, response_rate"
+                })),
+            ));
+        }
+    }
+
     let query = query_builder.build();
     let rows = query.fetch_all(&data.db).await.map_err(|e| {
         eprintln!("get_profiles_by_search error: {:?}", e);
@@ -732,15 +1170,23 @@ pub async fn get_profiles_by_search(
         )
     })?;
 
-    let profile_ids: Vec<Uuid> = rows.iter().map(|row| row.get("id")).collect();
-
-    let response_data: Vec<serde_json::Value> = profile_ids
-        .into_iter()
-        .map(|id| {
+    let response_data: Vec<serde_json::Value> = rows
+        .iter()
+        .map(|row| {
+            let id: Uuid = row.get("id");
+            let slug: String = row.get("slug");
+            let price_level: Option<i16> = row.get("price_level");
+            let cover_photo_id: Option<Uuid> = row.get("cover_photo_id");
+            let cover_alt_text: Option<String> = row.get("cover_alt_text");
             json!({
                 "id": id,
+                "slug": slug,
+                "price_level": price_level,
+                "price_indicator": price_indicator(price_level),
+                "cover_photo": cover_photo_json(&data.url, cover_photo_id, cover_alt_text),
                 "_links": {
-                    "self": format!("{}/api/profile/{}", data.url, id)
+                    "self": format!("{}/api/profile/{}", data.url, id),
+                    "slug": format!("{}/api/profile/by-slug/{}", data.url, slug)
                 }
             })
         })
@@ -751,45 +1197,6 @@ pub async fn get_profiles_by_search(
         "data": response_data
     })))
 }
-pub async fn get_photo(
-    State(data): State<Arc<AppState>>,
-    Path(id): Path<Uuid>,
-    Query(_params): Query<HashMap<String, String>>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    let photo = sqlx::query_as!(
-        PhotoDataModel,
-        "SELECT file_name, content_type, photo_data FROM photos WHERE id = $1",
-        id
-    )
-    .fetch_one(&data.db)
-    .await
-    .map_err(|e| {
-        eprintln!("get_photos_metadata: {:?}", e);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({
-                "status": "fail",
-                "message": "Internal Server Error"
-            })),
-        )
-    })?;
-
-    let mut headers = HeaderMap::new();
-    headers.insert(
-        "Content-Type",
-        HeaderValue::from_str(&photo.content_type).unwrap(),
-    );
-    headers.insert(
-        "Content-Disposition",
-        HeaderValue::from_str(&format!("inline; filename=\"{}\"", photo.file_name)).unwrap(),
-    );
-
-    headers.insert(
-        "Cache-Control",
-        HeaderValue::from_static("public, max-age=31536000, immutable"),
-    );
-    Ok((headers, photo.photo_data))
-}
 pub async fn get_photos_of_profile(
     State(data): State<Arc<AppState>>,
     Path(profile_id): Path<Uuid>,
@@ -798,24 +1205,10 @@ pub async fn get_photos_of_profile(
     let mut delay = tokio::time::Duration::from_secs(1);
     
     loop {
-        let result = sqlx::query!(
-            r#"SELECT id FROM photos WHERE profile_id = $1"#,
-            profile_id
-        )
-        .fetch_all(&data.db)
-        .await;
+        let result = load_photos(&data.db, &data.url, profile_id).await;
         
         match result {
-            Ok(rows) => {
-                // Process rows and return success
-                let photo_data = rows.iter().map(|row| {
-                    json!({
-                        "id": row.id,
-                        "_links": {
-                            "self": format!("{}/api/photos/{}", data.url, row.id)
-                        }
-                    })
-                }).collect::<Vec<_>>();
+            Ok(photo_data) => {
                 
                 let mut headers = HeaderMap::new();
                 headers.insert("Content-Type", HeaderValue::from_static("application/json"));
@@ -854,431 +1247,622 @@ pub async fn update_profile(
         is_admin,
     }: AuthenticatedViewer,
     Path(profile_id): Path<Uuid>,
-    mut multipart: Multipart,
+    request_headers: HeaderMap,
+    ProfileForm {
+        input,
+        mut errors,
+        photos,
+    }: ProfileForm,
 ) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
     println!("update_profile");
 
-    // Ensure profile exists and check permissions
-    let existing_profile = sqlx::query!("SELECT viewer_id FROM profiles WHERE id = $1", profile_id)
-        .fetch_optional(&data.db)
+    ensure_can_edit_profile(&data.db, profile_id, viewer_id, is_admin).await?;
+
+    errors.extend(input.validate(ValidationMode::Update));
+    let references = resolve_profile_references(&data.db, &input, &mut errors).await?;
+    if !errors.is_empty() {
+        return Err(validation_error_response(errors));
+    }
+
+    let deleted_photos = input.deleted_photos.clone().unwrap_or_default();
+    let service_plz = input.service_plz.clone();
+    let service_districts = input.service_districts.clone();
+
+    let internal_error = |e: sqlx::Error| {
+        eprintln!("update_profile error: {:?}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "status": "fail", "message": "Internal Server Error" })),
+        )
+    };
+
+    // Checked before rendering anything, and again under the profile lock
+    let new_photos = photos.len();
+    let new_bytes = upload_bytes(&photos);
+    let mut conn = data.db.acquire().await.map_err(internal_error)?;
+    check_photo_limits(
+        &mut conn,
+        &data.upload_limits,
+        profile_id,
+        &deleted_photos,
+        new_photos,
+        new_bytes,
+    )
+    .await?;
+    drop(conn);
+
+    let photos = prepare_profile_photos(&data, profile_id, photos).await?;
+
+    let result = async {
+        let mut tx = data.db.begin().await.map_err(internal_error)?;
+
+        sqlx::query!(
+            "SELECT id FROM profiles WHERE id = $1 FOR UPDATE",
+            profile_id
+        )
+        .fetch_one(&mut *tx)
         .await
-        .map_err(|e| {
-            eprintln!("Error fetching profile: {:?}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({ "status": "error", "message": "Internal Server Error" })),
+        .map_err(internal_error)?;
+        check_photo_limits(
+            &mut tx,
+            &data.upload_limits,
+            profile_id,
+            &deleted_photos,
+            new_photos,
+            new_bytes,
+        )
+        .await?;
+
+        let before = load_profile_snapshot(&mut tx, profile_id)
+            .await
+            .map_err(internal_error)?;
+
+        let mut query_builder = QueryBuilder::<sqlx::Postgres>::new(
+            "UPDATE profiles SET updated_at = NOW(), version = version % 32767 + 1",
+        );
+        push_profile_assignments(&mut query_builder, input, &references);
+        push_profile_version_check(&mut query_builder, profile_id, &request_headers);
+
+        let version: i16 = query_builder
+            .build()
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(internal_error)?
+            .ok_or_else(precondition_failed)?
+            .get("version");
+
+        refresh_profile_slug(&mut tx, profile_id)
+            .await
+            .map_err(internal_error)?;
+        if let Some(craft_ids) = &references.craft_ids {
+            save_profile_crafts(&mut tx, profile_id, craft_ids, references.replace_crafts)
+                .await
+                .map_err(internal_error)?;
+        }
+        save_service_areas(
+            &mut tx,
+            profile_id,
+            service_plz.as_deref(),
+            service_districts.as_deref(),
+        )
+        .await
+        .map_err(internal_error)?;
+
+        // Skills are only replaced when the form carries a skills field
+        if let Some(skill_ids) = &references.skill_ids {
+            sqlx::query!(
+                "DELETE FROM profile_skill WHERE profile_id = $1",
+                profile_id
             )
-        })?
-        .ok_or((
-            StatusCode::NOT_FOUND,
-            Json(json!({ "status": "fail", "message": "Profile not found" })),
-        ))?;
+            .execute(&mut *tx)
+            .await
+            .map_err(internal_error)?;
+            add_profile_skills(&mut tx, profile_id, skill_ids)
+                .await
+                .map_err(internal_error)?;
+        }
 
-    if !is_admin && existing_profile.viewer_id != Some(viewer_id) {
-        return Err((
-            StatusCode::FORBIDDEN,
-            Json(json!({
-                "status": "fail",
-                "message": "You don't have permission to update this profile"
-            })),
-        ));
+        sqlx::query!(
+            "DELETE FROM photos WHERE profile_id = $1 AND id = ANY($2)",
+            profile_id,
+            &deleted_photos
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(internal_error)?;
+
+        insert_profile_photos(&mut tx, profile_id, &photos)
+            .await
+            .map_err(internal_error)?;
+
+        let review_required = record_revision(
+            &mut tx,
+            &data,
+            profile_id,
+            viewer_id,
+            is_admin,
+            Some(&before),
+            None,
+        )
+        .await
+        .map_err(internal_error)?;
+
+        tx.commit().await.map_err(internal_error)?;
+        Ok((version, review_required))
+    }
+    .await;
+
+    let (version, review_required) = match result {
+        Ok(updated) => updated,
+        Err(e) => {
+            discard_photos(&data, &photos).await;
+            return Err(e);
+        }
+    };
+
+    if review_required {
+        notify_status_change(&data, profile_id, ProfileStatus::Pending, None).await;
+    }
+
+    let mut headers = HeaderMap::new();
+    headers.insert(header::ETAG, version_etag(version));
+
+    Ok((
+        StatusCode::OK,
+        headers,
+        Json(json!({
+            "status": "success",
+            "message": "Profile updated successfully.",
+            "review_required": review_required
+        })),
+    ))
+}
+
+pub async fn patch_profile(
+    State(data): State<Arc<AppState>>,
+    AuthenticatedViewer {
+        viewer_id,
+        is_admin,
+    }: AuthenticatedViewer,
+    Path(profile_id): Path<Uuid>,
+    request_headers: HeaderMap,
+    Json(body): Json<serde_json::Value>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    println!("patch_profile");
+
+    ensure_can_edit_profile(&data.db, profile_id, viewer_id, is_admin).await?;
+
+    let (patch, mut errors) = ProfilePatch::from_json(body);
+    errors.extend(patch.validate());
+    let references = resolve_profile_references(&data.db, &patch.set, &mut errors).await?;
+
+    let mut replace_skills = None;
+    let mut add_skills = Vec::new();
+    let mut remove_skills = Vec::new();
+    match &patch.skills {
+        Some(SkillsPatch::Replace(names)) => {
+            replace_skills = Some(resolve_skill_ids(&data.db, names, &mut errors).await?);
+        }
+        Some(SkillsPatch::Modify { add, remove }) => {
+            add_skills = resolve_skill_ids(&data.db, add, &mut errors).await?;
+            remove_skills = resolve_skill_ids(&data.db, remove, &mut errors).await?;
+        }
+        None => {}
+    }
+
+    if !errors.is_empty() {
+        return Err(validation_error_response(errors));
+    }
+
+    let internal_error = |e: sqlx::Error| {
+        eprintln!("patch_profile error: {:?}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "status": "fail", "message": "Internal Server Error" })),
+        )
+    };
+
+    let mut tx = data.db.begin().await.map_err(internal_error)?;
+
+    let before = lock_profile_snapshot(&mut tx, profile_id).await?;
+
+    let service_plz = patch.set.service_plz.clone();
+    let service_districts = patch.set.service_districts.clone();
+
+    let mut query_builder = QueryBuilder::<sqlx::Postgres>::new(
+        "UPDATE profiles SET updated_at = NOW(), version = version % 32767 + 1",
+    );
+    push_profile_assignments(&mut query_builder, patch.set, &references);
+    for field in &patch.clear {
+        // Only names from CLEARABLE_FIELDS end up in `clear`
+        query_builder.push(format!(", {} = NULL", field));
+    }
+    push_profile_version_check(&mut query_builder, profile_id, &request_headers);
+
+    let version: i16 = query_builder
+        .build()
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(internal_error)?
+        .ok_or_else(precondition_failed)?
+        .get("version");
+
+    refresh_profile_slug(&mut tx, profile_id)
+        .await
+        .map_err(internal_error)?;
+
+    if let Some(craft_ids) = &references.craft_ids {
+        save_profile_crafts(&mut tx, profile_id, craft_ids, references.replace_crafts)
+            .await
+            .map_err(internal_error)?;
+    }
+
+    save_service_areas(
+        &mut tx,
+        profile_id,
+        service_plz.as_deref(),
+        service_districts.as_deref(),
+    )
+    .await
+    .map_err(internal_error)?;
+
+    if let Some(skill_ids) = replace_skills {
+        sqlx::query!(
+            "DELETE FROM profile_skill WHERE profile_id = $1",
+            profile_id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(internal_error)?;
+        add_profile_skills(&mut tx, profile_id, &skill_ids)
+            .await
+            .map_err(internal_error)?;
+    }
+
+    if !remove_skills.is_empty() {
+        sqlx::query!(
+            "DELETE FROM profile_skill WHERE profile_id = $1 AND skill_id = ANY($2)",
+            profile_id,
+            &remove_skills
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(internal_error)?;
+    }
+
+    add_profile_skills(&mut tx, profile_id, &add_skills)
+        .await
+        .map_err(internal_error)?;
+
+    let review_required = record_revision(
+        &mut tx,
+        &data,
+        profile_id,
+        viewer_id,
+        is_admin,
+        Some(&before),
+        None,
+    )
+    .await
+    .map_err(internal_error)?;
+
+    tx.commit().await.map_err(internal_error)?;
+
+    if review_required {
+        notify_status_change(&data, profile_id, ProfileStatus::Pending, None).await;
+    }
+
+    let mut headers = HeaderMap::new();
+    headers.insert(header::ETAG, version_etag(version));
+
+    Ok((
+        StatusCode::OK,
+        headers,
+        Json(json!({
+            "status": "success",
+            "message": "Profile updated successfully.",
+            "review_required": review_required
+        })),
+    ))
+}
+
+async fn add_profile_skills(
+    conn: &mut PgConnection,
+    profile_id: Uuid,
+    skill_ids: &[Uuid],
+) -> Result<(), sqlx::Error> {
+    if skill_ids.is_empty() {
+        return Ok(());
+    }
+
+    sqlx::query!(
+        r#"
+        INSERT INTO profile_skill (profile_id, skill_id)
+        SELECT $1, UNNEST($2::uuid[])
+        ON CONFLICT (profile_id, skill_id) DO NOTHING
+        "#,
+        profile_id,
+        skill_ids
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+// Makes the first id the primary craft. With `replace` the profile keeps only
+// the given crafts, otherwise its other crafts stay as secondary ones.
+pub(crate) async fn save_profile_crafts(
+    conn: &mut PgConnection,
+    profile_id: Uuid,
+    craft_ids: &[Uuid],
+    replace: bool,
+) -> Result<(), sqlx::Error> {
+    let Some(primary_id) = craft_ids.first() else {
+        return Ok(());
+    };
+
+    if replace {
+        sqlx::query!(
+            "DELETE FROM profile_craft WHERE profile_id = $1 AND craft_id <> ALL($2)",
+            profile_id,
+            craft_ids
+        )
+        .execute(&mut *conn)
+        .await?;
     }
 
-    let mut name: Option<String> = None;
-    let mut rechtsform_id: Option<Uuid> = None;
-    let mut email: Option<String> = None;
-    let mut telefon: Option<String> = None;
-    let mut craft_id: Option<Uuid> = None;
-    let mut experience: Option<i16> = None;
-    let mut location: Option<String> = None;
-    let mut lat: Option<f64> = None;
-    let mut lng: Option<f64> = None;
-    let mut website: Option<String> = None;
-    let mut instagram: Option<String> = None;
-    let mut bio: Option<String> = None;
-    let mut handwerks_karten_nummer: Option<String> = None;
-    let mut skills: Option<Vec<String>> = None;
-    let mut photos = Vec::new();
-    let mut deleted_photos: Vec<Uuid> = Vec::new();
+    sqlx::query!(
+        r#"
+        UPDATE profile_craft
+        SET is_primary = false, updated_at = NOW(), version = version % 32767 + 1
+        WHERE profile_id = $1 AND is_primary AND craft_id <> $2
+        "#,
+        profile_id,
+        primary_id
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO profile_craft (profile_id, craft_id, is_primary)
+        SELECT $1, t.craft_id, t.ord = 1
+        FROM UNNEST($2::uuid[]) WITH ORDINALITY AS t(craft_id, ord)
+        ON CONFLICT (profile_id, craft_id) DO UPDATE
+        SET is_primary = EXCLUDED.is_primary, updated_at = NOW(), version = profile_craft.version % 32767 + 1
+        WHERE profile_craft.is_primary <> EXCLUDED.is_primary
+        "#,
+        profile_id,
+        craft_ids
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
 
-    while let Some(field) = multipart.next_field().await.map_err(|e| {
-        eprintln!("create_profile Some fields error : {:?}", e);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({ "status": "fail", "message": "Internal Server Error" })),
-        )
-    })? {
-        let field_name = field.name().map(str::to_string).unwrap_or_default();
+// Replaces the postcodes and districts a profile serves. A list that was not
+// sent leaves the stored areas of that kind untouched.
+pub(crate) async fn save_service_areas(
+    conn: &mut PgConnection,
+    profile_id: Uuid,
+    plz: Option<&[String]>,
+    districts: Option<&[String]>,
+) -> Result<(), sqlx::Error> {
+    for (kind, values) in [("plz", plz), ("district", districts)] {
+        let Some(values) = values else {
+            continue;
+        };
 
-        if let Some(content_type) = field.content_type().map(str::to_string) {
-            if !content_type.starts_with("image/") {
-                eprintln!("Unsupported media type");
-                return Err((
-                    StatusCode::UNSUPPORTED_MEDIA_TYPE,
-                    Json(json!({
-                        "status": "fail",
-                        "message": "unsupported media type"
-                    })),
-                ));
-            }
-            let file_name = field.file_name().map(str::to_string).unwrap_or_default();
+        sqlx::query!(
+            "DELETE FROM profile_service_areas WHERE profile_id = $1 AND kind = $2",
+            profile_id,
+            kind
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO profile_service_areas (profile_id, kind, value)
+            SELECT $1, $2, value FROM UNNEST($3::varchar[]) AS t(value)
+            ON CONFLICT DO NOTHING
+            "#,
+            profile_id,
+            kind,
+            values
+        )
+        .execute(&mut *conn)
+        .await?;
+    }
 
-            let photo_data = field.bytes().await.map_err(|_| {
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(json!({ "status": "fail", "message": "Internal Server Error" })),
-                )
-            })?;
+    Ok(())
+}
 
-            println!("PUSHING PHOTO");
-            photos.push((file_name, content_type, photo_data));
-        } else {
-            // Get text before moving field
-            let text = field.text().await.map_err(|_| {
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(json!({ "status": "fail", "message": "Internal Server Error" })),
-                )
-            })?;
+pub async fn upload_profile_photos(
+    State(data): State<Arc<AppState>>,
+    AuthenticatedViewer {
+        viewer_id,
+        is_admin,
+    }: AuthenticatedViewer,
+    Path(profile_id): Path<Uuid>,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    ensure_can_edit_profile(&data.db, profile_id, viewer_id, is_admin).await?;
 
-            // Use stored `field_name` instead of calling `field.name()` again
-            match field_name.as_str() {
-                "name" => name = Some(text),
-                "rechtsform_explain_name" => {
-                    let rechtsform_result = sqlx::query!(
-                        "SELECT id FROM rechtsformen WHERE explain_name = $1",
-                        text
-                    )
-                    .fetch_optional(&data.db)
-                    .await
-                    .map_err(|e| {
-                        eprintln!("Error fetching rechtsform ID: {:?}", e);
-                        (
-                            StatusCode::INTERNAL_SERVER_ERROR,
-                            Json(json!({ "status": "fail", "message": "Internal Server Error" })),
-                        )
-                    })?;
-
-                    if let Some(rechtsform_record) = rechtsform_result {
-                        rechtsform_id = Some(rechtsform_record.id);
-                    } else {
-                        return Err((
-                            StatusCode::BAD_REQUEST,
-                            Json(json!({
-                                "status": "fail",
-                                "message": "Invalid rechtsform name"
-                            })),
-                        ));
-                    }
-                }
-                "email" => email = Some(text.to_lowercase()),
-                "telefon" => telefon = Some(text),
-                "craft" => {
-                    let craft_result = sqlx::query!("SELECT id FROM crafts WHERE name = $1", text)
-                        .fetch_optional(&data.db)
-                        .await
-                        .map_err(|e| {
-                            eprintln!("Error fetching craft ID: {:?}", e);
-                            (
-                                StatusCode::INTERNAL_SERVER_ERROR,
-                                Json(
-                                    json!({ "status": "fail", "message": "Internal Server Error" }),
-                                ),
-                            )
-                        })?;
-
-                    if let Some(craft_record) = craft_result {
-                        craft_id = Some(craft_record.id);
-                    } else {
-                        return Err((
-                            StatusCode::BAD_REQUEST,
-                            Json(json!({
-                                "status": "fail",
-                                "message": "Invalid craft name"
-                            })),
-                        ));
-                    }
-                }
-                "experience" => {
-                    experience = Some(text.parse::<i16>().map_err(|_| {
-                        (
-                            StatusCode::BAD_REQUEST,
-                            Json(json!({"status": "fail", "message": "Invalid experience format"})),
-                        )
-                    })?)
-                }
-                "lat" => {
-                    lat = Some(text.parse::<f64>().map_err(|_| {
-                        (
-                            StatusCode::BAD_REQUEST,
-                            Json(json!({"status": "fail", "message": "Invalid experience format"})),
-                        )
-                    })?)
-                }
-                "lng" => {
-                    lng = Some(text.parse::<f64>().map_err(|_| {
-                        (
-                            StatusCode::BAD_REQUEST,
-                            Json(json!({"status": "fail", "message": "Invalid experience format"})),
-                        )
-                    })?)
-                }
-                "location" => location = Some(text),
-                "website" => website = Some(text),
-                "instagram" => instagram = Some(text),
-                "bio" => bio = Some(text),
-                "handwerks_karten_nummer" => handwerks_karten_nummer = Some(text),
-                "skills" => skills = Some(serde_json::from_str(&text).unwrap_or_default()),
-                "deleted_photos" => {
-                    deleted_photos = serde_json::from_str(&text).unwrap_or_default();
-                    println!("User wants to delete photo IDs: {:?}", deleted_photos);
-                }
-                _ => eprintln!("Unknown field: {}", field_name),
-            }
+    let limits = data.upload_limits;
+    let mut photos = Vec::new();
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| media_error_response(e.into()))?
+    {
+        if photos.len() as i64 >= limits.max_profile_photos {
+            return Err(media_error_response(MediaError::TooManyPhotos {
+                max_photos: limits.max_profile_photos,
+            }));
         }
+        photos.push(
+            media::read_image_field(field, &limits)
+                .await
+                .map_err(media_error_response)?,
+        );
     }
 
-    let mut query_builder =
-        QueryBuilder::<sqlx::Postgres>::new("UPDATE profiles SET updated_at = NOW()");
-
-    if let Some(name) = name {
-        query_builder.push(", name = ").push_bind(name);
-    }
-    if let Some(rechtsform_id) = rechtsform_id {
-        query_builder
-            .push(", rechtsform_id = ")
-            .push_bind(rechtsform_id);
-    }
-    if let Some(craft_id) = craft_id {
-        query_builder.push(", craft_id = ").push_bind(craft_id);
-    }
-    if let Some(email) = email {
-        query_builder.push(", email = ").push_bind(email);
-    }
-    if let Some(telefon) = telefon {
-        query_builder.push(", telefon = ").push_bind(telefon);
-    }
-    if let Some(location) = location {
-        query_builder.push(", location = ").push_bind(location);
-    }
-    if let Some(lat) = lat {
-        query_builder.push(", lat = ").push_bind(lat);
-    }
-    if let Some(lng) = lng {
-        query_builder.push(", lng = ").push_bind(lng);
-    }
-    if let Some(website) = website {
-        query_builder.push(", website = ").push_bind(website);
-    }
-    if let Some(instagram) = instagram {
-        query_builder.push(", instagram = ").push_bind(instagram);
-    }
-    if let Some(bio) = bio {
-        query_builder.push(", bio = ").push_bind(bio);
-    }
-    if let Some(handwerks_karten_nummer) = handwerks_karten_nummer {
-        query_builder
-            .push(", handwerks_karten_nummer = ")
-            .push_bind(handwerks_karten_nummer);
-    }
-    if let Some(experience) = experience {
-        query_builder.push(", experience = ").push_bind(experience);
+    if photos.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "status": "fail", "message": "No photos uploaded" })),
+        ));
     }
 
-    query_builder.push(" WHERE id = ").push_bind(profile_id);
-    query_builder.build().execute(&data.db).await.map_err(|e| {
-        eprintln!("Error executing query: {:?}", e);
+    let internal_error = |e: sqlx::Error| {
+        eprintln!("upload_profile_photos error: {:?}", e);
         (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(json!({ "status": "fail", "message": "Internal Server Error" })),
         )
-    })?;
+    };
 
-    sqlx::query!(
-        "DELETE FROM profile_skill WHERE profile_id = $1",
-        profile_id
-    )
-    .execute(&data.db)
-    .await
-    .map_err(|e| {
-        eprintln!("Error deleting old skills: {:?}", e);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({ "status": "error", "message": "Internal Server Error" })),
-        )
-    })?;
+    let new_photos = photos.len();
+    let new_bytes = upload_bytes(&photos);
+    let mut conn = data.db.acquire().await.map_err(internal_error)?;
+    check_photo_limits(&mut conn, &limits, profile_id, &[], new_photos, new_bytes).await?;
+    drop(conn);
 
-    if let Some(skills) = skills {
-        let skill_ids = sqlx::query!("SELECT id, name FROM skills WHERE name = ANY($1)", &skills)
-            .fetch_all(&data.db)
-            .await
-            .map_err(|e| {
-                eprintln!("Error retrieving skill IDs: {:?}", e);
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(json!({ "status": "error", "message": "Internal Server Error" })),
-                )
-            })?;
+    let photos = prepare_profile_photos(&data, profile_id, photos).await?;
 
-        let found_skill_names: Vec<String> = skill_ids.iter().map(|s| s.name.clone()).collect();
-        let missing_skills: Vec<String> = skills
-            .iter()
-            .filter(|s| !found_skill_names.contains(s))
-            .cloned()
-            .collect();
+    let result = async {
+        let mut tx = data.db.begin().await.map_err(internal_error)?;
 
-        if !missing_skills.is_empty() {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                Json(json!({
-                    "status": "fail",
-                    "message": "Some skills are not recognized",
-                    "missing_skills": missing_skills
-                })),
-            ));
-        }
+        sqlx::query!(
+            "SELECT id FROM profiles WHERE id = $1 FOR UPDATE",
+            profile_id
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(internal_error)?;
+        check_photo_limits(&mut tx, &limits, profile_id, &[], new_photos, new_bytes).await?;
 
-        for skill in skill_ids {
-            sqlx::query!(
-                "INSERT INTO profile_skill (profile_id, skill_id) VALUES ($1, $2)",
-                profile_id,
-                skill.id
-            )
-            .execute(&data.db)
+        let before = load_profile_snapshot(&mut tx, profile_id)
             .await
-            .map_err(|e| {
-                eprintln!("Error inserting into profile_skill: {:?}", e);
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(json!({ "status": "error", "message": "Internal Server Error" })),
-                )
-            })?;
-        }
-    }
-
-    for photo_id in &deleted_photos {
-        let delete_result = sqlx::query!(
-            "DELETE FROM photos WHERE id = $1 AND profile_id = $2",
-            photo_id,
-            profile_id
+            .map_err(internal_error)?;
+        insert_profile_photos(&mut tx, profile_id, &photos)
+            .await
+            .map_err(internal_error)?;
+        let review_required = record_revision(
+            &mut tx,
+            &data,
+            profile_id,
+            viewer_id,
+            is_admin,
+            Some(&before),
+            None,
         )
-        .execute(&data.db)
-        .await;
+        .await
+        .map_err(internal_error)?;
 
-        if let Err(e) = delete_result {
-            eprintln!("Error deleting photo {photo_id}: {e}");
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({ "status": "fail", "message": "Internal Server Error" })),
-            ));
-        }
+        tx.commit().await.map_err(internal_error)?;
+        Ok(review_required)
     }
+    .await;
 
-    for (file_name, _original_content_type, original_bytes) in photos {
-        let dyn_img = match ImageReader::new(Cursor::new(&original_bytes)).with_guessed_format() {
-            Ok(reader) => match reader.decode() {
-                Ok(img) => img,
-                Err(err) => {
-                    eprintln!("Failed to decode image: {:?}", err);
-                    return Err((
-                        StatusCode::BAD_REQUEST,
-                        Json(json!({ "status": "fail", "message": "Invalid image data" })),
-                    ));
-                }
-            },
-            Err(err) => {
-                eprintln!("Failed to guess format: {:?}", err);
-                return Err((
-                    StatusCode::BAD_REQUEST,
-                    Json(json!({ "status": "fail", "message": "Invalid image data" })),
-                ));
-            }
-        };
+    let review_required = match result {
+        Ok(review_required) => review_required,
+        Err(e) => {
+            discard_photos(&data, &photos).await;
+            return Err(e);
+        }
+    };
 
-        let (orig_w, orig_h) = dyn_img.dimensions();
-        let max_dim = 800u32;
+    if review_required {
+        notify_status_change(&data, profile_id, ProfileStatus::Pending, None).await;
+    }
 
-        let scale_w = max_dim as f64 / orig_w as f64;
-        let scale_h = max_dim as f64 / orig_h as f64;
-        let scale = scale_w.min(scale_h).min(1.0);
+    let response_data: Vec<serde_json::Value> = photos
+        .iter()
+        .map(|photo| {
+            json!({
+                "id": photo.id,
+                "_links": photo_links(&data.url, photo.id)
+            })
+        })
+        .collect();
 
-        let new_w = (orig_w as f64 * scale).round() as u32;
-        let new_h = (orig_h as f64 * scale).round() as u32;
+    Ok((
+        StatusCode::CREATED,
+        Json(json!({
+            "status": "success",
+            "data": response_data,
+            "review_required": review_required
+        })),
+    ))
+}
 
-        let resized_img = if new_w != orig_w || new_h != orig_h {
-            dyn_img.resize_exact(new_w, new_h, FilterType::CatmullRom)
-        } else {
-            dyn_img
-        };
+pub async fn delete_profile_photo(
+    State(data): State<Arc<AppState>>,
+    AuthenticatedViewer {
+        viewer_id,
+        is_admin,
+    }: AuthenticatedViewer,
+    Path((profile_id, photo_id)): Path<(Uuid, Uuid)>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    ensure_can_edit_profile(&data.db, profile_id, viewer_id, is_admin).await?;
 
-        let mut quality = 90;
-        let mut compressed_bytes = Vec::new();
-        const MAX_SIZE: usize = 400_000;
-        const MIN_QUALITY: u8 = 10;
+    let internal_error = |e: sqlx::Error| {
+        eprintln!("Error deleting photo {photo_id}: {e}");
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "status": "fail", "message": "Internal Server Error" })),
+        )
+    };
 
-        loop {
-            compressed_bytes.clear();
-            let mut encoder = JpegEncoder::new_with_quality(&mut compressed_bytes, quality);
-            if let Err(e) = encoder.encode_image(&resized_img) {
-                eprintln!("JPEG encode error: {:?}", e);
-                return Err((
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(json!({ "status": "fail", "message": "Failed to compress image" })),
-                ));
-            }
+    let mut tx = data.db.begin().await.map_err(internal_error)?;
 
-            if compressed_bytes.len() <= MAX_SIZE {
-                break;
-            }
+    let before = lock_profile_snapshot(&mut tx, profile_id).await?;
 
-            if quality <= MIN_QUALITY {
-                println!(
-                    "WARNING: Could not reduce below 200 KB even at Q={}",
-                    quality
-                );
-                break;
-            }
+    let result = sqlx::query!(
+        "DELETE FROM photos WHERE id = $1 AND profile_id = $2",
+        photo_id,
+        profile_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(internal_error)?;
 
-            quality = quality.saturating_sub(5);
-        }
+    if result.rows_affected() == 0 {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({ "status": "fail", "message": "Photo not found" })),
+        ));
+    }
 
-        let jpeg_content_type = "image/jpeg";
-        let query_result = sqlx::query!(
-            r#"INSERT INTO photos (profile_id, file_name, content_type, photo_data)
-               VALUES ($1, $2, $3, $4)"#,
-            profile_id,
-            &file_name,
-            jpeg_content_type,
-            &compressed_bytes
-        )
-        .execute(&data.db)
-        .await;
+    let review_required = record_revision(
+        &mut tx,
+        &data,
+        profile_id,
+        viewer_id,
+        is_admin,
+        Some(&before),
+        None,
+    )
+    .await
+    .map_err(internal_error)?;
 
-        match query_result {
-            Err(e) => {
-                if e.to_string().contains("duplicate key") {
-                    println!("Duplicate photo found.");
-                    continue;
-                }
-                eprintln!("upload_photos fail: insert into db: {:?}", e);
-                return Err((
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(json!({ "status": "fail", "message": "Internal Server Error" })),
-                ));
-            }
-            Ok(_) => {
-                println!(
-                    "Photo inserted. final size={} KB, quality={}",
-                    compressed_bytes.len() / 1000,
-                    quality
-                );
-            }
-        }
+    tx.commit().await.map_err(internal_error)?;
+
+    if review_required {
+        notify_status_change(&data, profile_id, ProfileStatus::Pending, None).await;
     }
 
     Ok((
         StatusCode::OK,
-        Json(json!({"status": "success","message": "Profile updated successfully."})),
+        Json(json!({
+            "status": "success",
+            "message": "Photo deleted.",
+            "review_required": review_required
+        })),
     ))
 }
 
@@ -1400,7 +1984,7 @@ pub async fn get_unaccepted_profiles(
         r#"
         SELECT p.id
         FROM profiles p
-        WHERE p.accepted = false
+        WHERE p.status = 'pending'
         "#
     )
     .fetch_all(&data.db)
@@ -1492,67 +2076,33 @@ pub async fn get_profiles_without_viewer(
     })))
 }
 
-pub async fn accept_profile(
+pub async fn get_profile_email(
     State(data): State<Arc<AppState>>,
+    viewer: AuthenticatedViewer,
     Path(profile_id): Path<Uuid>,
-    AuthenticatedViewer {
-        is_admin, ..
-    }: AuthenticatedViewer,
 ) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    if !is_admin {
-        return Err((
-            StatusCode::UNAUTHORIZED,
-            Json(json!({
-                "status": "fail",
-                "message": "Only admins can accept profiles."
-            })),
-        ));
-    }
-
-    let result = sqlx::query!(
-        r#"
-        UPDATE profiles
-        SET accepted = true
-        WHERE id = $1
-        "#,
-        profile_id
-    )
-    .execute(&data.db)
-    .await
-    .map_err(|e| {
-        eprintln!("Error updating profile accepted: {:?}", e);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({
-                "status": "fail",
-                "message": "Internal Server Error"
-            })),
-        )
-    })?;
-
-    if result.rows_affected() == 0 {
+    let show_contact = can_see_contact(&data.db, profile_id, Some(&viewer))
+        .await
+        .map_err(|e| {
+            eprintln!("Error checking contact access: {:?}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({
+                    "status": "fail",
+                    "message": "Internal Server Error"
+                })),
+            )
+        })?;
+    if !show_contact {
         return Err((
-            StatusCode::NOT_FOUND,
+            StatusCode::FORBIDDEN,
             Json(json!({
                 "status": "fail",
-                "message": "Profile not found"
+                "message": "Contact details are only shared through messages"
             })),
         ));
     }
 
-    Ok((
-        StatusCode::OK,
-        Json(json!({
-            "status": "success",
-            "message": "Profile accepted successfully"
-        })),
-    ))
-}
-
-pub async fn get_profile_email(
-    State(data): State<Arc<AppState>>,
-    Path(profile_id): Path<Uuid>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
     let record = sqlx::query!(
         r#"
         SELECT v.email
@@ -1,98 +1,981 @@
-use std::{error::Error, time::Duration};
-
-use chromiumoxide::{Browser, BrowserConfig};
-use futures::StreamExt;
-use scraper::{Html, Selector};
-use tokio::time::sleep;
-
-pub struct Rating {
-    rating: f32,
-    review_count: u32,
-}
-
-pub async fn get_ratings_by_google_maps_link(google_maps_link: &str) -> Option<Rating> {
-    return match scrape_page(google_maps_link).await {
-        Ok(html) => {
-            if let Some((rating, review_count)) = extract_review_data(&html).await {
-                Some(Rating {
-                    rating,
-                    review_count,
-                })
-            } else {
-                None
-            }
-        }
-        Err(e) => {
-            eprintln!("❌ Error: {}", e);
-            None
-        }
-    };
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde_json::json;
+use sqlx::{PgConnection, Pool, Postgres};
+use uuid::Uuid;
+
+use crate::{
+    model::{ReviewModel, ReviewStatus},
+    rating_provider::{parse_ratings_csv, store_ratings, PLACES_SOURCE, REVIEWS_SOURCE},
+    schema::{
+        ModerationSchema, PageQuery, RatingSourceSchema, ReviewInput, ReviewReplySchema,
+        ReviewReportSchema,
+    },
+    validation::{contains_blocked_word, contains_link, validation_error_response, FieldError},
+    AppState,
+};
+
+use super::{
+    auth::AuthenticatedViewer, moderation::required_reason, profile::ensure_can_edit_profile,
+};
+
+const PAGE_LIMIT_DEFAULT: i64 = 20;
+const PAGE_LIMIT_MAX: i64 = 100;
+
+// Reviews shown with the profile itself
+const PROFILE_REVIEWS: i64 = 5;
+
+fn internal_error(e: impl std::fmt::Debug) -> (StatusCode, Json<serde_json::Value>) {
+    eprintln!("rating error: {:?}", e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(json!({ "status": "fail", "message": "Internal Server Error" })),
+    )
+}
+
+fn review_not_found() -> (StatusCode, Json<serde_json::Value>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({ "status": "fail", "message": "Review not found" })),
+    )
+}
+
+#[derive(Default)]
+struct ReviewFilter {
+    id: Option<Uuid>,
+    profile_id: Option<Uuid>,
+    status: Option<ReviewStatus>,
+}
+
+async fn load_reviews(
+    db: &Pool<Postgres>,
+    filter: ReviewFilter,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<ReviewModel>, sqlx::Error> {
+    sqlx::query_as!(
+        ReviewModel,
+        r#"
+        SELECT r.id, r.profile_id,
+            v.first_name || COALESCE(' ' || NULLIF(LEFT(v.last_name, 1), '') || '.', '') AS "author_name!",
+            r.rating, r.body, r.status, r.inquiry_id, r.owner_reply, r.owner_replied_at,
+            r.created_at, r.updated_at
+        FROM reviews r
+        JOIN viewers v ON v.id = r.viewer_id
+        WHERE ($1::uuid IS NULL OR r.id = $1)
+            AND ($2::uuid IS NULL OR r.profile_id = $2)
+            AND ($3::text IS NULL OR r.status = $3)
+        ORDER BY r.created_at DESC, r.id
+        LIMIT $4 OFFSET $5
+        "#,
+        filter.id,
+        filter.profile_id,
+        filter.status.map(|status| status.as_str()),
+        limit,
+        offset
+    )
+    .fetch_all(db)
+    .await
+}
+
+async fn load_review(
+    db: &Pool<Postgres>,
+    review_id: Uuid,
+) -> Result<ReviewModel, (StatusCode, Json<serde_json::Value>)> {
+    load_reviews(
+        db,
+        ReviewFilter {
+            id: Some(review_id),
+            ..Default::default()
+        },
+        1,
+        0,
+    )
+    .await
+    .map_err(internal_error)?
+    .pop()
+    .ok_or_else(review_not_found)
 }
 
-async fn extract_review_data(html: &str) -> Option<(f32, u32)> {
-    let document = Html::parse_document(html);
+// Average and number of reviews of a profile from `ratings`
+pub async fn load_rating(
+    db: &Pool<Postgres>,
+    profile_id: Uuid,
+) -> Result<serde_json::Value, sqlx::Error> {
+    let rating = sqlx::query!(
+        "SELECT rating, review_count FROM ratings WHERE profile_id = $1 AND source = $2",
+        profile_id,
+        REVIEWS_SOURCE
+    )
+    .fetch_optional(db)
+    .await?;
 
-    let rating_selector = Selector::parse(".fontDisplayLarge").unwrap();
-    let rating = document
-        .select(&rating_selector)
-        .next()
-        .map(|el| el.text().collect::<String>())
-        .map(|e| e.replace(",", "."))
-        .map(|el| el.parse::<f32>());
+    Ok(match rating {
+        Some(rating) => json!({
+            "average": (rating.rating * 10.0).round() / 10.0,
+            "count": rating.review_count
+        }),
+        None => json!({ "average": null, "count": 0 }),
+    })
+}
+
+// Ratings imported from other platforms, each with where and when it was
+// fetched
+pub async fn load_external_ratings(
+    db: &Pool<Postgres>,
+    profile_id: Uuid,
+) -> Result<Vec<serde_json::Value>, sqlx::Error> {
+    let ratings = sqlx::query!(
+        r#"
+        SELECT source, rating, review_count, url, fetched_at
+        FROM ratings
+        WHERE profile_id = $1 AND source <> $2
+        ORDER BY source
+        "#,
+        profile_id,
+        REVIEWS_SOURCE
+    )
+    .fetch_all(db)
+    .await?;
 
-    let review_count_selector = Selector::parse("button.GQjSyb span").unwrap();
-    let review_count = document
-        .select(&review_count_selector)
-        .next()
-        .map(|el| el.text().collect::<String>())
-        .map(|e| e.replace(",", "."))
-        .map(|el| el.split_whitespace().collect::<Vec<_>>()[0].parse::<u32>());
+    Ok(ratings
+        .into_iter()
+        .map(|rating| {
+            json!({
+                "source": rating.source,
+                "average": (rating.rating * 10.0).round() / 10.0,
+                "count": rating.review_count,
+                "url": rating.url,
+                "fetched_at": rating.fetched_at
+            })
+        })
+        .collect())
+}
+
+// The most recent published reviews for the profile page
+pub async fn load_recent_reviews(
+    db: &Pool<Postgres>,
+    profile_id: Uuid,
+) -> Result<Vec<ReviewModel>, sqlx::Error> {
+    load_reviews(
+        db,
+        ReviewFilter {
+            profile_id: Some(profile_id),
+            status: Some(ReviewStatus::Published),
+            ..Default::default()
+        },
+        PROFILE_REVIEWS,
+        0,
+    )
+    .await
+}
+
+// Recomputes the profile's row in `ratings` from its published reviews
+async fn refresh_rating(conn: &mut PgConnection, profile_id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        WITH aggregate AS (
+            SELECT AVG(rating)::FLOAT8 AS rating, LEAST(COUNT(*), 32767)::SMALLINT AS review_count
+            FROM reviews
+            WHERE profile_id = $1 AND status = 'published'
+        ),
+        removed AS (
+            DELETE FROM ratings
+            WHERE profile_id = $1 AND source = $2 AND (SELECT review_count FROM aggregate) = 0
+        )
+        INSERT INTO ratings (profile_id, source, rating, review_count)
+        SELECT $1, $2, rating, review_count
+        FROM aggregate
+        WHERE review_count > 0
+        ON CONFLICT (profile_id, source) DO UPDATE
+        SET rating = EXCLUDED.rating,
+            review_count = EXCLUDED.review_count,
+            fetched_at = NOW(),
+            updated_at = NOW(),
+            version = ratings.version % 32767 + 1
+        "#,
+        profile_id,
+        REVIEWS_SOURCE
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
 
-    return if let (Some(Ok(rating)), Some(Ok(review_count))) = (rating, review_count) {
-        Some((rating, review_count))
+// Moves a review to `to` if its current status is one of `from` and logs the
+// change. Returns the previous status, or None if the review is missing or
+// was in another status.
+async fn change_review_status(
+    conn: &mut PgConnection,
+    review_id: Uuid,
+    viewer_id: Option<Uuid>,
+    from: &[ReviewStatus],
+    to: ReviewStatus,
+    reason: Option<&str>,
+) -> Result<Option<ReviewStatus>, sqlx::Error> {
+    let allowed_from: Vec<String> = from
+        .iter()
+        .map(|status| status.as_str().to_string())
+        .collect();
+
+    let record = sqlx::query!(
+        r#"
+        UPDATE reviews r
+        SET status = $2,
+            status_reason = $3,
+            status_changed_at = NOW(),
+            version = r.version % 32767 + 1
+        FROM (SELECT id, status FROM reviews WHERE id = $1 FOR UPDATE) old
+        WHERE r.id = old.id AND old.status = ANY($4)
+        RETURNING old.status AS from_status
+        "#,
+        review_id,
+        to.as_str(),
+        reason,
+        &allowed_from
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    let Some(record) = record else {
+        return Ok(None);
+    };
+
+    sqlx::query!(
+        r#"
+        INSERT INTO review_status_changes (review_id, viewer_id, from_status, to_status, reason)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        review_id,
+        viewer_id,
+        record.from_status,
+        to.as_str(),
+        reason
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(ReviewStatus::parse(&record.from_status))
+}
+
+// Why a review text has to wait for an admin before it is published
+fn hold_reason(data: &AppState, body: &str) -> Option<&'static str> {
+    if contains_link(body) {
+        Some("contains a link")
+    } else if contains_blocked_word(body, &data.review_blocked_words) {
+        Some("contains a blocked word")
     } else {
         None
-    };
+    }
 }
 
-async fn scrape_page(url: &str) -> Result<String, Box<dyn Error>> {
-    let config = BrowserConfig::builder()
-        .chrome_executable(
-            "/Applications/Google Chrome Canary.app/Contents/MacOS/Google Chrome Canary",
+// A linked inquiry must be the reviewer's own, at this profile and done
+async fn check_review_inquiry(
+    db: &Pool<Postgres>,
+    input: &ReviewInput,
+    profile_id: Uuid,
+    viewer_id: Uuid,
+) -> Result<Option<FieldError>, (StatusCode, Json<serde_json::Value>)> {
+    let Some(inquiry_id) = input.inquiry_id else {
+        return Ok(None);
+    };
+
+    let completed = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM inquiries
+            WHERE id = $1 AND profile_id = $2 AND viewer_id = $3 AND status = 'done'
+        ) AS "completed!"
+        "#,
+        inquiry_id,
+        profile_id,
+        viewer_id
+    )
+    .fetch_one(db)
+    .await
+    .map_err(internal_error)?;
+
+    Ok((!completed).then(|| {
+        FieldError::new(
+            "inquiry_id",
+            "not_completed",
+            "must be one of your completed inquiries at this profile",
         )
-        .no_sandbox()
-        .build()?;
+    }))
+}
 
-    // Launch browser
-    let (mut browser, mut handler) = Browser::launch(config).await?;
+pub async fn get_profile_reviews(
+    State(data): State<Arc<AppState>>,
+    Path(profile_id): Path<Uuid>,
+    Query(query): Query<PageQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let limit = query
+        .limit
+        .unwrap_or(PAGE_LIMIT_DEFAULT)
+        .clamp(1, PAGE_LIMIT_MAX);
+    let page = query.page.unwrap_or(1).max(1);
 
-    // Keep event loop running to avoid crashes
-    tokio::spawn(async move { while let Some(_) = handler.next().await {} });
+    let reviews = load_reviews(
+        &data.db,
+        ReviewFilter {
+            profile_id: Some(profile_id),
+            status: Some(ReviewStatus::Published),
+            ..Default::default()
+        },
+        limit,
+        (page - 1) * limit,
+    )
+    .await
+    .map_err(internal_error)?;
 
-    // Open a new tab
-    let page = browser.new_page(url).await?;
-    println!("🚀 Page loaded");
+    let rating = load_rating(&data.db, profile_id)
+        .await
+        .map_err(internal_error)?;
 
-    // Wait for the cookie pop-up to appear
-    sleep(Duration::from_secs(5)).await;
+    Ok(Json(json!({
+        "status": "success",
+        "data": reviews,
+        "rating": rating,
+        "page": page,
+        "limit": limit
+    })))
+}
 
-    // Try to find and click the "Accept All" button
-    let accept_button_xpath = "//span[@jsname='V67aGc']/ancestor::button";
-    if let Some(accept_button) = page.find_element(accept_button_xpath).await.ok() {
-        println!("🍪 Found 'Accept All' button. Clicking...");
-        accept_button.click().await.ok();
-        sleep(Duration::from_secs(3)).await; // Wait for page to reload
-    } else {
-        println!("❌ 'Accept All' button not found.");
+pub async fn create_review(
+    State(data): State<Arc<AppState>>,
+    AuthenticatedViewer { viewer_id, .. }: AuthenticatedViewer,
+    Path(profile_id): Path<Uuid>,
+    Json(body): Json<ReviewInput>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let owner_id = sqlx::query_scalar!(
+        "SELECT viewer_id FROM profiles WHERE id = $1 AND status = 'approved'",
+        profile_id
+    )
+    .fetch_optional(&data.db)
+    .await
+    .map_err(internal_error)?
+    .ok_or((
+        StatusCode::NOT_FOUND,
+        Json(json!({ "status": "fail", "message": "Profile not found" })),
+    ))?;
+
+    if owner_id == Some(viewer_id) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "status": "fail",
+                "message": "You cannot review your own profile."
+            })),
+        ));
     }
 
-    // Scrape the **actual** page content after accepting cookies
-    let html = page.content().await?;
+    let mut errors = body.validate();
+    errors.extend(check_review_inquiry(&data.db, &body, profile_id, viewer_id).await?);
+    if !errors.is_empty() {
+        return Err(validation_error_response(errors));
+    }
 
-    // Close browser
-    browser.close().await?;
+    let hold = hold_reason(&data, body.body.trim());
+    let status = match hold {
+        Some(_) => ReviewStatus::Held,
+        None => ReviewStatus::Published,
+    };
+
+    let mut tx = data.db.begin().await.map_err(internal_error)?;
+
+    let review_id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO reviews (profile_id, viewer_id, inquiry_id, rating, body, status, status_reason)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        ON CONFLICT (profile_id, viewer_id) DO NOTHING
+        RETURNING id
+        "#,
+        profile_id,
+        viewer_id,
+        body.inquiry_id,
+        body.rating,
+        body.body.trim(),
+        status.as_str(),
+        hold
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(internal_error)?
+    .ok_or((
+        StatusCode::CONFLICT,
+        Json(json!({
+            "status": "fail",
+            "message": "You have already reviewed this profile."
+        })),
+    ))?;
+
+    refresh_rating(&mut tx, profile_id)
+        .await
+        .map_err(internal_error)?;
 
-    Ok(html)
+    tx.commit().await.map_err(internal_error)?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(json!({
+            "status": "success",
+            "data": load_review(&data.db, review_id).await?
+        })),
+    ))
 }
 
-pub async fn insert_rating
+// The author edits rating and text of their review
+pub async fn update_review(
+    State(data): State<Arc<AppState>>,
+    AuthenticatedViewer { viewer_id, .. }: AuthenticatedViewer,
+    Path(review_id): Path<Uuid>,
+    Json(body): Json<ReviewInput>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let profile_id = sqlx::query_scalar!(
+        "SELECT profile_id FROM reviews WHERE id = $1 AND viewer_id = $2",
+        review_id,
+        viewer_id
+    )
+    .fetch_optional(&data.db)
+    .await
+    .map_err(internal_error)?
+    .ok_or_else(review_not_found)?;
+
+    let mut errors = body.validate();
+    errors.extend(check_review_inquiry(&data.db, &body, profile_id, viewer_id).await?);
+    if !errors.is_empty() {
+        return Err(validation_error_response(errors));
+    }
+
+    let mut tx = data.db.begin().await.map_err(internal_error)?;
+
+    sqlx::query!(
+        r#"
+        UPDATE reviews
+        SET rating = $2, body = $3, inquiry_id = $4,
+            updated_at = NOW(), version = version % 32767 + 1
+        WHERE id = $1
+        "#,
+        review_id,
+        body.rating,
+        body.body.trim(),
+        body.inquiry_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+
+    // Holds are automatic, so an edited text is held or released again.
+    // Reviews hidden by an admin stay hidden.
+    match hold_reason(&data, body.body.trim()) {
+        Some(reason) => change_review_status(
+            &mut tx,
+            review_id,
+            None,
+            &[ReviewStatus::Published],
+            ReviewStatus::Held,
+            Some(reason),
+        ),
+        None => change_review_status(
+            &mut tx,
+            review_id,
+            None,
+            &[ReviewStatus::Held],
+            ReviewStatus::Published,
+            None,
+        ),
+    }
+    .await
+    .map_err(internal_error)?;
+
+    refresh_rating(&mut tx, profile_id)
+        .await
+        .map_err(internal_error)?;
+
+    tx.commit().await.map_err(internal_error)?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "data": load_review(&data.db, review_id).await?
+    })))
+}
+
+// Authors may withdraw their review, admins may remove any
+pub async fn delete_review(
+    State(data): State<Arc<AppState>>,
+    AuthenticatedViewer {
+        viewer_id,
+        is_admin,
+    }: AuthenticatedViewer,
+    Path(review_id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let mut tx = data.db.begin().await.map_err(internal_error)?;
+
+    let profile_id = sqlx::query_scalar!(
+        "DELETE FROM reviews WHERE id = $1 AND (viewer_id = $2 OR $3) RETURNING profile_id",
+        review_id,
+        viewer_id,
+        is_admin
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(internal_error)?
+    .ok_or_else(review_not_found)?;
+
+    refresh_rating(&mut tx, profile_id)
+        .await
+        .map_err(internal_error)?;
+
+    tx.commit().await.map_err(internal_error)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// The profile owner answers a review publicly, once
+pub async fn reply_to_review(
+    State(data): State<Arc<AppState>>,
+    AuthenticatedViewer {
+        viewer_id,
+        is_admin,
+    }: AuthenticatedViewer,
+    Path(review_id): Path<Uuid>,
+    Json(body): Json<ReviewReplySchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let profile_id = sqlx::query_scalar!("SELECT profile_id FROM reviews WHERE id = $1", review_id)
+        .fetch_optional(&data.db)
+        .await
+        .map_err(internal_error)?
+        .ok_or_else(review_not_found)?;
+
+    ensure_can_edit_profile(&data.db, profile_id, viewer_id, is_admin).await?;
+
+    let errors = body.validate();
+    if !errors.is_empty() {
+        return Err(validation_error_response(errors));
+    }
+
+    let result = sqlx::query!(
+        r#"
+        UPDATE reviews
+        SET owner_reply = $2, owner_replied_at = NOW(),
+            updated_at = NOW(), version = version % 32767 + 1
+        WHERE id = $1 AND owner_reply IS NULL
+        "#,
+        review_id,
+        body.reply.trim()
+    )
+    .execute(&data.db)
+    .await
+    .map_err(internal_error)?;
+
+    if result.rows_affected() == 0 {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(json!({
+                "status": "fail",
+                "message": "This review has already been answered."
+            })),
+        ));
+    }
+
+    Ok(Json(json!({
+        "status": "success",
+        "data": load_review(&data.db, review_id).await?
+    })))
+}
+
+// Any signed-in viewer may report a published review once
+pub async fn report_review(
+    State(data): State<Arc<AppState>>,
+    AuthenticatedViewer { viewer_id, .. }: AuthenticatedViewer,
+    Path(review_id): Path<Uuid>,
+    Json(body): Json<ReviewReportSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let errors = body.validate();
+    if !errors.is_empty() {
+        return Err(validation_error_response(errors));
+    }
+
+    let published = sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM reviews WHERE id = $1 AND status = 'published') AS "published!""#,
+        review_id
+    )
+    .fetch_one(&data.db)
+    .await
+    .map_err(internal_error)?;
+    if !published {
+        return Err(review_not_found());
+    }
+
+    let comment = body
+        .comment
+        .as_deref()
+        .map(str::trim)
+        .filter(|comment| !comment.is_empty());
+
+    let report_id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO review_reports (review_id, viewer_id, reason, comment)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (review_id, viewer_id) DO NOTHING
+        RETURNING id
+        "#,
+        review_id,
+        viewer_id,
+        body.reason.trim(),
+        comment
+    )
+    .fetch_optional(&data.db)
+    .await
+    .map_err(internal_error)?
+    .ok_or((
+        StatusCode::CONFLICT,
+        Json(json!({
+            "status": "fail",
+            "message": "You have already reported this review."
+        })),
+    ))?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(json!({
+            "status": "success",
+            "data": { "id": report_id }
+        })),
+    ))
+}
+
+fn admin_only(is_admin: bool) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    if !is_admin {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            Json(json!({
+                "status": "fail",
+                "message": "Only admins can moderate reviews."
+            })),
+        ));
+    }
+    Ok(())
+}
+
+// Admin queue: held reviews and reviews with open reports, most reported first
+pub async fn get_reported_reviews(
+    State(data): State<Arc<AppState>>,
+    AuthenticatedViewer { is_admin, .. }: AuthenticatedViewer,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    admin_only(is_admin)?;
+
+    let reviews = sqlx::query!(
+        r#"
+        SELECT r.id, r.profile_id, p.name AS profile_name, r.rating, r.body, r.status,
+            r.status_reason, r.created_at,
+            COUNT(rr.id) AS "report_count!",
+            COALESCE(
+                array_agg(DISTINCT rr.reason) FILTER (WHERE rr.reason IS NOT NULL),
+                '{}'
+            ) AS "report_reasons!",
+            COALESCE(
+                array_agg(rr.comment ORDER BY rr.created_at) FILTER (WHERE rr.comment IS NOT NULL),
+                '{}'
+            ) AS "report_comments!",
+            MAX(rr.created_at) AS last_reported_at
+        FROM reviews r
+        JOIN profiles p ON p.id = r.profile_id
+        LEFT JOIN review_reports rr ON rr.review_id = r.id AND rr.resolved_at IS NULL
+        GROUP BY r.id, p.name
+        HAVING r.status = 'held' OR COUNT(rr.id) > 0
+        ORDER BY COUNT(rr.id) DESC, r.created_at
+        "#
+    )
+    .fetch_all(&data.db)
+    .await
+    .map_err(internal_error)?;
+
+    let reviews: Vec<serde_json::Value> = reviews
+        .into_iter()
+        .map(|review| {
+            json!({
+                "id": review.id,
+                "profile_id": review.profile_id,
+                "profile_name": review.profile_name,
+                "rating": review.rating,
+                "body": review.body,
+                "review_status": review.status,
+                "status_reason": review.status_reason,
+                "created_at": review.created_at,
+                "report_count": review.report_count,
+                "report_reasons": review.report_reasons,
+                "report_comments": review.report_comments,
+                "last_reported_at": review.last_reported_at
+            })
+        })
+        .collect();
+
+    Ok(Json(json!({
+        "status": "success",
+        "data": reviews
+    })))
+}
+
+// Closes the open reports once an admin has acted on the review
+async fn resolve_reports(conn: &mut PgConnection, review_id: Uuid) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE review_reports
+        SET resolved_at = NOW(), updated_at = NOW(), version = version % 32767 + 1
+        WHERE review_id = $1 AND resolved_at IS NULL
+        "#,
+        review_id
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+// Shared by hide and restore; the rating only counts published reviews
+async fn moderate_review(
+    data: &AppState,
+    review_id: Uuid,
+    viewer_id: Uuid,
+    to: ReviewStatus,
+    reason: Option<&str>,
+) -> Result<ReviewModel, (StatusCode, Json<serde_json::Value>)> {
+    let mut tx = data.db.begin().await.map_err(internal_error)?;
+
+    let current = sqlx::query!(
+        "SELECT profile_id, status FROM reviews WHERE id = $1 FOR UPDATE",
+        review_id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(internal_error)?
+    .ok_or_else(review_not_found)?;
+
+    let changed = change_review_status(
+        &mut tx,
+        review_id,
+        Some(viewer_id),
+        to.allowed_from(),
+        to,
+        reason,
+    )
+    .await
+    .map_err(internal_error)?;
+    let resolved = resolve_reports(&mut tx, review_id)
+        .await
+        .map_err(internal_error)?;
+
+    // Restoring a published review dismisses its reports
+    let dismissed = to == ReviewStatus::Published && resolved > 0;
+    if changed.is_none() && !dismissed {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(json!({
+                "status": "fail",
+                "message": format!(
+                    "Review cannot change from {} to {}.",
+                    current.status,
+                    to.as_str()
+                )
+            })),
+        ));
+    }
+    refresh_rating(&mut tx, current.profile_id)
+        .await
+        .map_err(internal_error)?;
+
+    tx.commit().await.map_err(internal_error)?;
+
+    load_review(&data.db, review_id).await
+}
+
+pub async fn hide_review(
+    State(data): State<Arc<AppState>>,
+    AuthenticatedViewer {
+        viewer_id,
+        is_admin,
+    }: AuthenticatedViewer,
+    Path(review_id): Path<Uuid>,
+    Json(body): Json<ModerationSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    admin_only(is_admin)?;
+    let reason = required_reason(&body)?;
+
+    let review = moderate_review(
+        &data,
+        review_id,
+        viewer_id,
+        ReviewStatus::Hidden,
+        Some(reason),
+    )
+    .await?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "data": review
+    })))
+}
+
+pub async fn restore_review(
+    State(data): State<Arc<AppState>>,
+    AuthenticatedViewer {
+        viewer_id,
+        is_admin,
+    }: AuthenticatedViewer,
+    Path(review_id): Path<Uuid>,
+    Json(body): Json<ModerationSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    admin_only(is_admin)?;
+
+    let reason = match body.reason.as_deref().map(str::trim) {
+        Some(reason) if !reason.is_empty() => Some(required_reason(&body)?),
+        _ => None,
+    };
+
+    let review =
+        moderate_review(&data, review_id, viewer_id, ReviewStatus::Published, reason).await?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "data": review
+    })))
+}
+
+// Admin links a profile to its entry at an external provider, or unlinks it
+// with a null `external_id`
+pub async fn update_rating_source(
+    State(data): State<Arc<AppState>>,
+    AuthenticatedViewer { is_admin, .. }: AuthenticatedViewer,
+    Path(profile_id): Path<Uuid>,
+    Json(body): Json<RatingSourceSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    admin_only(is_admin)?;
+
+    let errors = body.validate(&[PLACES_SOURCE]);
+    if !errors.is_empty() {
+        return Err(validation_error_response(errors));
+    }
+
+    let exists = sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM profiles WHERE id = $1) AS "exists!""#,
+        profile_id
+    )
+    .fetch_one(&data.db)
+    .await
+    .map_err(internal_error)?;
+    if !exists {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({ "status": "fail", "message": "Profile not found" })),
+        ));
+    }
+
+    let source = body.source.trim();
+    match body.external_id.as_deref().map(str::trim) {
+        Some(external_id) if !external_id.is_empty() => {
+            sqlx::query!(
+                r#"
+                INSERT INTO profile_rating_sources (profile_id, source, external_id)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (profile_id, source) DO UPDATE
+                SET external_id = EXCLUDED.external_id,
+                    updated_at = NOW(),
+                    version = profile_rating_sources.version % 32767 + 1
+                "#,
+                profile_id,
+                source,
+                external_id
+            )
+            .execute(&data.db)
+            .await
+            .map_err(internal_error)?;
+        }
+        _ => {
+            let mut tx = data.db.begin().await.map_err(internal_error)?;
+            sqlx::query!(
+                "DELETE FROM profile_rating_sources WHERE profile_id = $1 AND source = $2",
+                profile_id,
+                source
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(internal_error)?;
+            sqlx::query!(
+                "DELETE FROM ratings WHERE profile_id = $1 AND source = $2",
+                profile_id,
+                source
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(internal_error)?;
+            tx.commit().await.map_err(internal_error)?;
+        }
+    }
+
+    let sources = sqlx::query!(
+        "SELECT source, external_id FROM profile_rating_sources WHERE profile_id = $1 ORDER BY source",
+        profile_id
+    )
+    .fetch_all(&data.db)
+    .await
+    .map_err(internal_error)?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "data": sources
+            .into_iter()
+            .map(|s| json!({ "source": s.source, "external_id": s.external_id }))
+            .collect::<Vec<_>>()
+    })))
+}
+
+// Admin imports ratings from a CSV body with the columns
+// profile_id, rating, review_count and optionally url and source. Nothing is
+// stored if a row is invalid.
+pub async fn import_ratings(
+    State(data): State<Arc<AppState>>,
+    AuthenticatedViewer { is_admin, .. }: AuthenticatedViewer,
+    body: String,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    admin_only(is_admin)?;
+
+    let (ratings, mut errors) = parse_ratings_csv(&body);
+
+    let profile_ids: Vec<Uuid> = ratings.iter().map(|rating| rating.profile_id).collect();
+    let known = sqlx::query_scalar!("SELECT id FROM profiles WHERE id = ANY($1)", &profile_ids)
+        .fetch_all(&data.db)
+        .await
+        .map_err(internal_error)?;
+    for rating in &ratings {
+        if !known.contains(&rating.profile_id) {
+            errors.push(FieldError::unknown(
+                "profile_id",
+                &rating.profile_id.to_string(),
+            ));
+        }
+    }
+
+    if ratings.is_empty() && errors.is_empty() {
+        errors.push(FieldError::required("rows"));
+    }
+    if !errors.is_empty() {
+        return Err(validation_error_response(errors));
+    }
+
+    let imported = store_ratings(&data.db, &ratings)
+        .await
+        .map_err(internal_error)?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "data": { "imported": imported }
+    })))
+}
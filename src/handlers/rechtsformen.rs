@@ -23,6 +23,7 @@ pub async fn get_rechtsformen(
         .fetch_all(&data.db)
         .await
         .map_err(|e| {
+            eprintln!("Error getting rechtsformen: {:?}", e);
             (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 Json(json!({
@@ -58,6 +59,7 @@ pub async fn get_explain_rechtsformen(
     .fetch_all(&data.db)
     .await
     .map_err(|e| {
+        eprintln!("Error getting explain rechtsformen: {:?}", e);
         (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(json!({
@@ -163,7 +165,7 @@ pub async fn update_rechtsform(
     }
     let updated_rechtsform = sqlx::query!(
         r#"
-        UPDATE rechtsformen SET updated_at = NOW(), name = $1
+        UPDATE rechtsformen SET updated_at = NOW(), version = version % 32767 + 1, name = $1
         WHERE name = $2
         RETURNING id, name;
         "#,
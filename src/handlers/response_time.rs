@@ -0,0 +1,120 @@
+use serde_json::json;
+use sqlx::{PgConnection, Pool, Postgres};
+use uuid::Uuid;
+
+const HOUR_SECONDS: f64 = 60.0 * 60.0;
+const HIGH_RESPONSE_RATE: f64 = 0.9;
+
+// Badges for the median first-response time and the response rate from
+// `profile_response_stats`, fastest applicable time badge only
+fn response_badges(
+    median_response_seconds: Option<f64>,
+    response_rate: Option<f64>,
+) -> Vec<serde_json::Value> {
+    let mut badges = Vec::new();
+
+    let time_badge = median_response_seconds.and_then(|seconds| {
+        if seconds <= HOUR_SECONDS {
+            Some(("within_hour", "antwortet meist innerhalb einer Stunde"))
+        } else if seconds <= 24.0 * HOUR_SECONDS {
+            Some(("within_day", "antwortet meist innerhalb eines Tages"))
+        } else if seconds <= 3.0 * 24.0 * HOUR_SECONDS {
+            Some((
+                "within_three_days",
+                "antwortet meist innerhalb von drei Tagen",
+            ))
+        } else {
+            None
+        }
+    });
+    if let Some((key, label)) = time_badge {
+        badges.push(json!({ "key": key, "label": label }));
+    }
+
+    if response_rate.is_some_and(|rate| rate >= HIGH_RESPONSE_RATE) {
+        badges.push(json!({
+            "key": "high_response_rate",
+            "label": "beantwortet fast alle Anfragen"
+        }));
+    }
+
+    badges
+}
+
+pub async fn load_response_stats(
+    db: &Pool<Postgres>,
+    profile_id: Uuid,
+) -> Result<serde_json::Value, sqlx::Error> {
+    let stats = sqlx::query!(
+        r#"
+        SELECT contact_count, response_rate, median_response_seconds
+        FROM profile_response_stats
+        WHERE profile_id = $1
+        "#,
+        profile_id
+    )
+    .fetch_optional(db)
+    .await?;
+
+    let (contact_count, response_rate, median_response_seconds) = match stats {
+        Some(stats) => (
+            stats.contact_count.unwrap_or_default(),
+            stats.response_rate,
+            stats.median_response_seconds,
+        ),
+        None => (0, None, None),
+    };
+
+    Ok(json!({
+        "contact_count": contact_count,
+        "response_rate": response_rate,
+        "median_response_minutes": median_response_seconds.map(|seconds| (seconds / 60.0).round() as i64),
+        "badges": response_badges(median_response_seconds, response_rate)
+    }))
+}
+
+// Starts the response clock for a customer contacting a profile
+pub async fn record_contact(
+    conn: &mut PgConnection,
+    profile_id: Uuid,
+    viewer_id: Uuid,
+    source: &str,
+    source_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO profile_contacts (profile_id, viewer_id, source, source_id)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (source, source_id) DO NOTHING
+        "#,
+        profile_id,
+        viewer_id,
+        source,
+        source_id
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+// Stops the clock on the profile's first reaction; later ones are ignored
+pub async fn record_first_response(
+    conn: &mut PgConnection,
+    source: &str,
+    source_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE profile_contacts
+        SET first_response_at = NOW(), updated_at = NOW(), version = version % 32767 + 1
+        WHERE source = $1 AND source_id = $2 AND first_response_at IS NULL
+        "#,
+        source,
+        source_id
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,461 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde_json::json;
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::{
+    model::{ProfileSnapshot, ProfileStatus},
+    utils::{if_match_versions, precondition_failed, refresh_profile_slug, version_etag},
+    AppState,
+};
+
+use super::{
+    auth::AuthenticatedViewer,
+    moderation::{change_profile_status, notify_status_change},
+    profile::{save_profile_crafts, save_service_areas},
+};
+
+pub async fn load_profile_snapshot(
+    conn: &mut PgConnection,
+    profile_id: Uuid,
+) -> Result<ProfileSnapshot, sqlx::Error> {
+    sqlx::query_as!(
+        ProfileSnapshot,
+        r#"
+        SELECT p.name,
+            r.explain_name AS "rechtsform?",
+            p.email,
+            p.telefon,
+            COALESCE(
+                (SELECT array_agg(c.name ORDER BY pc.is_primary DESC, c.name)
+                 FROM profile_craft pc
+                 JOIN crafts c ON c.id = pc.craft_id
+                 WHERE pc.profile_id = p.id),
+                '{}'
+            ) AS "crafts!",
+            p.experience,
+            p.location,
+            p.lat,
+            p.lng,
+            p.service_radius_km,
+            COALESCE(
+                (SELECT array_agg(a.value ORDER BY a.value)
+                 FROM profile_service_areas a
+                 WHERE a.profile_id = p.id AND a.kind = 'plz'),
+                '{}'
+            ) AS "service_plz",
+            COALESCE(
+                (SELECT array_agg(a.value ORDER BY a.value)
+                 FROM profile_service_areas a
+                 WHERE a.profile_id = p.id AND a.kind = 'district'),
+                '{}'
+            ) AS "service_districts",
+            p.availability_status,
+            p.available_from,
+            COALESCE(
+                (SELECT array_agg(
+                    concat(h.weekday, ' ', to_char(h.opens_at, 'HH24:MI'), '-', to_char(h.closes_at, 'HH24:MI'))
+                    ORDER BY h.weekday, h.opens_at)
+                 FROM profile_opening_hours h
+                 WHERE h.profile_id = p.id),
+                '{}'
+            ) AS "opening_hours!",
+            COALESCE(
+                (SELECT array_agg(
+                    concat_ws(': ', concat(c.starts_on, ' - ', c.ends_on), c.note)
+                    ORDER BY c.starts_on, c.ends_on)
+                 FROM profile_closures c
+                 WHERE c.profile_id = p.id),
+                '{}'
+            ) AS "closures!",
+            COALESCE(
+                (SELECT array_agg(
+                    rtrim(concat(ps.name, ' (', s.name, '): ', ps.price_model, ' ',
+                        concat_ws('-', ps.price_min_cents, ps.price_max_cents)))
+                    ORDER BY ps.position, ps.created_at)
+                 FROM profile_services ps
+                 JOIN skills s ON s.id = ps.skill_id
+                 WHERE ps.profile_id = p.id),
+                '{}'
+            ) AS "services!",
+            p.website,
+            p.instagram,
+            p.bio,
+            p.handwerks_karten_nummer,
+            COALESCE(
+                (SELECT array_agg(s.name ORDER BY s.name)
+                 FROM profile_skill ps
+                 JOIN skills s ON s.id = ps.skill_id
+                 WHERE ps.profile_id = p.id),
+                '{}'
+            ) AS "skills!",
+            COALESCE(
+                (SELECT array_agg(ph.id ORDER BY ph.position, ph.created_at, ph.id)
+                 FROM photos ph
+                 WHERE ph.profile_id = p.id),
+                '{}'
+            ) AS "photos!",
+            p.cover_photo_id AS cover_photo,
+            COALESCE(
+                (SELECT array_agg(concat_ws(' / ', ph.caption, ph.alt_text) ORDER BY ph.position, ph.created_at, ph.id)
+                 FROM photos ph
+                 WHERE ph.profile_id = p.id
+                    AND (ph.caption IS NOT NULL OR ph.alt_text IS NOT NULL)),
+                '{}'
+            ) AS "photo_texts!",
+            COALESCE(
+                (SELECT array_agg(concat_ws(': ', pp.title, pp.description) ORDER BY pp.created_at, pp.id)
+                 FROM photo_projects pp
+                 WHERE pp.profile_id = p.id),
+                '{}'
+            ) AS "projects!"
+        FROM profiles p
+        LEFT JOIN rechtsformen r ON p.rechtsform_id = r.id
+        WHERE p.id = $1
+        "#,
+        profile_id
+    )
+    .fetch_one(conn)
+    .await
+}
+
+// Field-by-field `{ field: { old, new } }` between two snapshots. `before` is
+// `None` for a newly created profile.
+pub fn diff_snapshots(
+    before: Option<&ProfileSnapshot>,
+    after: &ProfileSnapshot,
+) -> serde_json::Map<String, serde_json::Value> {
+    let before = before
+        .map(|snapshot| json!(snapshot))
+        .unwrap_or_else(|| json!({}));
+    let after = json!(after);
+
+    let mut changes = serde_json::Map::new();
+    if let serde_json::Value::Object(after) = after {
+        for (field, new) in after {
+            let old = before
+                .get(&field)
+                .cloned()
+                .unwrap_or(serde_json::Value::Null);
+            if old != new {
+                changes.insert(field, json!({ "old": old, "new": new }));
+            }
+        }
+    }
+    changes
+}
+
+// Stores a revision if anything changed. When a non-admin edits an accepted
+// profile and touches one of the review fields, the profile goes back to
+// pending review. Returns whether that happened, so that the caller can notify
+// the owner once its transaction is committed.
+pub async fn record_revision(
+    conn: &mut PgConnection,
+    data: &AppState,
+    profile_id: Uuid,
+    viewer_id: Uuid,
+    is_admin: bool,
+    before: Option<&ProfileSnapshot>,
+    rollback_of: Option<Uuid>,
+) -> Result<bool, sqlx::Error> {
+    let after = load_profile_snapshot(&mut *conn, profile_id).await?;
+    let changes = diff_snapshots(before, &after);
+    if changes.is_empty() {
+        return Ok(false);
+    }
+
+    sqlx::query!(
+        r#"
+        INSERT INTO profile_revisions (profile_id, viewer_id, changes, snapshot, rollback_of)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        profile_id,
+        viewer_id,
+        serde_json::Value::Object(changes.clone()),
+        json!(after),
+        rollback_of
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    if is_admin || before.is_none() {
+        return Ok(false);
+    }
+
+    if !changes
+        .keys()
+        .any(|field| data.review_fields.contains(field))
+    {
+        return Ok(false);
+    }
+
+    let changed = change_profile_status(
+        &mut *conn,
+        profile_id,
+        Some(viewer_id),
+        &[ProfileStatus::Approved],
+        ProfileStatus::Pending,
+        None,
+    )
+    .await?;
+
+    // Drafts and rejected profiles stay where they are until the owner
+    // submits them
+    Ok(changed.is_some())
+}
+
+pub async fn get_profile_revisions(
+    State(data): State<Arc<AppState>>,
+    AuthenticatedViewer { is_admin, .. }: AuthenticatedViewer,
+    Path(profile_id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    if !is_admin {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            Json(json!({
+                "status": "fail",
+                "message": "Only admins can view profile revisions."
+            })),
+        ));
+    }
+
+    let rows = sqlx::query!(
+        r#"
+        SELECT pr.id, pr.viewer_id, v.email AS "editor_email?", pr.changes, pr.rollback_of, pr.created_at
+        FROM profile_revisions pr
+        LEFT JOIN viewers v ON pr.viewer_id = v.id
+        WHERE pr.profile_id = $1
+        ORDER BY pr.created_at DESC
+        "#,
+        profile_id
+    )
+    .fetch_all(&data.db)
+    .await
+    .map_err(|e| {
+        eprintln!("get_profile_revisions error: {:?}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "status": "fail",
+                "message": "Internal Server Error"
+            })),
+        )
+    })?;
+
+    let response_data: Vec<serde_json::Value> = rows
+        .into_iter()
+        .map(|row| {
+            json!({
+                "id": row.id,
+                "viewer_id": row.viewer_id,
+                "editor_email": row.editor_email,
+                "changes": row.changes,
+                "rollback_of": row.rollback_of,
+                "createdAt": row.created_at,
+                "_links": {
+                    "rollback": format!(
+                        "{}/api/profile/{}/revisions/{}/rollback",
+                        data.url, profile_id, row.id
+                    )
+                }
+            })
+        })
+        .collect();
+
+    Ok(Json(json!({
+        "status": "success",
+        "data": response_data
+    })))
+}
+
+// Restores the editable fields, service areas and skills stored in a
+// revision. Availability, the service catalogue, photos, the cover photo,
+// photo texts and projects are listed in revisions for review only; deleted
+// photos cannot be restored.
+pub async fn rollback_profile_revision(
+    State(data): State<Arc<AppState>>,
+    viewer: AuthenticatedViewer,
+    Path((profile_id, revision_id)): Path<(Uuid, Uuid)>,
+    request_headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    if !viewer.is_admin {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            Json(json!({
+                "status": "fail",
+                "message": "Only admins can roll back profiles."
+            })),
+        ));
+    }
+
+    let internal_error = |e: sqlx::Error| {
+        eprintln!("rollback_profile_revision error: {:?}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "status": "fail", "message": "Internal Server Error" })),
+        )
+    };
+
+    let mut tx = data.db.begin().await.map_err(internal_error)?;
+
+    let revision = sqlx::query!(
+        "SELECT snapshot FROM profile_revisions WHERE id = $1 AND profile_id = $2",
+        revision_id,
+        profile_id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(internal_error)?
+    .ok_or((
+        StatusCode::NOT_FOUND,
+        Json(json!({ "status": "fail", "message": "Revision not found" })),
+    ))?;
+
+    let snapshot: ProfileSnapshot = serde_json::from_value(revision.snapshot).map_err(|e| {
+        eprintln!("rollback_profile_revision: invalid snapshot: {:?}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "status": "fail", "message": "Internal Server Error" })),
+        )
+    })?;
+
+    let before = load_profile_snapshot(&mut tx, profile_id)
+        .await
+        .map_err(internal_error)?;
+
+    let version = sqlx::query!(
+        r#"
+        UPDATE profiles SET
+            updated_at = NOW(),
+            version = version % 32767 + 1,
+            name = $2,
+            rechtsform_id = COALESCE((SELECT id FROM rechtsformen WHERE explain_name = $3), rechtsform_id),
+            email = $4,
+            telefon = $5,
+            experience = $6,
+            location = $7,
+            lat = $8,
+            lng = $9,
+            website = $10,
+            instagram = $11,
+            bio = $12,
+            handwerks_karten_nummer = $13
+        WHERE id = $1 AND ($14::smallint[] IS NULL OR version = ANY($14))
+        RETURNING version
+        "#,
+        profile_id,
+        snapshot.name,
+        snapshot.rechtsform,
+        snapshot.email,
+        snapshot.telefon,
+        snapshot.experience,
+        snapshot.location,
+        snapshot.lat,
+        snapshot.lng,
+        snapshot.website,
+        snapshot.instagram,
+        snapshot.bio,
+        snapshot.handwerks_karten_nummer,
+        if_match_versions(&request_headers) as Option<Vec<i16>>
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(internal_error)?
+    .ok_or_else(precondition_failed)?
+    .version;
+
+    refresh_profile_slug(&mut tx, profile_id)
+        .await
+        .map_err(internal_error)?;
+
+    // Older revisions do not know the service area and keep the current one
+    if let (Some(plz), Some(districts)) = (&snapshot.service_plz, &snapshot.service_districts) {
+        sqlx::query!(
+            "UPDATE profiles SET service_radius_km = $2 WHERE id = $1",
+            profile_id,
+            snapshot.service_radius_km
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(internal_error)?;
+
+        save_service_areas(&mut tx, profile_id, Some(plz), Some(districts))
+            .await
+            .map_err(internal_error)?;
+    }
+
+    // Crafts that were deleted since the revision are skipped
+    let craft_ids: Vec<Uuid> = sqlx::query_scalar!(
+        r#"
+        SELECT c.id
+        FROM UNNEST($1::text[]) WITH ORDINALITY AS t(name, ord)
+        JOIN crafts c ON c.name = t.name
+        ORDER BY t.ord
+        "#,
+        &snapshot.crafts
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+
+    save_profile_crafts(&mut tx, profile_id, &craft_ids, true)
+        .await
+        .map_err(internal_error)?;
+
+    sqlx::query!(
+        "DELETE FROM profile_skill WHERE profile_id = $1",
+        profile_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO profile_skill (profile_id, skill_id)
+        SELECT $1, id FROM skills WHERE name = ANY($2)
+        "#,
+        profile_id,
+        &snapshot.skills
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+
+    let review_required = record_revision(
+        &mut tx,
+        &data,
+        profile_id,
+        viewer.viewer_id,
+        viewer.is_admin,
+        Some(&before),
+        Some(revision_id),
+    )
+    .await
+    .map_err(internal_error)?;
+
+    tx.commit().await.map_err(internal_error)?;
+
+    if review_required {
+        notify_status_change(&data, profile_id, ProfileStatus::Pending, None).await;
+    }
+
+    let mut headers = HeaderMap::new();
+    headers.insert(header::ETAG, version_etag(version));
+
+    Ok((
+        StatusCode::OK,
+        headers,
+        Json(json!({
+            "status": "success",
+            "message": "Profile rolled back."
+        })),
+    ))
+}
@@ -0,0 +1,295 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    validation::{is_valid_instagram_handle, is_valid_url},
+    AppState,
+};
+
+// Limit of URLs per sitemap file set by the sitemap protocol
+const SITEMAP_MAX_URLS: i64 = 50_000;
+
+#[derive(Deserialize, Debug, Default)]
+pub struct SitemapQuery {
+    pub page: Option<i64>,
+}
+
+fn internal_error(e: sqlx::Error) -> (StatusCode, Json<serde_json::Value>) {
+    eprintln!("seo error: {:?}", e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(json!({ "status": "fail", "message": "Internal Server Error" })),
+    )
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+// Profile link for the `instagram` field, which holds either a URL or a handle
+fn instagram_url(instagram: &str) -> Option<String> {
+    if is_valid_url(instagram) {
+        Some(instagram.to_string())
+    } else if is_valid_instagram_handle(instagram) {
+        Some(format!(
+            "https://www.instagram.com/{}",
+            instagram.trim_start_matches('@')
+        ))
+    } else {
+        None
+    }
+}
+
+fn lastmod(updated_at: DateTime<Utc>) -> String {
+    updated_at.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn xml_response(body: String) -> impl IntoResponse {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/xml; charset=utf-8"),
+    );
+    headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static("public, max-age=3600"),
+    );
+    (headers, body)
+}
+
+// Approved profiles not hidden by reports as a single urlset, or as a sitemap
+// index pointing to `?page=N` urlsets once there are more than 50k of them
+pub async fn get_sitemap(
+    State(data): State<Arc<AppState>>,
+    Query(query): Query<SitemapQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let pages = sqlx::query!(
+        r#"
+        SELECT numbered.page AS "page!", MAX(numbered.updated_at) AS "lastmod!", COUNT(*) AS "count!"
+        FROM (
+            SELECT (ROW_NUMBER() OVER (ORDER BY created_at, id) - 1) / $1 AS page, updated_at
+            FROM profiles
+            WHERE status = 'approved' AND reports_hidden_at IS NULL
+        ) numbered
+        GROUP BY numbered.page
+        ORDER BY numbered.page
+        "#,
+        SITEMAP_MAX_URLS
+    )
+    .fetch_all(&data.db)
+    .await
+    .map_err(internal_error)?;
+
+    let page = match query.page {
+        Some(page) => page,
+        None if pages.len() <= 1 => 0,
+        None => {
+            let mut body = String::from(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<sitemapindex xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+            );
+            for page in &pages {
+                body.push_str(&format!(
+                    "  <sitemap>\n    <loc>{}</loc>\n    <lastmod>{}</lastmod>\n  </sitemap>\n",
+                    escape_xml(&format!("{}/sitemap.xml?page={}", data.url, page.page)),
+                    lastmod(page.lastmod)
+                ));
+            }
+            body.push_str("</sitemapindex>\n");
+            return Ok(xml_response(body));
+        }
+    };
+
+    if page < 0 || (page > 0 && page as usize >= pages.len()) {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({ "status": "fail", "message": "Sitemap page not found" })),
+        ));
+    }
+
+    let rows = sqlx::query!(
+        r#"
+        SELECT slug, updated_at
+        FROM profiles
+        WHERE status = 'approved' AND reports_hidden_at IS NULL
+        ORDER BY created_at, id
+        OFFSET $1
+        LIMIT $2
+        "#,
+        page * SITEMAP_MAX_URLS,
+        SITEMAP_MAX_URLS
+    )
+    .fetch_all(&data.db)
+    .await
+    .map_err(internal_error)?;
+
+    let mut body = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+    );
+    for row in rows {
+        body.push_str(&format!(
+            "  <url>\n    <loc>{}</loc>\n    <lastmod>{}</lastmod>\n  </url>\n",
+            escape_xml(&format!("{}/profile/{}", data.url, row.slug)),
+            lastmod(row.updated_at)
+        ));
+    }
+    body.push_str("</urlset>\n");
+
+    Ok(xml_response(body))
+}
+
+// schema.org description of an approved, not hidden profile for embedding as
+// JSON-LD
+pub async fn get_profile_json_ld(
+    State(data): State<Arc<AppState>>,
+    Path(profile_id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let profile = sqlx::query!(
+        r#"
+        SELECT p.name, p.slug, p.location, p.lat, p.lng, p.website, p.instagram, p.bio,
+            COALESCE(
+                (SELECT array_agg(c.name ORDER BY pc.is_primary DESC, c.name)
+                 FROM profile_craft pc
+                 JOIN crafts c ON c.id = pc.craft_id
+                 WHERE pc.profile_id = p.id),
+                '{}'
+            ) AS "crafts!",
+            COALESCE(
+                (SELECT array_agg(s.name ORDER BY s.name)
+                 FROM profile_skill ps
+                 JOIN skills s ON s.id = ps.skill_id
+                 WHERE ps.profile_id = p.id),
+                '{}'
+            ) AS "skills!",
+            COALESCE(
+                (SELECT array_agg(ph.id ORDER BY ph.id = p.cover_photo_id DESC, ph.position, ph.id)
+                 FROM photos ph
+                 WHERE ph.profile_id = p.id),
+                '{}'
+            ) AS "photos!",
+            r.rating AS "rating?",
+            r.review_count AS "review_count?"
+        FROM profiles p
+        LEFT JOIN ratings r ON r.profile_id = p.id AND r.source = 'reviews'
+        WHERE p.id = $1 AND p.status = 'approved' AND p.reports_hidden_at IS NULL
+        "#,
+        profile_id
+    )
+    .fetch_optional(&data.db)
+    .await
+    .map_err(internal_error)?
+    .ok_or((
+        StatusCode::NOT_FOUND,
+        Json(json!({ "status": "fail", "message": "Profile not found" })),
+    ))?;
+
+    let profile_url = format!("{}/profile/{}", data.url, profile.slug);
+
+    let mut same_as = Vec::new();
+    if let Some(website) = profile.website.filter(|w| !w.is_empty()) {
+        same_as.push(website);
+    }
+    if let Some(instagram) = profile.instagram.as_deref().and_then(instagram_url) {
+        same_as.push(instagram);
+    }
+
+    let knows_about: Vec<&String> = profile.crafts.iter().chain(&profile.skills).collect();
+
+    let mut json_ld = json!({
+        "@context": "https://schema.org",
+        "@type": ["LocalBusiness", "HomeAndConstructionBusiness"],
+        "@id": profile_url,
+        "url": profile_url,
+        "name": profile.name,
+        "address": {
+            "@type": "PostalAddress",
+            "addressLocality": profile.location,
+            "addressCountry": "DE"
+        },
+        "geo": {
+            "@type": "GeoCoordinates",
+            "latitude": profile.lat,
+            "longitude": profile.lng
+        },
+        "knowsAbout": knows_about,
+        "image": profile
+            .photos
+            .iter()
+            .map(|id| format!("{}/api/photos/{}", data.url, id))
+            .collect::<Vec<_>>()
+    });
+
+    let fields = json_ld.as_object_mut().unwrap();
+    if let Some(bio) = profile.bio.filter(|b| !b.is_empty()) {
+        fields.insert("description".to_string(), json!(bio));
+    }
+    if !same_as.is_empty() {
+        fields.insert("sameAs".to_string(), json!(same_as));
+    }
+    if let (Some(rating), Some(review_count)) = (profile.rating, profile.review_count) {
+        if review_count > 0 {
+            fields.insert(
+                "aggregateRating".to_string(),
+                json!({
+                    "@type": "AggregateRating",
+                    "ratingValue": rating,
+                    "reviewCount": review_count,
+                    "bestRating": 5,
+                    "worstRating": 1
+                }),
+            );
+        }
+    }
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/ld+json"),
+    );
+    headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static("public, max-age=3600"),
+    );
+
+    Ok((headers, Json(json_ld)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn instagram_url_prefixes_handles_only() {
+        assert_eq!(
+            instagram_url("@tischlerei.mueller").as_deref(),
+            Some("https://www.instagram.com/tischlerei.mueller")
+        );
+        assert_eq!(
+            instagram_url("tischlerei_mueller").as_deref(),
+            Some("https://www.instagram.com/tischlerei_mueller")
+        );
+        assert_eq!(
+            instagram_url("https://www.instagram.com/tischlerei.mueller/").as_deref(),
+            Some("https://www.instagram.com/tischlerei.mueller/")
+        );
+        assert_eq!(
+            instagram_url("http://instagr.am/mueller").as_deref(),
+            Some("http://instagr.am/mueller")
+        );
+        assert_eq!(instagram_url(""), None);
+        assert_eq!(instagram_url("not a handle"), None);
+    }
+}
@@ -0,0 +1,250 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde_json::json;
+use sqlx::{Pool, Postgres};
+use uuid::Uuid;
+
+use crate::{
+    model::ProfileStatus,
+    schema::ServiceCatalogueSchema,
+    validation::{validation_error_response, FieldError},
+    AppState,
+};
+
+use super::{
+    auth::AuthenticatedViewer,
+    moderation::notify_status_change,
+    profile::{ensure_can_edit_profile, lock_profile_snapshot},
+    revision::record_revision,
+};
+
+// Median hourly rate in cents below which a profile is € and €€
+const PRICE_LEVEL_CHEAP_BELOW_CENTS: i32 = 5_000;
+const PRICE_LEVEL_MODERATE_BELOW_CENTS: i32 = 8_000;
+
+fn internal_error(e: sqlx::Error) -> (StatusCode, Json<serde_json::Value>) {
+    eprintln!("services error: {:?}", e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(json!({ "status": "fail", "message": "Internal Server Error" })),
+    )
+}
+
+// "€" to "€€€" for a price level of 1 to 3
+pub fn price_indicator(price_level: Option<i16>) -> Option<String> {
+    price_level.map(|level| "€".repeat(level.clamp(1, 3) as usize))
+}
+
+pub async fn load_services(
+    db: &Pool<Postgres>,
+    profile_id: Uuid,
+) -> Result<Vec<serde_json::Value>, sqlx::Error> {
+    let services = sqlx::query!(
+        r#"
+        SELECT ps.id, ps.name, s.name AS skill, ps.price_model, ps.price_min_cents, ps.price_max_cents
+        FROM profile_services ps
+        JOIN skills s ON s.id = ps.skill_id
+        WHERE ps.profile_id = $1
+        ORDER BY ps.position, ps.created_at
+        "#,
+        profile_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(services
+        .into_iter()
+        .map(|service| {
+            json!({
+                "id": service.id,
+                "name": service.name,
+                "skill": service.skill,
+                "price_model": service.price_model,
+                "price_min_cents": service.price_min_cents,
+                "price_max_cents": service.price_max_cents
+            })
+        })
+        .collect())
+}
+
+async fn catalogue_response(
+    db: &Pool<Postgres>,
+    profile_id: Uuid,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let profile = sqlx::query!("SELECT price_level FROM profiles WHERE id = $1", profile_id)
+        .fetch_optional(db)
+        .await
+        .map_err(internal_error)?
+        .ok_or((
+            StatusCode::NOT_FOUND,
+            Json(json!({ "status": "fail", "message": "Profile not found" })),
+        ))?;
+
+    let services = load_services(db, profile_id)
+        .await
+        .map_err(internal_error)?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "data": {
+            "services": services,
+            "price_level": profile.price_level,
+            "price_indicator": price_indicator(profile.price_level)
+        }
+    })))
+}
+
+pub async fn get_services(
+    State(data): State<Arc<AppState>>,
+    Path(profile_id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    catalogue_response(&data.db, profile_id).await
+}
+
+// Owner replaces the catalogue; the price level is derived from the median
+// hourly rate, taking the middle of a price range
+pub async fn update_services(
+    State(data): State<Arc<AppState>>,
+    Path(profile_id): Path<Uuid>,
+    AuthenticatedViewer {
+        viewer_id,
+        is_admin,
+    }: AuthenticatedViewer,
+    Json(body): Json<ServiceCatalogueSchema>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    ensure_can_edit_profile(&data.db, profile_id, viewer_id, is_admin).await?;
+
+    let mut errors = body.validate();
+
+    let skill_names: Vec<String> = body
+        .services
+        .iter()
+        .map(|service| service.skill.trim().to_string())
+        .collect();
+    let skills = sqlx::query!(
+        "SELECT id, name FROM skills WHERE name = ANY($1)",
+        &skill_names
+    )
+    .fetch_all(&data.db)
+    .await
+    .map_err(internal_error)?;
+
+    let mut skill_ids = Vec::with_capacity(skill_names.len());
+    for (i, name) in skill_names.iter().enumerate() {
+        match skills.iter().find(|skill| &skill.name == name) {
+            Some(skill) => skill_ids.push(skill.id),
+            None if !name.is_empty() => {
+                errors.push(FieldError::unknown(&format!("services[{}].skill", i), name))
+            }
+            None => {}
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(validation_error_response(errors));
+    }
+
+    let names: Vec<Option<String>> = body
+        .services
+        .iter()
+        .map(|service| service.name.as_deref().map(|name| name.trim().to_string()))
+        .collect();
+    let price_models: Vec<String> = body
+        .services
+        .iter()
+        .map(|service| service.price_model.clone())
+        .collect();
+    let price_mins: Vec<Option<i32>> = body.services.iter().map(|s| s.price_min_cents).collect();
+    let price_maxs: Vec<Option<i32>> = body.services.iter().map(|s| s.price_max_cents).collect();
+
+    let mut tx = data.db.begin().await.map_err(internal_error)?;
+
+    let before = lock_profile_snapshot(&mut tx, profile_id).await?;
+
+    sqlx::query!(
+        "DELETE FROM profile_services WHERE profile_id = $1",
+        profile_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO profile_services (profile_id, skill_id, name, price_model, price_min_cents, price_max_cents, position)
+        SELECT $1, t.skill_id, COALESCE(t.name, s.name), t.price_model, t.price_min, t.price_max, t.ord
+        FROM UNNEST($2::uuid[], $3::varchar[], $4::varchar[], $5::int[], $6::int[])
+            WITH ORDINALITY AS t(skill_id, name, price_model, price_min, price_max, ord)
+        JOIN skills s ON s.id = t.skill_id
+        "#,
+        profile_id,
+        &skill_ids,
+        &names as &[Option<String>],
+        &price_models,
+        &price_mins as &[Option<i32>],
+        &price_maxs as &[Option<i32>]
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+
+    sqlx::query!(
+        r#"
+        UPDATE profiles
+        SET price_level = (
+                SELECT CASE
+                    WHEN median IS NULL THEN NULL
+                    WHEN median < $2 THEN 1
+                    WHEN median < $3 THEN 2
+                    ELSE 3
+                END
+                FROM (
+                    SELECT percentile_cont(0.5) WITHIN GROUP (
+                        ORDER BY COALESCE(
+                            (price_min_cents + price_max_cents) / 2.0,
+                            price_min_cents,
+                            price_max_cents
+                        )
+                    ) AS median
+                    FROM profile_services
+                    WHERE profile_id = $1 AND price_model = 'hourly'
+                ) hourly
+            ),
+            updated_at = NOW(),
+            version = version % 32767 + 1
+        WHERE id = $1
+        "#,
+        profile_id,
+        PRICE_LEVEL_CHEAP_BELOW_CENTS as f64,
+        PRICE_LEVEL_MODERATE_BELOW_CENTS as f64
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+
+    let review_required = record_revision(
+        &mut tx,
+        &data,
+        profile_id,
+        viewer_id,
+        is_admin,
+        Some(&before),
+        None,
+    )
+    .await
+    .map_err(internal_error)?;
+
+    tx.commit().await.map_err(internal_error)?;
+
+    if review_required {
+        notify_status_change(&data, profile_id, ProfileStatus::Pending, None).await;
+    }
+
+    catalogue_response(&data.db, profile_id).await
+}
@@ -132,7 +132,7 @@ pub async fn update_skill(
     }
     let updated_skill = sqlx::query!(
         r#"
-        UPDATE skills SET updated_at = NOW(), name = $1
+        UPDATE skills SET updated_at = NOW(), version = version % 32767 + 1, name = $1
         WHERE name = $2
         RETURNING id, name;
         "#,
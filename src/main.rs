@@ -1,13 +1,19 @@
+mod blob_store;
 mod email;
 mod handlers;
+mod media;
 mod model;
+mod rating_provider;
 mod route;
 mod schema;
 mod utils;
+mod validation;
 
 use axum::extract::DefaultBodyLimit;
+use blob_store::BlobStore;
 use dotenv::dotenv;
 use email::EmailManager;
+use media::{ImagePolicy, ImageWorkers, UploadLimits};
 use sqlx::{postgres::PgPoolOptions, Pool, Postgres};
 use std::{env, process::exit, sync::Arc, time::Duration};
 
@@ -16,6 +22,13 @@ pub struct AppState {
     email_manager: Arc<EmailManager>,
     url: String,
     domain: String,
+    review_fields: Vec<String>,
+    review_blocked_words: Vec<String>,
+    profile_report_threshold: i64,
+    blob_store: Arc<dyn BlobStore>,
+    image_policy: ImagePolicy,
+    image_workers: ImageWorkers,
+    upload_limits: UploadLimits,
 }
 
 #[tokio::main]
@@ -25,6 +38,31 @@ async fn main() {
     let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set!");
     let url = env::var("URL").expect("URL must be set!");
     let domain = env::var("DOMAIN").expect("DOMAIN must be set!");
+    // Profile fields whose change by the owner sends the profile back to review
+    let review_fields = env::var("PROFILE_REVIEW_FIELDS")
+        .unwrap_or_else(|_| {
+            "name,crafts,handwerks_karten_nummer,website,instagram,bio,photos,cover_photo,photo_texts,projects,services"
+                .to_string()
+        })
+        .split(',')
+        .map(|field| field.trim().to_string())
+        .filter(|field| !field.is_empty())
+        .collect();
+    // Reviews containing one of these words are held for moderation
+    let review_blocked_words = env::var("REVIEW_BLOCKED_WORDS")
+        .unwrap_or_else(|_| "arschloch,betrüger,idiot,scheiße,wichser".to_string())
+        .split(',')
+        .map(|word| word.trim().to_lowercase())
+        .filter(|word| !word.is_empty())
+        .collect();
+
+    // Open reports from different viewers after which a profile is hidden
+    // from listings and search until an admin has looked at it
+    let profile_report_threshold = env::var("PROFILE_REPORT_THRESHOLD")
+        .ok()
+        .and_then(|threshold| threshold.parse::<i64>().ok())
+        .unwrap_or(3)
+        .max(1);
 
     let smtp_email = env::var("SMTP_EMAIL").expect("SMTP_EMAIL must be set");
     let smtp_password = env::var("SMTP_PASSWORD").expect("SMTP_PASSWORD must be set");
@@ -54,13 +92,65 @@ async fn main() {
         }
     };
 
-    let app = route::create_router(Arc::new(AppState {
+    let blob_store = match blob_store::blob_store_from_env() {
+        Ok(store) => store,
+        Err(e) => {
+            eprintln!("Failed to create BlobStore: {}", e);
+            exit(1);
+        }
+    };
+
+    // `mano migrate-photo-blobs` moves photos stored in the database into
+    // the blob store and exits
+    if env::args().nth(1).as_deref() == Some("migrate-photo-blobs") {
+        match blob_store::migrate_photo_blobs(&pool, blob_store.as_ref(), 100).await {
+            Ok((migrated, 0)) => {
+                println!("Moved {} photos into the blob store", migrated);
+                exit(0);
+            }
+            Ok((migrated, failed)) => {
+                eprintln!("Moved {} photos, {} failed", migrated, failed);
+                exit(1);
+            }
+            Err(e) => {
+                eprintln!("migrate-photo-blobs failed: {:?}", e);
+                exit(1);
+            }
+        }
+    }
+
+    let app_state = Arc::new(AppState {
         db: pool.clone(),
         email_manager: email_manager.clone(),
         url,
         domain,
-    }))
-    .layer(DefaultBodyLimit::max(40 * 1024 * 1024));
+        review_fields,
+        review_blocked_words,
+        profile_report_threshold,
+        blob_store,
+        image_policy: ImagePolicy::from_env(),
+        image_workers: ImageWorkers::from_env(),
+        upload_limits: UploadLimits::from_env(),
+    });
+
+    tokio::spawn(handlers::messages::run_message_digests(app_state.clone()));
+    tokio::spawn(blob_store::run_photo_blob_cleanup(app_state.clone()));
+
+    let rating_providers = rating_provider::providers_from_env();
+    if !rating_providers.is_empty() {
+        let refresh_hours = env::var("RATING_REFRESH_HOURS")
+            .ok()
+            .and_then(|hours| hours.parse::<u64>().ok())
+            .unwrap_or(24)
+            .max(1);
+        tokio::spawn(rating_provider::run_rating_refresh(
+            app_state.clone(),
+            rating_providers,
+            Duration::from_secs(refresh_hours * 60 * 60),
+        ));
+    }
+
+    let app = route::create_router(app_state).layer(DefaultBodyLimit::max(40 * 1024 * 1024));
     let listener = tokio::net::TcpListener::bind("0.0.0.0:8000").await.unwrap();
     axum::serve(listener, app).await.unwrap();
 }
@@ -0,0 +1,799 @@
+use std::{env, io::Cursor, sync::Arc};
+
+use axum::{
+    body::Bytes,
+    extract::multipart::{Field, MultipartError},
+    http::StatusCode,
+    Json,
+};
+use image::{
+    codecs::jpeg::JpegEncoder,
+    error::{EncodingError, ImageFormatHint},
+    imageops::FilterType,
+    metadata::Orientation,
+    DynamicImage, GenericImageView, ImageDecoder, ImageError, ImageFormat, ImageReader,
+};
+use serde_json::json;
+use thiserror::Error;
+use tokio::sync::Semaphore;
+
+#[derive(Error, Debug)]
+pub enum MediaError {
+    #[error("Invalid image data: {0}")]
+    Decode(ImageError),
+    #[error("Failed to encode image: {0}")]
+    Encode(ImageError),
+    #[error("Image worker failed: {0}")]
+    Worker(#[from] tokio::task::JoinError),
+    #[error("Failed to read upload: {0}")]
+    Multipart(#[from] MultipartError),
+    #[error("Upload is larger than {max_bytes} bytes")]
+    TooLarge { max_bytes: usize },
+    #[error("Image of {width}x{height} exceeds {max_pixels} pixels")]
+    TooManyPixels {
+        width: u32,
+        height: u32,
+        max_pixels: u64,
+    },
+    #[error("Unsupported image format")]
+    UnsupportedFormat,
+    // Only without the `heic` feature
+    #[error("HEIC images can not be processed")]
+    Heic,
+    #[error("Profile already has {max_photos} photos")]
+    TooManyPhotos { max_photos: i64 },
+    #[error("Profile photos exceed {max_bytes} bytes")]
+    ProfileTooLarge { max_bytes: i64 },
+}
+
+fn megabytes(bytes: u64) -> u64 {
+    bytes.div_ceil(1024 * 1024)
+}
+
+pub fn media_error_response(e: MediaError) -> (StatusCode, Json<serde_json::Value>) {
+    let fail = |status: StatusCode, message: String| {
+        (
+            status,
+            Json(json!({ "status": "fail", "message": message })),
+        )
+    };
+    match e {
+        MediaError::Multipart(e) => {
+            eprintln!("Failed to read multipart upload: {:?}", e);
+            let status = e.status();
+            if status == StatusCode::PAYLOAD_TOO_LARGE {
+                fail(status, "Request body is too large".to_string())
+            } else {
+                fail(status, "Invalid multipart body".to_string())
+            }
+        }
+        MediaError::TooLarge { max_bytes } => fail(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!(
+                "Photo is too large, at most {} MB are allowed",
+                megabytes(max_bytes as u64)
+            ),
+        ),
+        MediaError::TooManyPixels {
+            width,
+            height,
+            max_pixels,
+        } => fail(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!(
+                "Photo has {}x{} pixels, at most {} megapixels are allowed",
+                width,
+                height,
+                max_pixels / 1_000_000
+            ),
+        ),
+        MediaError::UnsupportedFormat => fail(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            if cfg!(feature = "heic") {
+                "Unsupported image format, allowed are JPEG, PNG, WebP and HEIC".to_string()
+            } else {
+                "Unsupported image format, allowed are JPEG, PNG and WebP".to_string()
+            },
+        ),
+        MediaError::Heic => fail(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "HEIC photos are not supported yet, please upload JPEG, PNG or WebP".to_string(),
+        ),
+        MediaError::TooManyPhotos { max_photos } => fail(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("A profile can have at most {} photos", max_photos),
+        ),
+        MediaError::ProfileTooLarge { max_bytes } => fail(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!(
+                "Photos of a profile can take at most {} MB",
+                megabytes(max_bytes as u64)
+            ),
+        ),
+        MediaError::Decode(e) => {
+            eprintln!("Failed to decode image: {:?}", e);
+            (
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "status": "fail", "message": "Invalid image data" })),
+            )
+        }
+        e => {
+            eprintln!("Image processing error: {:?}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "status": "fail", "message": "Failed to compress image" })),
+            )
+        }
+    }
+}
+
+// How an image is shrunk: scaled to fit `max_dim`, then encoded as JPEG
+// starting at `start_quality` and lowered in steps of 5 until the result fits
+// into `max_bytes`. At `min_quality` the result is kept even if it is larger.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImagePolicy {
+    pub max_dim: u32,
+    pub max_bytes: usize,
+    pub start_quality: u8,
+    pub min_quality: u8,
+}
+
+impl Default for ImagePolicy {
+    fn default() -> Self {
+        ImagePolicy {
+            max_dim: 800,
+            max_bytes: 400_000,
+            start_quality: 90,
+            min_quality: 10,
+        }
+    }
+}
+
+impl ImagePolicy {
+    // IMAGE_MAX_DIM, IMAGE_MAX_BYTES and IMAGE_MIN_QUALITY override the defaults
+    pub fn from_env() -> Self {
+        fn var<T: std::str::FromStr>(name: &str) -> Option<T> {
+            env::var(name).ok().and_then(|value| value.parse().ok())
+        }
+
+        let default = ImagePolicy::default();
+        let min_quality = var("IMAGE_MIN_QUALITY")
+            .unwrap_or(default.min_quality)
+            .clamp(1, default.start_quality);
+        ImagePolicy {
+            max_dim: var("IMAGE_MAX_DIM").unwrap_or(default.max_dim).max(1),
+            max_bytes: var("IMAGE_MAX_BYTES").unwrap_or(default.max_bytes),
+            start_quality: default.start_quality,
+            min_quality,
+        }
+    }
+
+    pub fn with_max_dim(self, max_dim: u32) -> Self {
+        ImagePolicy { max_dim, ..self }
+    }
+}
+
+// What an upload may be, checked while it is read and before anything is
+// decoded. `max_pixels` guards against decompression bombs: small files that
+// claim huge dimensions in their header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UploadLimits {
+    pub max_file_bytes: usize,
+    pub max_pixels: u64,
+    pub max_profile_photos: i64,
+    pub max_profile_bytes: i64,
+}
+
+impl Default for UploadLimits {
+    fn default() -> Self {
+        UploadLimits {
+            max_file_bytes: 10 * 1024 * 1024,
+            max_pixels: 40_000_000,
+            max_profile_photos: 20,
+            max_profile_bytes: 100 * 1024 * 1024,
+        }
+    }
+}
+
+impl UploadLimits {
+    // UPLOAD_MAX_FILE_BYTES, UPLOAD_MAX_PIXELS, PROFILE_PHOTOS_MAX and
+    // PROFILE_PHOTOS_MAX_BYTES override the defaults
+    pub fn from_env() -> Self {
+        fn var<T: std::str::FromStr>(name: &str) -> Option<T> {
+            env::var(name).ok().and_then(|value| value.parse().ok())
+        }
+
+        let default = UploadLimits::default();
+        UploadLimits {
+            max_file_bytes: var("UPLOAD_MAX_FILE_BYTES").unwrap_or(default.max_file_bytes),
+            max_pixels: var("UPLOAD_MAX_PIXELS").unwrap_or(default.max_pixels),
+            max_profile_photos: var("PROFILE_PHOTOS_MAX").unwrap_or(default.max_profile_photos),
+            max_profile_bytes: var("PROFILE_PHOTOS_MAX_BYTES").unwrap_or(default.max_profile_bytes),
+        }
+    }
+
+    // Count and size of a profile's photos after adding `new_photos` uploads
+    // of `new_bytes`. Uploads count with their raw size, they are only
+    // encoded later.
+    pub fn check_profile(
+        &self,
+        photos: i64,
+        bytes: i64,
+        new_photos: usize,
+        new_bytes: usize,
+    ) -> Result<(), MediaError> {
+        if photos + new_photos as i64 > self.max_profile_photos {
+            return Err(MediaError::TooManyPhotos {
+                max_photos: self.max_profile_photos,
+            });
+        }
+        if bytes + new_bytes as i64 > self.max_profile_bytes {
+            return Err(MediaError::ProfileTooLarge {
+                max_bytes: self.max_profile_bytes,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UploadFormat {
+    Jpeg,
+    Png,
+    Webp,
+    Heic,
+}
+
+impl UploadFormat {
+    // Width and height from the header, without decoding the pixels
+    fn dimensions(self, bytes: &[u8]) -> Result<(u32, u32), MediaError> {
+        let format = match self {
+            UploadFormat::Jpeg => ImageFormat::Jpeg,
+            UploadFormat::Png => ImageFormat::Png,
+            UploadFormat::Webp => ImageFormat::WebP,
+            #[cfg(feature = "heic")]
+            UploadFormat::Heic => return heic::dimensions(bytes),
+            #[cfg(not(feature = "heic"))]
+            UploadFormat::Heic => return Err(MediaError::Heic),
+        };
+        ImageReader::with_format(Cursor::new(bytes), format)
+            .into_dimensions()
+            .map_err(MediaError::Decode)
+    }
+
+    // By magic bytes, whatever the client claims the content type to be.
+    // Without the `heic` feature HEIC is still recognized, to tell the user
+    // why it is rejected.
+    pub fn sniff(bytes: &[u8]) -> Result<Self, MediaError> {
+        const HEIC_BRANDS: [&[u8]; 6] = [b"heic", b"heix", b"hevc", b"hevx", b"mif1", b"msf1"];
+
+        if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Ok(UploadFormat::Jpeg)
+        } else if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Ok(UploadFormat::Png)
+        } else if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+            Ok(UploadFormat::Webp)
+        } else if bytes.len() >= 12
+            && &bytes[4..8] == b"ftyp"
+            && HEIC_BRANDS.contains(&&bytes[8..12])
+        {
+            if cfg!(feature = "heic") {
+                Ok(UploadFormat::Heic)
+            } else {
+                Err(MediaError::Heic)
+            }
+        } else {
+            Err(MediaError::UnsupportedFormat)
+        }
+    }
+}
+
+// Enough to tell all allowed formats apart
+const SNIFF_LEN: usize = 12;
+
+// Reads an image part chunk by chunk. The request is aborted as soon as the
+// part is over the size limit or its first bytes are not an allowed format,
+// and the header dimensions are checked before the image is ever decoded.
+pub async fn read_image_field(
+    mut field: Field<'_>,
+    limits: &UploadLimits,
+) -> Result<(Option<String>, UploadFormat, Bytes), MediaError> {
+    let file_name = field.file_name().map(str::to_string);
+
+    let mut data = Vec::new();
+    let mut format = None;
+    while let Some(chunk) = field.chunk().await? {
+        if data.len() + chunk.len() > limits.max_file_bytes {
+            return Err(MediaError::TooLarge {
+                max_bytes: limits.max_file_bytes,
+            });
+        }
+        data.extend_from_slice(&chunk);
+        if format.is_none() && data.len() >= SNIFF_LEN {
+            format = Some(UploadFormat::sniff(&data)?);
+        }
+    }
+    let format = match format {
+        Some(format) => format,
+        None => UploadFormat::sniff(&data)?,
+    };
+
+    let (width, height) = format.dimensions(&data)?;
+    if u64::from(width) * u64::from(height) > limits.max_pixels {
+        return Err(MediaError::TooManyPixels {
+            width,
+            height,
+            max_pixels: limits.max_pixels,
+        });
+    }
+
+    Ok((file_name, format, Bytes::from(data)))
+}
+
+// Decodes with the EXIF orientation applied, so that phone photos taken
+// sideways come out upright. The pixels are all that is kept: the encoders
+// below write no EXIF, XMP or ICC data, which drops GPS positions and camera
+// details from everything we store.
+pub fn decode(bytes: &[u8]) -> Result<DynamicImage, MediaError> {
+    #[cfg(feature = "heic")]
+    if let Ok(UploadFormat::Heic) = UploadFormat::sniff(bytes) {
+        return heic::decode(bytes);
+    }
+
+    let mut decoder = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .map_err(ImageError::IoError)
+        .and_then(|reader| reader.into_decoder())
+        .map_err(MediaError::Decode)?;
+    // A broken EXIF block is no reason to reject the photo
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let mut img = DynamicImage::from_decoder(decoder).map_err(MediaError::Decode)?;
+    img.apply_orientation(orientation);
+    Ok(img)
+}
+
+// HEIC through libheif. Rotation and mirroring are stored as image properties
+// rather than EXIF and libheif applies them while decoding.
+#[cfg(feature = "heic")]
+mod heic {
+    use image::{
+        error::{DecodingError, ImageFormatHint},
+        DynamicImage, ImageError, RgbImage, RgbaImage,
+    };
+    use libheif_rs::{ColorSpace, HeifContext, HeifError, LibHeif, RgbChroma};
+
+    use super::MediaError;
+
+    fn decode_error(e: HeifError) -> MediaError {
+        MediaError::Decode(ImageError::Decoding(DecodingError::new(
+            ImageFormatHint::Name("HEIC".to_string()),
+            e,
+        )))
+    }
+
+    pub fn dimensions(bytes: &[u8]) -> Result<(u32, u32), MediaError> {
+        let context = HeifContext::read_from_bytes(bytes).map_err(decode_error)?;
+        let handle = context.primary_image_handle().map_err(decode_error)?;
+        Ok((handle.width(), handle.height()))
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<DynamicImage, MediaError> {
+        let context = HeifContext::read_from_bytes(bytes).map_err(decode_error)?;
+        let handle = context.primary_image_handle().map_err(decode_error)?;
+        let has_alpha = handle.has_alpha_channel();
+        let chroma = if has_alpha {
+            RgbChroma::Rgba
+        } else {
+            RgbChroma::Rgb
+        };
+        let image = LibHeif::new()
+            .decode(&handle, ColorSpace::Rgb(chroma), None)
+            .map_err(decode_error)?;
+
+        let planes = image.planes();
+        let Some(plane) = planes.interleaved else {
+            return Err(MediaError::UnsupportedFormat);
+        };
+        // Rows may be padded beyond width * channels
+        let row_len = plane.width as usize * if has_alpha { 4 } else { 3 };
+        let pixels: Vec<u8> = plane
+            .data
+            .chunks(plane.stride)
+            .take(plane.height as usize)
+            .flat_map(|row| &row[..row_len])
+            .copied()
+            .collect();
+
+        let img = if has_alpha {
+            RgbaImage::from_raw(plane.width, plane.height, pixels).map(DynamicImage::ImageRgba8)
+        } else {
+            RgbImage::from_raw(plane.width, plane.height, pixels).map(DynamicImage::ImageRgb8)
+        };
+        img.ok_or(MediaError::UnsupportedFormat)
+    }
+}
+
+// Scales down to fit `max_dim`, keeping the aspect ratio; never scales up
+pub fn fit(img: &DynamicImage, max_dim: u32) -> DynamicImage {
+    let (width, height) = img.dimensions();
+    if width <= max_dim && height <= max_dim {
+        return img.clone();
+    }
+    img.resize(max_dim, max_dim, FilterType::CatmullRom)
+}
+
+pub fn encode_jpeg(img: &DynamicImage, quality: u8) -> Result<Vec<u8>, MediaError> {
+    let mut data = Vec::new();
+    DynamicImage::ImageRgb8(img.to_rgb8())
+        .write_with_encoder(JpegEncoder::new_with_quality(&mut data, quality))
+        .map_err(MediaError::Encode)?;
+    Ok(data)
+}
+
+// Lossy WebP through libwebp, keeping the alpha channel if there is one
+pub fn encode_webp(img: &DynamicImage, quality: u8) -> Result<Vec<u8>, MediaError> {
+    let encoded = if img.color().has_alpha() {
+        let pixels = img.to_rgba8();
+        webp::Encoder::from_rgba(&pixels, pixels.width(), pixels.height())
+            .encode_simple(false, quality as f32)
+    } else {
+        let pixels = img.to_rgb8();
+        webp::Encoder::from_rgb(&pixels, pixels.width(), pixels.height())
+            .encode_simple(false, quality as f32)
+    };
+    encoded.map(|data| data.to_vec()).map_err(|e| {
+        MediaError::Encode(ImageError::Encoding(EncodingError::new(
+            ImageFormatHint::Exact(ImageFormat::WebP),
+            format!("{:?}", e),
+        )))
+    })
+}
+
+// Encodes an already scaled image as JPEG within the policy's byte budget
+pub fn encode_jpeg_within(img: &DynamicImage, policy: &ImagePolicy) -> Result<Vec<u8>, MediaError> {
+    let mut quality = policy.start_quality;
+    loop {
+        let data = encode_jpeg(img, quality)?;
+        if data.len() <= policy.max_bytes || quality <= policy.min_quality {
+            if data.len() > policy.max_bytes {
+                println!(
+                    "WARNING: Could not reduce below {} KB even at Q={}",
+                    policy.max_bytes / 1000,
+                    quality
+                );
+            }
+            return Ok(data);
+        }
+        quality = quality.saturating_sub(5).max(policy.min_quality);
+    }
+}
+
+// Decode, scale and encode in one go, for uploads that are stored once
+pub fn compress(bytes: &[u8], policy: &ImagePolicy) -> Result<Vec<u8>, MediaError> {
+    let img = fit(&decode(bytes)?, policy.max_dim);
+    encode_jpeg_within(&img, policy)
+}
+
+// Runs image work on the blocking pool. The permits keep a burst of uploads
+// from occupying every blocking thread and all CPU cores at once.
+#[derive(Clone)]
+pub struct ImageWorkers {
+    permits: Arc<Semaphore>,
+}
+
+impl ImageWorkers {
+    pub fn new(limit: usize) -> Self {
+        ImageWorkers {
+            permits: Arc::new(Semaphore::new(limit.max(1))),
+        }
+    }
+
+    // IMAGE_WORKERS, by default the number of CPU cores
+    pub fn from_env() -> Self {
+        let limit = env::var("IMAGE_WORKERS")
+            .ok()
+            .and_then(|limit| limit.parse().ok())
+            .unwrap_or_else(|| {
+                std::thread::available_parallelism()
+                    .map(|cores| cores.get())
+                    .unwrap_or(2)
+            });
+        ImageWorkers::new(limit)
+    }
+
+    pub async fn run<T, F>(&self, work: F) -> Result<T, MediaError>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> Result<T, MediaError> + Send + 'static,
+    {
+        let _permit = self
+            .permits
+            .acquire()
+            .await
+            .expect("image worker semaphore is never closed");
+        tokio::task::spawn_blocking(work).await?
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use image::{Rgb, RgbImage};
+
+    use super::*;
+
+    // Noise compresses badly, so the quality steps make a visible difference
+    fn noise(width: u32, height: u32) -> DynamicImage {
+        let mut seed: u32 = 0x2545_f491;
+        DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |_, _| {
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            let [r, g, b, _] = seed.to_le_bytes();
+            Rgb([r, g, b])
+        }))
+    }
+
+    // Red left half, blue right half, so the direction of a rotation shows
+    fn halves(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, _| {
+            if x < width / 2 {
+                Rgb([255, 0, 0])
+            } else {
+                Rgb([0, 0, 255])
+            }
+        }))
+    }
+
+    // A JPEG as a phone writes it: stored landscape with an EXIF block that
+    // holds the Orientation tag and a GPS position
+    pub(crate) fn exif_jpeg(width: u32, height: u32, orientation: u16) -> Vec<u8> {
+        let mut tiff = Vec::new();
+        tiff.extend_from_slice(b"MM\0*");
+        tiff.extend_from_slice(&8u32.to_be_bytes());
+        // IFD0: Orientation and a pointer to the GPS IFD
+        tiff.extend_from_slice(&2u16.to_be_bytes());
+        tiff.extend_from_slice(&[0x01, 0x12, 0x00, 0x03, 0, 0, 0, 1]);
+        tiff.extend_from_slice(&orientation.to_be_bytes());
+        tiff.extend_from_slice(&[0, 0]);
+        tiff.extend_from_slice(&[0x88, 0x25, 0x00, 0x04, 0, 0, 0, 1]);
+        tiff.extend_from_slice(&38u32.to_be_bytes());
+        tiff.extend_from_slice(&0u32.to_be_bytes());
+        // GPS IFD: GPSLatitudeRef and GPSLatitude 52/1 31/1 12/1
+        tiff.extend_from_slice(&2u16.to_be_bytes());
+        tiff.extend_from_slice(&[0x00, 0x01, 0x00, 0x02, 0, 0, 0, 2, b'N', 0, 0, 0]);
+        tiff.extend_from_slice(&[0x00, 0x02, 0x00, 0x05, 0, 0, 0, 3]);
+        tiff.extend_from_slice(&68u32.to_be_bytes());
+        tiff.extend_from_slice(&0u32.to_be_bytes());
+        for value in [52u32, 31, 12] {
+            tiff.extend_from_slice(&value.to_be_bytes());
+            tiff.extend_from_slice(&1u32.to_be_bytes());
+        }
+
+        let jpeg = encode_jpeg(&halves(width, height), 90).unwrap();
+        let mut data = jpeg[..2].to_vec();
+        data.extend_from_slice(&[0xFF, 0xE1]);
+        data.extend_from_slice(&(tiff.len() as u16 + 8).to_be_bytes());
+        data.extend_from_slice(b"Exif\0\0");
+        data.extend_from_slice(&tiff);
+        data.extend_from_slice(&jpeg[2..]);
+        data
+    }
+
+    // EXIF in JPEG APP1 or in a WebP chunk, or a bare TIFF header
+    pub(crate) fn has_metadata(data: &[u8]) -> bool {
+        [&b"Exif"[..], b"EXIF", b"XMP", b"MM\0*", b"II*\0"]
+            .iter()
+            .any(|marker| data.windows(marker.len()).any(|window| window == *marker))
+    }
+
+    fn is_red(img: &DynamicImage, x: u32, y: u32) -> bool {
+        let [r, _, b, _] = img.get_pixel(x, y).0;
+        r > 200 && b < 60
+    }
+
+    #[test]
+    fn exif_fixture_carries_metadata() {
+        let data = exif_jpeg(300, 100, 6);
+        assert!(has_metadata(&data));
+        let mut decoder = ImageReader::new(Cursor::new(&data))
+            .with_guessed_format()
+            .unwrap()
+            .into_decoder()
+            .unwrap();
+        assert_eq!(decoder.orientation().unwrap(), Orientation::Rotate90);
+    }
+
+    #[test]
+    fn decode_applies_orientation_6() {
+        let img = decode(&exif_jpeg(300, 100, 6)).unwrap();
+        assert_eq!(img.dimensions(), (100, 300));
+        // Turned clockwise: the left half ends up on top
+        assert!(is_red(&img, 50, 20));
+        assert!(!is_red(&img, 50, 280));
+    }
+
+    #[test]
+    fn decode_applies_orientation_8() {
+        let img = decode(&exif_jpeg(300, 100, 8)).unwrap();
+        assert_eq!(img.dimensions(), (100, 300));
+        // Turned counter-clockwise: the left half ends up at the bottom
+        assert!(!is_red(&img, 50, 20));
+        assert!(is_red(&img, 50, 280));
+    }
+
+    #[test]
+    fn decode_without_orientation_keeps_dimensions() {
+        let img = decode(&exif_jpeg(300, 100, 1)).unwrap();
+        assert_eq!(img.dimensions(), (300, 100));
+    }
+
+    #[test]
+    fn encoders_drop_exif_and_gps() {
+        let img = fit(&decode(&exif_jpeg(300, 100, 6)).unwrap(), 200);
+        assert!(!has_metadata(&encode_jpeg(&img, 90).unwrap()));
+        assert!(!has_metadata(
+            &encode_jpeg_within(&img, &policy(usize::MAX)).unwrap()
+        ));
+        assert!(!has_metadata(&encode_webp(&img, 82).unwrap()));
+
+        let compressed = compress(&exif_jpeg(300, 100, 8), &policy(usize::MAX)).unwrap();
+        assert!(!has_metadata(&compressed));
+        assert_eq!(decode(&compressed).unwrap().dimensions(), (100, 300));
+    }
+
+    #[test]
+    fn encode_webp_is_lossy() {
+        let img = noise(400, 300);
+        let data = encode_webp(&img, 82).unwrap();
+        // Simple lossy files carry a single VP8 chunk, lossless ones VP8L
+        assert_eq!(&data[12..16], b"VP8 ");
+        assert!(data.len() < encode_webp(&img, 100).unwrap().len());
+        assert_eq!(decode(&data).unwrap().dimensions(), (400, 300));
+    }
+
+    #[test]
+    fn encode_webp_keeps_alpha() {
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+            40,
+            30,
+            image::Rgba([200, 0, 0, 128]),
+        ));
+        let decoded = decode(&encode_webp(&img, 82).unwrap()).unwrap();
+        assert!(decoded.color().has_alpha());
+        assert_eq!(decoded.to_rgba8().get_pixel(10, 10)[3], 128);
+    }
+
+    fn policy(max_bytes: usize) -> ImagePolicy {
+        ImagePolicy {
+            max_dim: 1000,
+            max_bytes,
+            start_quality: 90,
+            min_quality: 40,
+        }
+    }
+
+    #[test]
+    fn encode_jpeg_within_lowers_quality_until_it_fits() {
+        let img = noise(200, 200);
+        let best = encode_jpeg(&img, 90).unwrap();
+        let floor = encode_jpeg(&img, 40).unwrap();
+        let max_bytes = (best.len() + floor.len()) / 2;
+
+        let data = encode_jpeg_within(&img, &policy(max_bytes)).unwrap();
+        assert!(data.len() <= max_bytes);
+        assert!(data.len() > floor.len());
+    }
+
+    #[test]
+    fn encode_jpeg_within_keeps_first_quality_that_fits() {
+        let img = noise(64, 64);
+        let data = encode_jpeg_within(&img, &policy(usize::MAX)).unwrap();
+        assert_eq!(data, encode_jpeg(&img, 90).unwrap());
+    }
+
+    #[test]
+    fn encode_jpeg_within_stops_at_quality_floor() {
+        let img = noise(200, 200);
+        let data = encode_jpeg_within(&img, &policy(1)).unwrap();
+        assert_eq!(data, encode_jpeg(&img, 40).unwrap());
+    }
+
+    #[test]
+    fn fit_keeps_aspect_ratio() {
+        assert_eq!(fit(&noise(400, 100), 200).dimensions(), (200, 50));
+        assert_eq!(fit(&noise(100, 400), 200).dimensions(), (50, 200));
+        assert_eq!(fit(&noise(300, 300), 200).dimensions(), (200, 200));
+    }
+
+    #[test]
+    fn fit_never_upscales() {
+        assert_eq!(fit(&noise(120, 80), 200).dimensions(), (120, 80));
+        assert_eq!(fit(&noise(200, 10), 200).dimensions(), (200, 10));
+    }
+
+    fn encoded(format: ImageFormat) -> Vec<u8> {
+        let mut data = Vec::new();
+        noise(8, 8)
+            .write_to(&mut Cursor::new(&mut data), format)
+            .unwrap();
+        data
+    }
+
+    #[test]
+    fn sniff_detects_allowed_formats() {
+        assert_eq!(
+            UploadFormat::sniff(&encoded(ImageFormat::Jpeg)).unwrap(),
+            UploadFormat::Jpeg
+        );
+        assert_eq!(
+            UploadFormat::sniff(&encoded(ImageFormat::Png)).unwrap(),
+            UploadFormat::Png
+        );
+        assert_eq!(
+            UploadFormat::sniff(&encoded(ImageFormat::WebP)).unwrap(),
+            UploadFormat::Webp
+        );
+    }
+
+    #[test]
+    fn sniff_recognizes_heic() {
+        let heic = b"\0\0\0\x18ftypheic\0\0\0\0mif1heic";
+        if cfg!(feature = "heic") {
+            assert_eq!(UploadFormat::sniff(heic).unwrap(), UploadFormat::Heic);
+        } else {
+            assert!(matches!(UploadFormat::sniff(heic), Err(MediaError::Heic)));
+        }
+        let avif = b"\0\0\0\x18ftypavif\0\0\0\0mif1avif";
+        assert!(matches!(
+            UploadFormat::sniff(avif),
+            Err(MediaError::UnsupportedFormat)
+        ));
+    }
+
+    #[test]
+    fn sniff_rejects_other_formats() {
+        for data in [
+            encoded(ImageFormat::Gif),
+            encoded(ImageFormat::Bmp),
+            b"%PDF-1.7\n".to_vec(),
+            b"<svg xmlns=\"http://www.w3.org/2000/svg\"/>".to_vec(),
+            Vec::new(),
+        ] {
+            assert!(matches!(
+                UploadFormat::sniff(&data),
+                Err(MediaError::UnsupportedFormat)
+            ));
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn image_workers_respect_limit() {
+        let workers = ImageWorkers::new(2);
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+
+        let jobs = (0..8).map(|_| {
+            let workers = workers.clone();
+            let running = running.clone();
+            let peak = peak.clone();
+            async move {
+                workers
+                    .run(move || {
+                        let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                        peak.fetch_max(now, Ordering::SeqCst);
+                        std::thread::sleep(Duration::from_millis(20));
+                        running.fetch_sub(1, Ordering::SeqCst);
+                        Ok(())
+                    })
+                    .await
+            }
+        });
+        for result in futures::future::join_all(jobs).await {
+            result.unwrap();
+        }
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+    }
+}
@@ -1,4 +1,4 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use sqlx::FromRow;
 use uuid::Uuid;
 
@@ -67,7 +67,7 @@ pub struct ResetPasswordModel {
 }
 
 #[derive(Debug, FromRow, Deserialize, Serialize)]
-#[allow(non_snake_case)]
+#[allow(non_snake_case, dead_code)]
 pub struct ProfileModel {
     pub id: Uuid,
     pub viewer_id: Uuid,
@@ -75,7 +75,6 @@ pub struct ProfileModel {
     pub rechtsform_id: Uuid,
     pub email: String,
     pub telefon: String,
-    pub craft_id: Uuid,
     pub experience: i16,
     pub location: String,
     pub lat: f32,
@@ -92,7 +91,7 @@ pub struct ProfileModel {
 }
 
 #[derive(Debug, FromRow, Deserialize, Serialize)]
-#[allow(non_snake_case)]
+#[allow(non_snake_case, dead_code)]
 pub struct PhotoModel {
     pub id: Uuid,
     pub file_name: String,
@@ -106,9 +105,11 @@ pub struct PhotoModel {
 #[derive(Debug, FromRow, Deserialize, Serialize)]
 #[allow(non_snake_case)]
 pub struct PhotoDataModel {
+    pub profile_id: Uuid,
     pub file_name: String,
-    pub content_type: String,
-    pub photo_data: Vec<u8>,
+    pub storage_key: Option<String>,
+    // Only set for photos not yet moved by `mano migrate-photo-blobs`
+    pub photo_data: Option<Vec<u8>>,
 }
 
 #[derive(Debug, FromRow, Deserialize, Serialize)]
@@ -134,3 +135,323 @@ pub struct RechtsformModel {
 pub struct ExplainRechtsformModel {
     pub explain_name: String,
 }
+
+// Revisions from before multiple crafts store a single, optional `craft`
+fn deserialize_crafts<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Crafts {
+        List(Vec<String>),
+        Single(Option<String>),
+    }
+
+    Ok(match Crafts::deserialize(deserializer)? {
+        Crafts::List(crafts) => crafts,
+        Crafts::Single(craft) => craft.into_iter().collect(),
+    })
+}
+
+// Editable state of a profile as stored in profile_revisions.snapshot
+#[derive(Debug, FromRow, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ProfileSnapshot {
+    pub name: String,
+    pub rechtsform: Option<String>,
+    pub email: String,
+    pub telefon: Option<String>,
+    // Primary craft first
+    #[serde(default, alias = "craft", deserialize_with = "deserialize_crafts")]
+    pub crafts: Vec<String>,
+    pub experience: i16,
+    pub location: String,
+    pub lat: f64,
+    pub lng: f64,
+    pub service_radius_km: Option<i16>,
+    // None in revisions from before service areas were tracked
+    pub service_plz: Option<Vec<String>>,
+    pub service_districts: Option<Vec<String>>,
+    #[serde(default)]
+    pub availability_status: String,
+    pub available_from: Option<chrono::NaiveDate>,
+    // Weekday and hours of each opening time
+    #[serde(default)]
+    pub opening_hours: Vec<String>,
+    // Dates and note of each closure
+    #[serde(default)]
+    pub closures: Vec<String>,
+    // Name, skill and price of each catalogue entry
+    #[serde(default)]
+    pub services: Vec<String>,
+    pub website: Option<String>,
+    pub instagram: Option<String>,
+    pub bio: Option<String>,
+    pub handwerks_karten_nummer: String,
+    pub skills: Vec<String>,
+    // In display order
+    pub photos: Vec<Uuid>,
+    #[serde(default)]
+    pub cover_photo: Option<Uuid>,
+    // Caption and alt text of each photo that has one
+    #[serde(default)]
+    pub photo_texts: Vec<String>,
+    // Title and description of each project
+    #[serde(default)]
+    pub projects: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProfileStatus {
+    Draft,
+    Pending,
+    Approved,
+    Rejected,
+    Suspended,
+}
+
+impl ProfileStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProfileStatus::Draft => "draft",
+            ProfileStatus::Pending => "pending",
+            ProfileStatus::Approved => "approved",
+            ProfileStatus::Rejected => "rejected",
+            ProfileStatus::Suspended => "suspended",
+        }
+    }
+
+    pub fn parse(status: &str) -> Option<Self> {
+        match status {
+            "draft" => Some(ProfileStatus::Draft),
+            "pending" => Some(ProfileStatus::Pending),
+            "approved" => Some(ProfileStatus::Approved),
+            "rejected" => Some(ProfileStatus::Rejected),
+            "suspended" => Some(ProfileStatus::Suspended),
+            _ => None,
+        }
+    }
+
+    // Statuses from which a profile may move to `self`
+    pub fn allowed_from(&self) -> &'static [ProfileStatus] {
+        match self {
+            ProfileStatus::Draft => &[],
+            ProfileStatus::Pending => &[
+                ProfileStatus::Draft,
+                ProfileStatus::Rejected,
+                ProfileStatus::Approved,
+            ],
+            ProfileStatus::Approved => &[
+                ProfileStatus::Pending,
+                ProfileStatus::Rejected,
+                ProfileStatus::Suspended,
+            ],
+            ProfileStatus::Rejected => &[ProfileStatus::Pending],
+            ProfileStatus::Suspended => &[ProfileStatus::Pending, ProfileStatus::Approved],
+        }
+    }
+}
+
+// How readily a profile takes new jobs, set by its owner
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AvailabilityStatus {
+    Available,
+    Limited,
+    Booked,
+    Paused,
+}
+
+impl AvailabilityStatus {
+    pub fn parse(status: &str) -> Option<Self> {
+        match status {
+            "available" => Some(AvailabilityStatus::Available),
+            "limited" => Some(AvailabilityStatus::Limited),
+            "booked" => Some(AvailabilityStatus::Booked),
+            "paused" => Some(AvailabilityStatus::Paused),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceModel {
+    Hourly,
+    Fixed,
+    OnRequest,
+}
+
+impl PriceModel {
+    pub fn parse(price_model: &str) -> Option<Self> {
+        match price_model {
+            "hourly" => Some(PriceModel::Hourly),
+            "fixed" => Some(PriceModel::Fixed),
+            "on_request" => Some(PriceModel::OnRequest),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InquiryStatus {
+    New,
+    Answered,
+    Declined,
+    Done,
+}
+
+impl InquiryStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InquiryStatus::New => "new",
+            InquiryStatus::Answered => "answered",
+            InquiryStatus::Declined => "declined",
+            InquiryStatus::Done => "done",
+        }
+    }
+
+    pub fn parse(status: &str) -> Option<Self> {
+        match status {
+            "new" => Some(InquiryStatus::New),
+            "answered" => Some(InquiryStatus::Answered),
+            "declined" => Some(InquiryStatus::Declined),
+            "done" => Some(InquiryStatus::Done),
+            _ => None,
+        }
+    }
+
+    // Statuses from which an inquiry may move to `self`
+    pub fn allowed_from(&self) -> &'static [InquiryStatus] {
+        match self {
+            InquiryStatus::New => &[],
+            InquiryStatus::Answered => &[InquiryStatus::New],
+            InquiryStatus::Declined => &[InquiryStatus::New, InquiryStatus::Answered],
+            InquiryStatus::Done => &[InquiryStatus::New, InquiryStatus::Answered],
+        }
+    }
+}
+
+// An inquiry as shown to its customer and to the profile owner
+#[derive(Debug, FromRow, Deserialize, Serialize)]
+pub struct InquiryModel {
+    pub id: Uuid,
+    pub profile_id: Uuid,
+    pub profile_name: String,
+    pub customer_name: String,
+    pub craft: Option<String>,
+    pub message: String,
+    pub location: String,
+    pub timeframe: String,
+    pub status: String,
+    pub photos: Vec<Uuid>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub status_changed_at: chrono::DateTime<chrono::Utc>,
+}
+
+// A conversation as listed for one of its two participants
+#[derive(Debug, FromRow, Deserialize, Serialize)]
+pub struct ThreadSummaryModel {
+    pub id: Uuid,
+    pub profile_id: Uuid,
+    pub profile_name: String,
+    pub customer_name: String,
+    pub inquiry_id: Option<Uuid>,
+    pub is_customer: bool,
+    pub customer_shares_contact: bool,
+    pub owner_shares_contact: bool,
+    pub last_message: Option<String>,
+    pub last_message_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub unread_count: i64,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReviewStatus {
+    Published,
+    Held,
+    Hidden,
+}
+
+impl ReviewStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReviewStatus::Published => "published",
+            ReviewStatus::Held => "held",
+            ReviewStatus::Hidden => "hidden",
+        }
+    }
+
+    pub fn parse(status: &str) -> Option<Self> {
+        match status {
+            "published" => Some(ReviewStatus::Published),
+            "held" => Some(ReviewStatus::Held),
+            "hidden" => Some(ReviewStatus::Hidden),
+            _ => None,
+        }
+    }
+
+    // Statuses from which a review may move to `self`
+    pub fn allowed_from(&self) -> &'static [ReviewStatus] {
+        match self {
+            ReviewStatus::Published => &[ReviewStatus::Held, ReviewStatus::Hidden],
+            ReviewStatus::Held => &[ReviewStatus::Published],
+            ReviewStatus::Hidden => &[ReviewStatus::Published, ReviewStatus::Held],
+        }
+    }
+}
+
+// A review with the owner's reply
+#[derive(Debug, FromRow, Deserialize, Serialize)]
+pub struct ReviewModel {
+    pub id: Uuid,
+    pub profile_id: Uuid,
+    pub author_name: String,
+    pub rating: i16,
+    pub body: String,
+    pub status: String,
+    pub inquiry_id: Option<Uuid>,
+    pub owner_reply: Option<String>,
+    pub owner_replied_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn snapshot(crafts: (&str, serde_json::Value)) -> ProfileSnapshot {
+        let mut value = json!({
+            "name": "Tischlerei Müller",
+            "rechtsform": null,
+            "email": "info@example.de",
+            "telefon": null,
+            "experience": 12,
+            "location": "Nürnberg",
+            "lat": 49.45,
+            "lng": 11.08,
+            "website": null,
+            "instagram": null,
+            "bio": null,
+            "handwerks_karten_nummer": "1",
+            "skills": [],
+            "photos": []
+        });
+        value[crafts.0] = crafts.1;
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn snapshot_maps_legacy_craft_into_crafts() {
+        assert_eq!(snapshot(("craft", json!("Tischler"))).crafts, ["Tischler"]);
+        assert!(snapshot(("craft", json!(null))).crafts.is_empty());
+        assert_eq!(
+            snapshot(("crafts", json!(["Tischler", "Zimmerer"]))).crafts,
+            ["Tischler", "Zimmerer"]
+        );
+        assert!(snapshot(("other", json!(null))).crafts.is_empty());
+    }
+}
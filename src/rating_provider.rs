@@ -0,0 +1,633 @@
+use std::{collections::HashSet, path::PathBuf, sync::Arc, time::Duration};
+
+use futures::future::BoxFuture;
+use serde::Deserialize;
+use sqlx::{Pool, Postgres};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{validation::FieldError, AppState};
+
+// Source of the aggregate of our own reviews in `ratings`
+pub const REVIEWS_SOURCE: &str = "reviews";
+pub const PLACES_SOURCE: &str = "google";
+pub const CSV_SOURCE: &str = "csv";
+const SOURCE_MAX: usize = 30;
+const URL_MAX: usize = 500;
+
+#[derive(Error, Debug)]
+pub enum RatingProviderError {
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Failed to read ratings file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+// A rating of a profile as reported by an external source
+#[derive(Debug, Clone)]
+pub struct ExternalRating {
+    pub profile_id: Uuid,
+    pub source: String,
+    pub rating: f64,
+    pub review_count: i32,
+    pub url: Option<String>,
+}
+
+// A profile together with its id at the provider
+#[derive(Debug, Clone)]
+pub struct RatingTarget {
+    pub profile_id: Uuid,
+    pub external_id: String,
+}
+
+pub trait RatingProvider: Send + Sync {
+    // Name of the provider in `profile_rating_sources`
+    fn source(&self) -> &'static str;
+
+    // Current ratings for the given profiles. Providers that know their
+    // profiles themselves, like a CSV file, ignore the targets.
+    fn fetch<'a>(
+        &'a self,
+        targets: &'a [RatingTarget],
+    ) -> BoxFuture<'a, Result<Vec<ExternalRating>, RatingProviderError>>;
+}
+
+// Place details in the format of the Google Places API (New)
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PlaceDetails {
+    rating: Option<f64>,
+    user_rating_count: Option<i32>,
+    google_maps_uri: Option<String>,
+}
+
+pub struct PlacesRatingProvider {
+    client: reqwest::Client,
+    base_url: String,
+    api_key: String,
+}
+
+impl PlacesRatingProvider {
+    pub fn new(base_url: &str, api_key: &str) -> Result<Self, RatingProviderError> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()?;
+        Ok(PlacesRatingProvider {
+            client,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key: api_key.to_string(),
+        })
+    }
+
+    async fn fetch_place(&self, place_id: &str) -> Result<PlaceDetails, RatingProviderError> {
+        let details = self
+            .client
+            .get(format!(
+                "{}/places/{}",
+                self.base_url,
+                urlencoding::encode(place_id)
+            ))
+            .header("X-Goog-Api-Key", &self.api_key)
+            .header("X-Goog-FieldMask", "rating,userRatingCount,googleMapsUri")
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<PlaceDetails>()
+            .await?;
+        Ok(details)
+    }
+}
+
+impl RatingProvider for PlacesRatingProvider {
+    fn source(&self) -> &'static str {
+        PLACES_SOURCE
+    }
+
+    fn fetch<'a>(
+        &'a self,
+        targets: &'a [RatingTarget],
+    ) -> BoxFuture<'a, Result<Vec<ExternalRating>, RatingProviderError>> {
+        Box::pin(async move {
+            let mut ratings = Vec::with_capacity(targets.len());
+            // One unknown place must not stop the others
+            for target in targets {
+                match self.fetch_place(&target.external_id).await {
+                    Ok(PlaceDetails {
+                        rating: Some(rating),
+                        user_rating_count,
+                        google_maps_uri,
+                    }) => ratings.push(ExternalRating {
+                        profile_id: target.profile_id,
+                        source: PLACES_SOURCE.to_string(),
+                        rating,
+                        review_count: user_rating_count.unwrap_or_default(),
+                        url: google_maps_uri,
+                    }),
+                    Ok(_) => {}
+                    Err(e) => eprintln!(
+                        "places rating for profile {} failed: {}",
+                        target.profile_id, e
+                    ),
+                }
+            }
+            Ok(ratings)
+        })
+    }
+}
+
+// Reads `profile_id,rating,review_count[,url][,source]` rows from a file that
+// is maintained by hand
+pub struct CsvRatingProvider {
+    path: PathBuf,
+}
+
+impl CsvRatingProvider {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        CsvRatingProvider { path: path.into() }
+    }
+}
+
+impl RatingProvider for CsvRatingProvider {
+    fn source(&self) -> &'static str {
+        CSV_SOURCE
+    }
+
+    fn fetch<'a>(
+        &'a self,
+        _targets: &'a [RatingTarget],
+    ) -> BoxFuture<'a, Result<Vec<ExternalRating>, RatingProviderError>> {
+        Box::pin(async move {
+            let input = tokio::fs::read_to_string(&self.path).await?;
+            let (ratings, errors) = parse_ratings_csv(&input);
+            for error in &errors {
+                eprintln!("{}: {}", self.path.display(), error.message);
+            }
+            Ok(ratings)
+        })
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct CsvRow {
+    profile_id: String,
+    rating: String,
+    review_count: String,
+    url: Option<String>,
+    source: Option<String>,
+}
+
+// Parses a ratings CSV with a header row. Invalid rows are left out and
+// reported as errors on `rows[<line>].<column>`.
+pub fn parse_ratings_csv(input: &str) -> (Vec<ExternalRating>, Vec<FieldError>) {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_reader(input.as_bytes());
+
+    let mut ratings = Vec::new();
+    let mut errors = Vec::new();
+    let mut seen = HashSet::new();
+
+    let headers = match reader.headers() {
+        Ok(headers) => headers.clone(),
+        Err(e) => {
+            errors.push(FieldError::new("rows", "invalid_format", e.to_string()));
+            return (ratings, errors);
+        }
+    };
+
+    for result in reader.records() {
+        let row = result.and_then(|record| {
+            let line = record.position().map(|p| p.line()).unwrap_or_default();
+            record
+                .deserialize::<CsvRow>(Some(&headers))
+                .map(|row| (line, row))
+        });
+        let (line, row) = match row {
+            Ok(row) => row,
+            Err(e) => {
+                errors.push(FieldError::new("rows", "invalid_format", e.to_string()));
+                continue;
+            }
+        };
+        let field = |column: &str| format!("rows[{}].{}", line, column);
+        let row_errors = errors.len();
+
+        let profile_id = Uuid::parse_str(&row.profile_id).ok();
+        if profile_id.is_none() {
+            errors.push(FieldError::invalid_format(&field("profile_id"), "a UUID"));
+        }
+
+        let rating = row.rating.replace(',', ".").parse::<f64>().ok();
+        if !rating.is_some_and(|rating| (1.0..=5.0).contains(&rating)) {
+            errors.push(FieldError::out_of_range(&field("rating"), 1, 5));
+        }
+
+        let review_count = row.review_count.parse::<i32>().ok();
+        if review_count.is_none_or(|count| count < 0) {
+            errors.push(FieldError::invalid_format(
+                &field("review_count"),
+                "a non-negative number",
+            ));
+        }
+
+        let source = row
+            .source
+            .filter(|source| !source.is_empty())
+            .unwrap_or_else(|| CSV_SOURCE.to_string())
+            .to_lowercase();
+        if source == REVIEWS_SOURCE
+            || source.chars().count() > SOURCE_MAX
+            || !source
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            errors.push(FieldError::invalid_format(
+                &field("source"),
+                "a provider name other than reviews",
+            ));
+        }
+
+        let url = row.url.filter(|url| !url.is_empty());
+        if url
+            .as_deref()
+            .is_some_and(|url| url.chars().count() > URL_MAX || !url.starts_with("https://"))
+        {
+            errors.push(FieldError::invalid_format(&field("url"), "an https URL"));
+        }
+
+        let (Some(profile_id), Some(rating), Some(review_count)) =
+            (profile_id, rating, review_count)
+        else {
+            continue;
+        };
+        if errors.len() > row_errors {
+            continue;
+        }
+        if !seen.insert((profile_id, source.clone())) {
+            errors.push(FieldError::new(
+                &field("profile_id"),
+                "duplicate",
+                format!("{} is listed twice for {}", field("profile_id"), source),
+            ));
+            continue;
+        }
+
+        ratings.push(ExternalRating {
+            profile_id,
+            source,
+            rating,
+            review_count,
+            url,
+        });
+    }
+
+    (ratings, errors)
+}
+
+// Upserts the ratings with the current time as fetch time. Ratings of
+// profiles that do not exist are skipped. Returns the number stored.
+pub async fn store_ratings(
+    db: &Pool<Postgres>,
+    ratings: &[ExternalRating],
+) -> Result<u64, sqlx::Error> {
+    let profile_ids: Vec<Uuid> = ratings.iter().map(|r| r.profile_id).collect();
+    let sources: Vec<String> = ratings.iter().map(|r| r.source.clone()).collect();
+    let values: Vec<f64> = ratings.iter().map(|r| r.rating).collect();
+    let review_counts: Vec<i32> = ratings.iter().map(|r| r.review_count).collect();
+    let urls: Vec<Option<String>> = ratings.iter().map(|r| r.url.clone()).collect();
+
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO ratings (profile_id, source, rating, review_count, url, fetched_at)
+        SELECT t.profile_id, t.source, t.rating, LEAST(t.review_count, 32767)::SMALLINT, t.url, NOW()
+        FROM UNNEST($1::uuid[], $2::varchar[], $3::float8[], $4::int[], $5::varchar[])
+            AS t(profile_id, source, rating, review_count, url)
+        JOIN profiles p ON p.id = t.profile_id
+        ON CONFLICT (profile_id, source) DO UPDATE
+        SET rating = EXCLUDED.rating,
+            review_count = EXCLUDED.review_count,
+            url = EXCLUDED.url,
+            fetched_at = EXCLUDED.fetched_at,
+            updated_at = NOW(),
+            version = ratings.version % 32767 + 1
+        "#,
+        &profile_ids,
+        &sources,
+        &values,
+        &review_counts,
+        &urls as &[Option<String>]
+    )
+    .execute(db)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+async fn refresh_provider(
+    db: &Pool<Postgres>,
+    provider: &dyn RatingProvider,
+) -> Result<u64, RatingProviderError> {
+    let targets = sqlx::query_as!(
+        RatingTarget,
+        "SELECT profile_id, external_id FROM profile_rating_sources WHERE source = $1",
+        provider.source()
+    )
+    .fetch_all(db)
+    .await?;
+
+    let ratings = provider.fetch(&targets).await?;
+    Ok(store_ratings(db, &ratings).await?)
+}
+
+// Places provider if an API key is configured, CSV provider if a file is
+pub fn providers_from_env() -> Vec<Box<dyn RatingProvider>> {
+    let mut providers: Vec<Box<dyn RatingProvider>> = Vec::new();
+
+    if let Ok(api_key) = std::env::var("PLACES_API_KEY") {
+        let base_url = std::env::var("PLACES_API_URL")
+            .unwrap_or_else(|_| "https://places.googleapis.com/v1".to_string());
+        match PlacesRatingProvider::new(&base_url, &api_key) {
+            Ok(provider) => providers.push(Box::new(provider)),
+            Err(e) => eprintln!("Failed to create PlacesRatingProvider: {:?}", e),
+        }
+    }
+    if let Ok(path) = std::env::var("RATINGS_CSV_PATH") {
+        providers.push(Box::new(CsvRatingProvider::new(path)));
+    }
+
+    providers
+}
+
+// Background job started from main
+pub async fn run_rating_refresh(
+    data: Arc<AppState>,
+    providers: Vec<Box<dyn RatingProvider>>,
+    every: Duration,
+) {
+    let mut interval = tokio::time::interval(every);
+    loop {
+        interval.tick().await;
+        for provider in &providers {
+            match refresh_provider(&data.db, provider.as_ref()).await {
+                Ok(stored) => println!(
+                    "run_rating_refresh: stored {} ratings from {}",
+                    stored,
+                    provider.source()
+                ),
+                Err(e) => eprintln!("run_rating_refresh {} error: {}", provider.source(), e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+
+    use super::*;
+
+    // Local stand-in for the places API. Serves `GET /v1/places/:id` from a
+    // map of place ids to place details in the API's format, and rejects
+    // requests without an API key like the real service.
+    mod places_mock {
+        use std::{collections::HashMap, sync::Arc};
+
+        use axum::{
+            extract::{Path, State},
+            http::{HeaderMap, StatusCode},
+            routing::get,
+            Json, Router,
+        };
+        use serde_json::json;
+
+        type Fixtures = Arc<HashMap<String, serde_json::Value>>;
+
+        async fn get_place(
+            State(fixtures): State<Fixtures>,
+            headers: HeaderMap,
+            Path(place_id): Path<String>,
+        ) -> (StatusCode, Json<serde_json::Value>) {
+            let has_key = headers
+                .get("X-Goog-Api-Key")
+                .and_then(|key| key.to_str().ok())
+                .is_some_and(|key| !key.is_empty());
+            if !has_key {
+                return (
+                    StatusCode::FORBIDDEN,
+                    Json(json!({
+                        "error": { "code": 403, "message": "API key missing", "status": "PERMISSION_DENIED" }
+                    })),
+                );
+            }
+
+            match fixtures.get(&place_id) {
+                Some(place) => (StatusCode::OK, Json(place.clone())),
+                None => (
+                    StatusCode::NOT_FOUND,
+                    Json(json!({
+                        "error": { "code": 404, "message": "Place not found", "status": "NOT_FOUND" }
+                    })),
+                ),
+            }
+        }
+
+        pub fn app(fixtures: HashMap<String, serde_json::Value>) -> Router {
+            Router::new()
+                .route("/v1/places/:id", get(get_place))
+                .with_state(Arc::new(fixtures))
+        }
+    }
+
+    // Serves `app` on a free local port until the test's runtime shuts down
+    async fn spawn(app: axum::Router) -> std::net::SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        addr
+    }
+
+    fn target(external_id: &str) -> RatingTarget {
+        RatingTarget {
+            profile_id: Uuid::new_v4(),
+            external_id: external_id.to_string(),
+        }
+    }
+
+    async fn places_mock() -> String {
+        let fixtures = HashMap::from([
+            (
+                "rated".to_string(),
+                json!({
+                    "rating": 4.6,
+                    "userRatingCount": 87,
+                    "googleMapsUri": "https://maps.google.com/?cid=1"
+                }),
+            ),
+            ("unrated".to_string(), json!({ "userRatingCount": 0 })),
+            ("only rating".to_string(), json!({ "rating": 3.0 })),
+        ]);
+        let addr = spawn(places_mock::app(fixtures)).await;
+        format!("http://{}/v1/", addr)
+    }
+
+    #[tokio::test]
+    async fn places_provider_fetches_rated_places() {
+        let provider = PlacesRatingProvider::new(&places_mock().await, "test-key").unwrap();
+        let targets = [
+            target("rated"),
+            target("unrated"),
+            target("missing"),
+            target("only rating"),
+        ];
+
+        let ratings = provider.fetch(&targets).await.unwrap();
+
+        assert_eq!(ratings.len(), 2);
+        assert_eq!(ratings[0].profile_id, targets[0].profile_id);
+        assert_eq!(ratings[0].source, PLACES_SOURCE);
+        assert_eq!(ratings[0].rating, 4.6);
+        assert_eq!(ratings[0].review_count, 87);
+        assert_eq!(
+            ratings[0].url.as_deref(),
+            Some("https://maps.google.com/?cid=1")
+        );
+        // Place ids are escaped in the path
+        assert_eq!(ratings[1].profile_id, targets[3].profile_id);
+        assert_eq!(ratings[1].review_count, 0);
+        assert_eq!(ratings[1].url, None);
+    }
+
+    #[tokio::test]
+    async fn places_provider_sends_api_key() {
+        let provider = PlacesRatingProvider::new(&places_mock().await, "").unwrap();
+        assert!(matches!(
+            provider.fetch_place("rated").await,
+            Err(RatingProviderError::Http(e)) if e.status() == Some(reqwest::StatusCode::FORBIDDEN)
+        ));
+        // A failing place is logged and skipped, not fatal for the run
+        assert!(provider.fetch(&[target("rated")]).await.unwrap().is_empty());
+    }
+
+    fn codes(errors: &[FieldError]) -> Vec<(&str, &str)> {
+        errors.iter().map(|e| (e.field.as_str(), e.code)).collect()
+    }
+
+    const PROFILE_A: &str = "6f1c2b52-4f0e-4c4e-9a53-2f1d0f3b8a01";
+    const PROFILE_B: &str = "0b7d1e2a-93c4-4d5f-8e6a-7c8b9d0e1f02";
+
+    #[test]
+    fn csv_reads_valid_rows() {
+        let input = format!(
+            "profile_id,rating,review_count,url,source\n\
+             {PROFILE_A},4.5,12,https://example.de/a,\n\
+             {PROFILE_B},\"4,8\",3,,Trustpilot\n"
+        );
+
+        let (ratings, errors) = parse_ratings_csv(&input);
+
+        assert_eq!(errors, vec![]);
+        assert_eq!(ratings.len(), 2);
+        assert_eq!(ratings[0].profile_id.to_string(), PROFILE_A);
+        assert_eq!(ratings[0].source, CSV_SOURCE);
+        assert_eq!(ratings[0].rating, 4.5);
+        assert_eq!(ratings[0].review_count, 12);
+        assert_eq!(ratings[0].url.as_deref(), Some("https://example.de/a"));
+        assert_eq!(ratings[1].source, "trustpilot");
+        assert_eq!(ratings[1].rating, 4.8);
+        assert_eq!(ratings[1].url, None);
+    }
+
+    #[test]
+    fn csv_optional_columns_can_be_left_out() {
+        let input = format!("profile_id,rating,review_count\n{PROFILE_A},5,1\n");
+        let (ratings, errors) = parse_ratings_csv(&input);
+        assert_eq!(errors, vec![]);
+        assert_eq!(ratings.len(), 1);
+        assert_eq!(ratings[0].source, CSV_SOURCE);
+    }
+
+    #[test]
+    fn csv_reports_bad_rows_by_line() {
+        let input = format!(
+            "profile_id,rating,review_count,url\n\
+             not-a-uuid,4,1,\n\
+             {PROFILE_A},0.5,1,\n\
+             {PROFILE_A},5.5,-1,\n\
+             {PROFILE_A},viel,1,\n\
+             {PROFILE_A},4,1,http://example.de\n\
+             {PROFILE_B},4,1,\n"
+        );
+
+        let (ratings, errors) = parse_ratings_csv(&input);
+
+        assert_eq!(
+            codes(&errors),
+            vec![
+                ("rows[2].profile_id", "invalid_format"),
+                ("rows[3].rating", "out_of_range"),
+                ("rows[4].rating", "out_of_range"),
+                ("rows[4].review_count", "invalid_format"),
+                ("rows[5].rating", "out_of_range"),
+                ("rows[6].url", "invalid_format"),
+            ]
+        );
+        assert_eq!(ratings.len(), 1);
+        assert_eq!(ratings[0].profile_id.to_string(), PROFILE_B);
+    }
+
+    #[test]
+    fn csv_rejects_duplicates_per_source() {
+        let input = format!(
+            "profile_id,rating,review_count,url,source\n\
+             {PROFILE_A},4,1,,\n\
+             {PROFILE_A},5,2,,csv\n\
+             {PROFILE_A},3,7,,trustpilot\n"
+        );
+
+        let (ratings, errors) = parse_ratings_csv(&input);
+
+        assert_eq!(codes(&errors), vec![("rows[3].profile_id", "duplicate")]);
+        assert_eq!(ratings.len(), 2);
+        assert_eq!(ratings[0].rating, 4.0);
+        assert_eq!(ratings[1].source, "trustpilot");
+    }
+
+    #[test]
+    fn csv_rejects_reviews_source() {
+        let input = format!(
+            "profile_id,rating,review_count,url,source\n\
+             {PROFILE_A},4,1,,reviews\n\
+             {PROFILE_A},4,1,,Reviews\n\
+             {PROFILE_A},4,1,,my-source\n\
+             {PROFILE_A},4,1,,{}\n",
+            "s".repeat(SOURCE_MAX + 1)
+        );
+
+        let (ratings, errors) = parse_ratings_csv(&input);
+
+        assert!(ratings.is_empty());
+        assert_eq!(
+            codes(&errors),
+            vec![
+                ("rows[2].source", "invalid_format"),
+                ("rows[3].source", "invalid_format"),
+                ("rows[4].source", "invalid_format"),
+                ("rows[5].source", "invalid_format"),
+            ]
+        );
+    }
+
+    #[test]
+    fn csv_without_rows() {
+        assert_eq!(parse_ratings_csv("").0.len(), 0);
+        let (ratings, errors) = parse_ratings_csv("profile_id,rating,review_count\n");
+        assert!(ratings.is_empty());
+        assert_eq!(errors, vec![]);
+    }
+}
@@ -4,23 +4,57 @@ use crate::{
             auth_status, get_viewer, is_admin, login, logout, pre_register, pre_reset_password,
             register, reset_password,
         },
+        availability::{get_availability, update_availability},
         craft::{create_craft, get_crafts, update_craft},
         favorits::{add_favorite, get_favorite_profiles, remove_favorite},
+        handwerkskarte::{
+            create_handwerkskammer, delete_handwerkskammer, get_handwerkskammern,
+            get_handwerkskarte, get_handwerkskarte_scan, get_pending_handwerkskarten,
+            reject_handwerkskarte, submit_handwerkskarte, update_handwerkskammer,
+            verify_handwerkskarte,
+        },
         health_checker_handler, health_checker_handler2,
+        inquiry::{
+            create_inquiry, get_inquiry, get_inquiry_photo, get_profile_inquiries,
+            get_sent_inquiries, update_inquiry_status,
+        },
+        messages::{
+            create_inquiry_thread, create_profile_thread, get_message_attachment, get_thread,
+            get_thread_messages, get_threads, mark_thread_read, send_message, share_thread_contact,
+        },
+        moderation::{
+            accept_profile, dismiss_profile_reports, get_profile_status, get_reported_profiles,
+            reject_profile, report_profile, submit_profile, suspend_profile,
+        },
+        photo::get_photo,
+        portfolio::{
+            get_projects, reorder_profile_photos, set_cover_photo, update_photo_details,
+            update_projects,
+        },
         profile::{
-            accept_profile, create_profile, delete_profile, get_photo, get_photos_of_profile,
-            get_profile, get_profile_email, get_profile_id, get_profiles, get_profiles_by_search,
-            get_profiles_without_viewer, get_unaccepted_profiles, update_profile,
+            create_profile, delete_profile, delete_profile_photo, get_photos_of_profile,
+            get_profile, get_profile_by_slug, get_profile_email, get_profile_id, get_profiles,
+            get_profiles_by_search, get_profiles_without_viewer, get_unaccepted_profiles,
+            patch_profile, update_profile, upload_profile_photos,
+        },
+        rating::{
+            create_review, delete_review, get_profile_reviews, get_reported_reviews, hide_review,
+            import_ratings, reply_to_review, report_review, restore_review, update_rating_source,
+            update_review,
         },
         rechtsformen::{
             create_rechtsform, get_explain_rechtsformen, get_rechtsformen, update_rechtsform,
         },
+        revision::{get_profile_revisions, rollback_profile_revision},
+        seo::{get_profile_json_ld, get_sitemap},
+        services::{get_services, update_services},
         skill::{create_skill, get_skills, update_skill},
     },
     AppState,
 };
 use axum::{
-    routing::{delete, get, post, put},
+    http::header,
+    routing::{delete, get, patch, post, put},
     Router,
 };
 use std::sync::Arc;
@@ -30,10 +64,12 @@ pub fn create_router(app_state: Arc<AppState>) -> Router {
     let cors = CorsLayer::new()
         .allow_origin(Any)
         .allow_headers(Any)
-        .allow_methods(Any);
+        .allow_methods(Any)
+        .expose_headers([header::ETAG]);
     Router::new()
         .route("/api/healthchecker", get(health_checker_handler))
         .route("/api/healthchecker2", get(health_checker_handler2))
+        .route("/sitemap.xml", get(get_sitemap))
         .route("/api/pre-register", post(pre_register))
         .route("/api/login", post(login))
         .route("/api/pre-reset-password", post(pre_reset_password))
@@ -56,10 +92,111 @@ pub fn create_router(app_state: Arc<AppState>) -> Router {
         .route("/api/profiles/unaccepted", get(get_unaccepted_profiles))
         .route("/api/profiles/unverified", get(get_profiles_without_viewer))
         .route("/api/profile/:id", get(get_profile))
+        .route("/api/profile/by-slug/:slug", get(get_profile_by_slug))
+        .route("/api/profile/:id/json-ld", get(get_profile_json_ld))
         .route("/api/profile/:id", delete(delete_profile))
         .route("/api/profile/:id", put(update_profile))
+        .route("/api/profile/:id", patch(patch_profile))
+        .route("/api/profile/:id/photos", post(upload_profile_photos))
+        .route("/api/profile/:id/photos/order", put(reorder_profile_photos))
+        .route(
+            "/api/profile/:id/photos/:photo_id",
+            delete(delete_profile_photo),
+        )
+        .route(
+            "/api/profile/:id/photos/:photo_id",
+            put(update_photo_details),
+        )
+        .route("/api/profile/:id/cover", put(set_cover_photo))
+        .route("/api/profile/:id/projects", get(get_projects))
+        .route("/api/profile/:id/projects", put(update_projects))
+        .route("/api/profile/:id/revisions", get(get_profile_revisions))
+        .route(
+            "/api/profile/:id/revisions/:revision_id/rollback",
+            post(rollback_profile_revision),
+        )
+        .route("/api/profile/:id/handwerkskarte", get(get_handwerkskarte))
+        .route(
+            "/api/profile/:id/handwerkskarte",
+            post(submit_handwerkskarte),
+        )
+        .route("/api/handwerkskammern", get(get_handwerkskammern))
+        .route("/api/handwerkskammern", post(create_handwerkskammer))
+        .route("/api/handwerkskammern/:id", put(update_handwerkskammer))
+        .route("/api/handwerkskammern/:id", delete(delete_handwerkskammer))
+        .route(
+            "/api/handwerkskarten/pending",
+            get(get_pending_handwerkskarten),
+        )
+        .route(
+            "/api/handwerkskarten/:id/scan",
+            get(get_handwerkskarte_scan),
+        )
+        .route(
+            "/api/handwerkskarten/:id/verify",
+            post(verify_handwerkskarte),
+        )
+        .route(
+            "/api/handwerkskarten/:id/reject",
+            post(reject_handwerkskarte),
+        )
         .route("/api/profile/email/:id", get(get_profile_email))
         .route("/api/profile/accept/:id", post(accept_profile))
+        .route("/api/profile/reject/:id", post(reject_profile))
+        .route("/api/profile/suspend/:id", post(suspend_profile))
+        .route("/api/profile/:id/report", post(report_profile))
+        .route(
+            "/api/profile/:id/reports/dismiss",
+            post(dismiss_profile_reports),
+        )
+        .route("/api/profiles/reported", get(get_reported_profiles))
+        .route("/api/profile/submit/:id", post(submit_profile))
+        .route("/api/profile/:id/status", get(get_profile_status))
+        .route(
+            "/api/profile/:id/availability",
+            get(get_availability).put(update_availability),
+        )
+        .route(
+            "/api/profile/:id/services",
+            get(get_services).put(update_services),
+        )
+        .route(
+            "/api/profile/:id/inquiries",
+            get(get_profile_inquiries).post(create_inquiry),
+        )
+        .route("/api/inquiries/sent", get(get_sent_inquiries))
+        .route("/api/inquiries/:id", get(get_inquiry))
+        .route("/api/inquiries/:id/status", patch(update_inquiry_status))
+        .route(
+            "/api/inquiries/:id/photos/:photo_id",
+            get(get_inquiry_photo),
+        )
+        .route(
+            "/api/profile/:id/reviews",
+            get(get_profile_reviews).post(create_review),
+        )
+        .route("/api/reviews/:id", put(update_review).delete(delete_review))
+        .route("/api/reviews/reported", get(get_reported_reviews))
+        .route("/api/profile/:id/rating-sources", put(update_rating_source))
+        .route("/api/ratings/import", post(import_ratings))
+        .route("/api/reviews/:id/reply", post(reply_to_review))
+        .route("/api/reviews/:id/report", post(report_review))
+        .route("/api/reviews/:id/hide", post(hide_review))
+        .route("/api/reviews/:id/restore", post(restore_review))
+        .route("/api/profile/:id/threads", post(create_profile_thread))
+        .route("/api/inquiries/:id/thread", post(create_inquiry_thread))
+        .route("/api/threads", get(get_threads))
+        .route("/api/threads/:id", get(get_thread))
+        .route(
+            "/api/threads/:id/messages",
+            get(get_thread_messages).post(send_message),
+        )
+        .route("/api/threads/:id/read", post(mark_thread_read))
+        .route("/api/threads/:id/contact", put(share_thread_contact))
+        .route(
+            "/api/threads/:id/attachments/:attachment_id",
+            get(get_message_attachment),
+        )
         .route("/api/profile", post(create_profile))
         .route("/api/profile-id", get(get_profile_id))
         .route("/api/profiles/search", post(get_profiles_by_search))
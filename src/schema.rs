@@ -1,5 +1,5 @@
+use chrono::{NaiveDate, NaiveTime};
 use serde::{Deserialize, Serialize};
-use sqlx::prelude::FromRow;
 
 // #[derive(Deserialize, Default)]
 // pub struct FilterOptions {
@@ -45,9 +45,18 @@ pub struct SearchSchema {
     pub craft: Option<String>,
     pub location: Option<String>,
     pub skill: Option<String>,
+    pub plz: Option<String>,
+    pub district: Option<String>,
     pub range: Option<f64>, // in kilometers
     pub lat: Option<f64>,
     pub lng: Option<f64>,
+    pub verified_craftsman: Option<bool>,
+    // Only profiles with a next available date within this many weeks
+    pub available_within_weeks: Option<i64>,
+    // 1 (€) to 3 (€€€)
+    pub max_price_level: Option<i16>,
+    // "price_asc", "price_desc", "response_time" or "response_rate"
+    pub sort: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -82,3 +91,216 @@ pub struct UpdateRechtsformSchema {
     pub old_name: String,
     pub new_name: String,
 }
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct ProfileInput {
+    pub name: Option<String>,
+    pub rechtsform: Option<String>,
+    pub email: Option<String>,
+    pub telefon: Option<String>,
+    // Primary craft; added to `crafts` if it is not listed there
+    pub craft: Option<String>,
+    pub crafts: Option<Vec<String>>,
+    pub experience: Option<i16>,
+    pub location: Option<String>,
+    pub lat: Option<f64>,
+    pub lng: Option<f64>,
+    pub service_radius_km: Option<i16>,
+    pub service_plz: Option<Vec<String>>,
+    pub service_districts: Option<Vec<String>>,
+    pub website: Option<String>,
+    pub instagram: Option<String>,
+    pub bio: Option<String>,
+    pub handwerks_karten_nummer: Option<String>,
+    pub skills: Option<Vec<String>>,
+    pub deleted_photos: Option<Vec<uuid::Uuid>>,
+}
+
+// `skills` in a PATCH body: an array replaces the whole list, an object
+// adds/removes individual skills.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum SkillsPatch {
+    Replace(Vec<String>),
+    Modify {
+        #[serde(default)]
+        add: Vec<String>,
+        #[serde(default)]
+        remove: Vec<String>,
+    },
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ProfilePatch {
+    pub set: ProfileInput,
+    pub clear: Vec<String>,
+    pub skills: Option<SkillsPatch>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ModerationSchema {
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct HandwerkskammerSchema {
+    pub name: String,
+    // POSIX regex the Handwerkskarte number has to match
+    pub number_pattern: String,
+    pub example: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct CreateProfileQuery {
+    pub draft: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct OpeningHoursSchema {
+    // ISO weekday, 1 = Monday .. 7 = Sunday
+    pub weekday: i16,
+    pub opens: NaiveTime,
+    pub closes: NaiveTime,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ClosureSchema {
+    pub starts_on: NaiveDate,
+    pub ends_on: NaiveDate,
+    pub note: Option<String>,
+}
+
+// Replaces the whole availability of a profile
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AvailabilitySchema {
+    pub status: String,
+    pub available_from: Option<NaiveDate>,
+    #[serde(default)]
+    pub opening_hours: Vec<OpeningHoursSchema>,
+    #[serde(default)]
+    pub closures: Vec<ClosureSchema>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ServiceSchema {
+    // Defaults to the skill name
+    pub name: Option<String>,
+    pub skill: String,
+    pub price_model: String,
+    pub price_min_cents: Option<i32>,
+    pub price_max_cents: Option<i32>,
+}
+
+// Replaces the whole service catalogue of a profile, in display order
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ServiceCatalogueSchema {
+    pub services: Vec<ServiceSchema>,
+}
+
+// Text fields of the multipart inquiry form
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct InquiryInput {
+    pub message: Option<String>,
+    pub craft: Option<String>,
+    pub location: Option<String>,
+    // "asap", "weeks", "months" or "flexible"
+    pub timeframe: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct PhotoQuery {
+    pub size: Option<String>,
+    pub format: Option<String>,
+}
+
+// All photos of a profile in their new display order
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PhotoOrderSchema {
+    pub photo_ids: Vec<uuid::Uuid>,
+}
+
+// Replaces the texts of a photo; missing or null clears them
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct PhotoDetailsSchema {
+    pub caption: Option<String>,
+    pub alt_text: Option<String>,
+}
+
+// `null` goes back to using the first photo
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct CoverPhotoSchema {
+    pub photo_id: Option<uuid::Uuid>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ProjectSchema {
+    // Existing project to keep; a new one is created without
+    pub id: Option<uuid::Uuid>,
+    pub title: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub photo_ids: Vec<uuid::Uuid>,
+}
+
+// Replaces all projects of a profile, in display order. Projects left out
+// are deleted, their photos stay on the profile.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ProjectsSchema {
+    pub projects: Vec<ProjectSchema>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct InquiryQuery {
+    pub status: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct InquiryStatusSchema {
+    pub status: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct PageQuery {
+    pub page: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ContactShareSchema {
+    pub share: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReviewInput {
+    // 1 to 5 stars
+    pub rating: i16,
+    pub body: String,
+    // A completed inquiry of the reviewer at this profile
+    pub inquiry_id: Option<uuid::Uuid>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReviewReplySchema {
+    pub reply: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReviewReportSchema {
+    // "spam", "offensive", "fake", "personal_data", "conflict_of_interest" or "other"
+    pub reason: String,
+    pub comment: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ProfileReportSchema {
+    // "fake", "offensive", "spam", "impersonation", "wrong_information" or "other"
+    pub category: String,
+    pub comment: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RatingSourceSchema {
+    pub source: String,
+    // Id of the profile at the provider, e.g. a place id; null unlinks it
+    pub external_id: Option<String>,
+}
@@ -1,16 +1,180 @@
 use std::sync::Arc;
 
 use axum::{
-    http::{header, StatusCode},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
     response::IntoResponse,
     Json,
 };
 use serde_json::json;
 use sha2::{Digest, Sha256};
+use sqlx::PgConnection;
 use uuid::Uuid;
 
 use crate::{model::UserSessionModel, AppState};
 
+// Strong ETag for a row, derived from its `version` column
+pub fn version_etag(version: i16) -> HeaderValue {
+    HeaderValue::from_str(&format!("\"{}\"", version)).unwrap()
+}
+
+// Versions listed in an If-Match header. `None` means the request carries no
+// precondition (header missing or `*`). Weak or foreign tags never match.
+pub fn if_match_versions(headers: &HeaderMap) -> Option<Vec<i16>> {
+    let values: Vec<&str> = headers
+        .get_all(header::IF_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .collect();
+
+    if values.is_empty() || values.contains(&"*") {
+        return None;
+    }
+
+    Some(
+        values
+            .into_iter()
+            .filter_map(|tag| tag.strip_prefix('"')?.strip_suffix('"')?.parse().ok())
+            .collect(),
+    )
+}
+
+pub fn precondition_failed() -> (StatusCode, Json<serde_json::Value>) {
+    (
+        StatusCode::PRECONDITION_FAILED,
+        Json(json!({
+            "status": "fail",
+            "message": "Profile was modified in the meantime. Reload it and try again."
+        })),
+    )
+}
+
+const SLUG_BASE_MAX: usize = 80;
+
+// URL slug from a profile's name and location, e.g. "Müller & Söhne", "Köln"
+// becomes "mueller-soehne-koeln"
+pub fn slugify(name: &str, location: &str) -> String {
+    let mut slug = String::new();
+    for c in format!("{} {}", name, location).to_lowercase().chars() {
+        let replacement = match c {
+            'ä' | 'æ' => "ae",
+            'ö' | 'œ' => "oe",
+            'ü' => "ue",
+            'ß' => "ss",
+            'à' | 'á' | 'â' | 'ã' | 'å' => "a",
+            'ç' => "c",
+            'è' | 'é' | 'ê' | 'ë' => "e",
+            'ì' | 'í' | 'î' | 'ï' => "i",
+            'ñ' => "n",
+            'ò' | 'ó' | 'ô' | 'õ' | 'ø' => "o",
+            'ù' | 'ú' | 'û' => "u",
+            'ý' | 'ÿ' => "y",
+            c if c.is_ascii_alphanumeric() => {
+                slug.push(c);
+                continue;
+            }
+            _ => "-",
+        };
+        if replacement == "-" && (slug.is_empty() || slug.ends_with('-')) {
+            continue;
+        }
+        slug.push_str(replacement);
+    }
+
+    let slug: String = slug.chars().take(SLUG_BASE_MAX).collect();
+    let slug = slug.trim_matches('-');
+    if slug.is_empty() {
+        "profil".to_string()
+    } else {
+        slug.to_string()
+    }
+}
+
+// First free slug of the form `base`, `base-2`, `base-3`, ... Slugs other
+// profiles used before a rename stay reserved so their redirects keep working.
+pub async fn unique_profile_slug(
+    conn: &mut PgConnection,
+    base: &str,
+    profile_id: Option<Uuid>,
+) -> Result<String, sqlx::Error> {
+    let taken: Vec<String> = sqlx::query_scalar!(
+        r#"
+        SELECT slug AS "slug!" FROM profiles
+        WHERE (slug = $1 OR slug LIKE $1 || '-%') AND id IS DISTINCT FROM $2
+        UNION
+        SELECT slug FROM profile_slug_redirects
+        WHERE (slug = $1 OR slug LIKE $1 || '-%') AND profile_id IS DISTINCT FROM $2
+        "#,
+        base,
+        profile_id
+    )
+    .fetch_all(conn)
+    .await?;
+
+    if !taken.iter().any(|slug| slug == base) {
+        return Ok(base.to_string());
+    }
+
+    let mut suffix = 2;
+    loop {
+        let candidate = format!("{}-{}", base, suffix);
+        if !taken.contains(&candidate) {
+            return Ok(candidate);
+        }
+        suffix += 1;
+    }
+}
+
+// Gives the profile a new slug after its name or location changed and keeps
+// the old one as a redirect
+pub async fn refresh_profile_slug(
+    conn: &mut PgConnection,
+    profile_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    let profile = sqlx::query!(
+        "SELECT name, location, slug FROM profiles WHERE id = $1",
+        profile_id
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    // Compared exactly: a suffix alone cannot tell "bau-berlin-24" for
+    // "Bau Berlin" apart from the slug of "Bau 24 Berlin"
+    let base = slugify(&profile.name, &profile.location);
+    let slug = unique_profile_slug(&mut *conn, &base, Some(profile_id)).await?;
+    if slug == profile.slug {
+        return Ok(());
+    }
+
+    sqlx::query!(
+        "UPDATE profiles SET slug = $2 WHERE id = $1",
+        profile_id,
+        slug
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO profile_slug_redirects (slug, profile_id)
+        VALUES ($1, $2)
+        ON CONFLICT (slug) DO UPDATE SET profile_id = EXCLUDED.profile_id, created_at = NOW()
+        "#,
+        profile.slug,
+        profile_id
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query!("DELETE FROM profile_slug_redirects WHERE slug = $1", slug)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(())
+}
+
 pub async fn log_user_in(
     viewer_id: &Uuid,
     data: Arc<AppState>,
@@ -34,7 +198,7 @@ pub async fn log_user_in(
         &salt,
     ).fetch_one(&data.db).await;
 
-    if let Err(_) = query_result {
+    if query_result.is_err() {
         let error_response = json!({
             "status": "fail",
             "message": "Internal Server Error."
@@ -75,7 +239,7 @@ pub async fn log_user_in(
     headers.append(header::SET_COOKIE, session_id_cookie.parse().unwrap());
 
     let update_result = sqlx::query!(
-        "UPDATE viewers SET last_login = NOW() WHERE id = $1",
+        "UPDATE viewers SET last_login = NOW(), version = version % 32767 + 1 WHERE id = $1",
         viewer_id
     )
     .execute(&data.db)
@@ -116,93 +280,3 @@ pub async fn log_user_in(
     println!("Login successful.");
     Ok((StatusCode::OK, headers, response).into_response())
 }
-
-// pub struct Rating {
-//     rating: f32,
-//     review_count: u32,
-// }
-
-// pub async fn get_ratings_by_google_maps_link(google_maps_link: &str) -> Option<Rating> {
-//     return match scrape_page(google_maps_link).await {
-//         Ok(html) => {
-//             if let Some((rating, review_count)) = extract_review_data(&html).await {
-//                 Some(Rating {
-//                     rating,
-//                     review_count,
-//                 })
-//             } else {
-//                 None
-//             }
-//         }
-//         Err(e) => {
-//             eprintln!("❌ Error: {}", e);
-//             None
-//         }
-//     };
-// }
-
-// async fn extract_review_data(html: &str) -> Option<(f32, u32)> {
-//     let document = Html::parse_document(html);
-//
-//     let rating_selector = Selector::parse(".fontDisplayLarge").unwrap();
-//     let rating = document
-//         .select(&rating_selector)
-//         .next()
-//         .map(|el| el.text().collect::<String>())
-//         .map(|e| e.replace(",", "."))
-//         .map(|el| el.parse::<f32>());
-//
-//     let review_count_selector = Selector::parse("button.GQjSyb span").unwrap();
-//     let review_count = document
-//         .select(&review_count_selector)
-//         .next()
-//         .map(|el| el.text().collect::<String>())
-//         .map(|e| e.replace(",", "."))
-//         .map(|el| el.split_whitespace().collect::<Vec<_>>()[0].parse::<u32>());
-//
-//     return if let (Some(Ok(rating)), Some(Ok(review_count))) = (rating, review_count) {
-//         Some((rating, review_count))
-//     } else {
-//         None
-//     };
-// }
-
-// async fn scrape_page(url: &str) -> Result<String, Box<dyn Error>> {
-//     let config = BrowserConfig::builder()
-//         .chrome_executable(
-//             "/Applications/Google Chrome Canary.app/Contents/MacOS/Google Chrome Canary",
-//         )
-//         .no_sandbox()
-//         .build()?;
-//
-//     // Launch browser
-//     let (mut browser, mut handler) = Browser::launch(config).await?;
-//
-//     // Keep event loop running to avoid crashes
-//     tokio::spawn(async move { while let Some(_) = handler.next().await {} });
-//
-//     // Open a new tab
-//     let page = browser.new_page(url).await?;
-//     println!("🚀 Page loaded");
-//
-//     // Wait for the cookie pop-up to appear
-//     sleep(Duration::from_secs(5)).await;
-//
-//     // Try to find and click the "Accept All" button
-//     let accept_button_xpath = "//span[@jsname='V67aGc']/ancestor::button";
-//     if let Some(accept_button) = page.find_element(accept_button_xpath).await.ok() {
-//         println!("🍪 Found 'Accept All' button. Clicking...");
-//         accept_button.click().await.ok();
-//         sleep(Duration::from_secs(3)).await; // Wait for page to reload
-//     } else {
-//         println!("❌ 'Accept All' button not found.");
-//     }
-//
-//     // Scrape the **actual** page content after accepting cookies
-//     let html = page.content().await?;
-//
-//     // Close browser
-//     browser.close().await?;
-//
-//     Ok(html)
-// }
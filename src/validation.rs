@@ -0,0 +1,2037 @@
+use std::collections::HashSet;
+
+use axum::{http::StatusCode, Json};
+use serde::Serialize;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    model::{AvailabilityStatus, PriceModel},
+    schema::{
+        AvailabilitySchema, HandwerkskammerSchema, InquiryInput, PhotoDetailsSchema,
+        PhotoOrderSchema, ProfileInput, ProfilePatch, ProfileReportSchema, ProjectsSchema,
+        RatingSourceSchema, ReviewInput, ReviewReplySchema, ReviewReportSchema,
+        ServiceCatalogueSchema, SkillsPatch,
+    },
+};
+
+// Column sizes from the profiles migration
+pub const NAME_MAX: usize = 100;
+pub const EMAIL_MAX: usize = 100;
+pub const TELEFON_MAX: usize = 100;
+pub const LOCATION_MAX: usize = 200;
+pub const WEBSITE_MAX: usize = 100;
+pub const INSTAGRAM_MAX: usize = 100;
+pub const BIO_MAX: usize = 500;
+pub const HANDWERKS_KARTEN_NUMMER_MAX: usize = 100;
+pub const HANDWERKSKAMMER_NAME_MAX: usize = 100;
+pub const NUMBER_PATTERN_MAX: usize = 200;
+pub const EXPERIENCE_MAX: i16 = 1000;
+pub const CRAFTS_MAX: usize = 5;
+pub const SERVICE_RADIUS_KM_MAX: i16 = 500;
+pub const SERVICE_AREAS_MAX: usize = 200;
+pub const DISTRICT_MAX: usize = 100;
+pub const OPENING_HOURS_MAX: usize = 21;
+pub const CLOSURES_MAX: usize = 100;
+pub const CLOSURE_NOTE_MAX: usize = 200;
+pub const SERVICES_MAX: usize = 50;
+pub const SERVICE_NAME_MAX: usize = 100;
+pub const INQUIRY_MESSAGE_MAX: usize = 2000;
+pub const INQUIRY_TIMEFRAMES: [&str; 4] = ["asap", "weeks", "months", "flexible"];
+pub const REVIEW_BODY_MAX: usize = 2000;
+pub const REVIEW_RATING_MIN: i16 = 1;
+pub const REVIEW_RATING_MAX: i16 = 5;
+pub const REVIEW_REPORT_REASONS: [&str; 6] = [
+    "spam",
+    "offensive",
+    "fake",
+    "personal_data",
+    "conflict_of_interest",
+    "other",
+];
+pub const REVIEW_REPORT_COMMENT_MAX: usize = 500;
+pub const EXTERNAL_ID_MAX: usize = 200;
+pub const PROFILE_REPORT_CATEGORIES: [&str; 6] = [
+    "fake",
+    "offensive",
+    "spam",
+    "impersonation",
+    "wrong_information",
+    "other",
+];
+pub const PROFILE_REPORT_COMMENT_MAX: usize = 1000;
+pub const PHOTO_CAPTION_MAX: usize = 200;
+pub const PHOTO_ALT_TEXT_MAX: usize = 300;
+pub const PROJECTS_MAX: usize = 20;
+pub const PROJECT_TITLE_MAX: usize = 100;
+pub const PROJECT_DESCRIPTION_MAX: usize = 1000;
+
+// Profile columns that may be NULL and can therefore be cleared by a patch
+pub const CLEARABLE_FIELDS: [&str; 5] = [
+    "telefon",
+    "website",
+    "instagram",
+    "bio",
+    "service_radius_km",
+];
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct FieldError {
+    pub field: String,
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &str, code: &'static str, message: impl Into<String>) -> Self {
+        FieldError {
+            field: field.to_string(),
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn required(field: &str) -> Self {
+        Self::new(field, "required", format!("{} is required", field))
+    }
+
+    pub fn too_long(field: &str, max: usize) -> Self {
+        Self::new(
+            field,
+            "too_long",
+            format!("{} must be at most {} characters", field, max),
+        )
+    }
+
+    pub fn invalid_format(field: &str, expected: &str) -> Self {
+        Self::new(
+            field,
+            "invalid_format",
+            format!("{} must be {}", field, expected),
+        )
+    }
+
+    pub fn out_of_range(field: &str, min: impl ToString, max: impl ToString) -> Self {
+        Self::new(
+            field,
+            "out_of_range",
+            format!(
+                "{} must be between {} and {}",
+                field,
+                min.to_string(),
+                max.to_string()
+            ),
+        )
+    }
+
+    pub fn unknown(field: &str, value: &str) -> Self {
+        Self::new(
+            field,
+            "unknown",
+            format!("{} '{}' does not exist", field, value),
+        )
+    }
+
+    pub fn unknown_field(field: &str) -> Self {
+        Self::new(
+            field,
+            "unknown_field",
+            format!("{} is not a known field", field),
+        )
+    }
+}
+
+pub fn validation_error_response(errors: Vec<FieldError>) -> (StatusCode, Json<serde_json::Value>) {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(json!({
+            "status": "fail",
+            "message": "Invalid profile data",
+            "errors": errors
+        })),
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ValidationMode {
+    Create,
+    Update,
+}
+
+impl ProfileInput {
+    /// Builds the input from raw `(name, value)` pairs as they arrive in a
+    /// multipart form. Values that cannot be parsed are reported as errors
+    /// instead of aborting, so the client gets every problem at once.
+    pub fn from_fields<I>(fields: I) -> (ProfileInput, Vec<FieldError>)
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let mut input = ProfileInput::default();
+        let mut errors = Vec::new();
+
+        for (name, text) in fields {
+            match name.as_str() {
+                "name" => input.name = Some(text),
+                "rechtsform" | "rechtsform_explain_name" => input.rechtsform = Some(text),
+                "email" => input.email = Some(text.trim().to_lowercase()),
+                "telefon" => input.telefon = Some(text),
+                "craft" => input.craft = Some(text),
+                "crafts" => match serde_json::from_str::<Vec<String>>(&text) {
+                    Ok(crafts) => input.crafts = Some(crafts),
+                    Err(_) => errors.push(FieldError::invalid_format(
+                        "crafts",
+                        "a JSON array of craft names",
+                    )),
+                },
+                "experience" => match text.trim().parse::<i16>() {
+                    Ok(value) => input.experience = Some(value),
+                    Err(_) => {
+                        errors.push(FieldError::invalid_format("experience", "a whole number"))
+                    }
+                },
+                "location" => input.location = Some(text),
+                "lat" => match text.trim().parse::<f64>() {
+                    Ok(value) => input.lat = Some(value),
+                    Err(_) => errors.push(FieldError::invalid_format("lat", "a number")),
+                },
+                "lng" => match text.trim().parse::<f64>() {
+                    Ok(value) => input.lng = Some(value),
+                    Err(_) => errors.push(FieldError::invalid_format("lng", "a number")),
+                },
+                "service_radius_km" => match text.trim().parse::<i16>() {
+                    Ok(value) => input.service_radius_km = Some(value),
+                    Err(_) => errors.push(FieldError::invalid_format(
+                        "service_radius_km",
+                        "a whole number",
+                    )),
+                },
+                "service_plz" => match serde_json::from_str::<Vec<String>>(&text) {
+                    Ok(plz) => {
+                        input.service_plz = Some(plz.iter().map(|p| p.trim().to_string()).collect())
+                    }
+                    Err(_) => errors.push(FieldError::invalid_format(
+                        "service_plz",
+                        "a JSON array of postcodes",
+                    )),
+                },
+                "service_districts" => match serde_json::from_str::<Vec<String>>(&text) {
+                    Ok(districts) => {
+                        input.service_districts =
+                            Some(districts.iter().map(|d| d.trim().to_string()).collect())
+                    }
+                    Err(_) => errors.push(FieldError::invalid_format(
+                        "service_districts",
+                        "a JSON array of district names",
+                    )),
+                },
+                "website" => input.website = Some(text),
+                "instagram" => input.instagram = Some(text),
+                "bio" => input.bio = Some(text),
+                "handwerks_karten_nummer" => input.handwerks_karten_nummer = Some(text),
+                "skills" => match serde_json::from_str::<Vec<String>>(&text) {
+                    Ok(skills) => input.skills = Some(skills),
+                    Err(_) => errors.push(FieldError::invalid_format(
+                        "skills",
+                        "a JSON array of skill names",
+                    )),
+                },
+                "deleted_photos" => match serde_json::from_str::<Vec<Uuid>>(&text) {
+                    Ok(ids) => input.deleted_photos = Some(ids),
+                    Err(_) => errors.push(FieldError::invalid_format(
+                        "deleted_photos",
+                        "a JSON array of photo ids",
+                    )),
+                },
+                _ => errors.push(FieldError::unknown_field(&name)),
+            }
+        }
+
+        (input, errors)
+    }
+
+    /// Same as `from_fields`, but for a JSON object body. Scalars are
+    /// converted to their text form so both encodings share one parser;
+    /// `null` is treated like an absent field.
+    pub fn from_json(body: serde_json::Value) -> (ProfileInput, Vec<FieldError>) {
+        let serde_json::Value::Object(map) = body else {
+            return (
+                ProfileInput::default(),
+                vec![FieldError::invalid_format("body", "a JSON object")],
+            );
+        };
+
+        let fields = map.into_iter().filter_map(|(name, value)| match value {
+            serde_json::Value::Null => None,
+            serde_json::Value::String(text) => Some((name, text)),
+            other => Some((name, other.to_string())),
+        });
+
+        ProfileInput::from_fields(fields)
+    }
+
+    /// Crafts named in the input with the primary one first and duplicates
+    /// removed. `None` if neither `craft` nor `crafts` was sent.
+    pub fn craft_names(&self) -> Option<Vec<String>> {
+        if self.craft.is_none() && self.crafts.is_none() {
+            return None;
+        }
+
+        let mut names: Vec<String> = Vec::new();
+        for name in self.craft.iter().chain(self.crafts.iter().flatten()) {
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+        Some(names)
+    }
+
+    pub fn validate(&self, mode: ValidationMode) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+
+        if mode == ValidationMode::Create {
+            for (field, value) in [
+                ("name", &self.name),
+                ("email", &self.email),
+                ("location", &self.location),
+                ("rechtsform", &self.rechtsform),
+            ] {
+                if value.as_deref().is_none_or(|v| v.trim().is_empty()) {
+                    errors.push(FieldError::required(field));
+                }
+            }
+        }
+
+        match self.craft_names() {
+            None if mode == ValidationMode::Create => errors.push(FieldError::required("crafts")),
+            None => {}
+            Some(crafts) => {
+                if crafts.is_empty() {
+                    errors.push(FieldError::required("crafts"));
+                } else if crafts.iter().any(|c| c.trim().is_empty()) {
+                    errors.push(FieldError::invalid_format(
+                        "crafts",
+                        "a list of craft names",
+                    ));
+                } else if crafts.len() > CRAFTS_MAX {
+                    errors.push(FieldError::new(
+                        "crafts",
+                        "too_many",
+                        format!("at most {} crafts are allowed", CRAFTS_MAX),
+                    ));
+                }
+            }
+        }
+
+        if let Some(name) = &self.name {
+            if name.trim().is_empty() {
+                if mode == ValidationMode::Update {
+                    errors.push(FieldError::required("name"));
+                }
+            } else {
+                check_length(&mut errors, "name", name, NAME_MAX);
+            }
+        }
+
+        if let Some(email) = &self.email {
+            if email.trim().is_empty() {
+                if mode == ValidationMode::Update {
+                    errors.push(FieldError::required("email"));
+                }
+            } else {
+                check_length(&mut errors, "email", email, EMAIL_MAX);
+                if !is_valid_email(email) {
+                    errors.push(FieldError::invalid_format(
+                        "email",
+                        "a valid e-mail address",
+                    ));
+                }
+            }
+        }
+
+        if let Some(telefon) = non_empty(&self.telefon) {
+            check_length(&mut errors, "telefon", telefon, TELEFON_MAX);
+            if !is_valid_phone(telefon) {
+                errors.push(FieldError::invalid_format(
+                    "telefon",
+                    "a valid phone number",
+                ));
+            }
+        }
+
+        if let Some(experience) = self.experience {
+            if !(0..=EXPERIENCE_MAX).contains(&experience) {
+                errors.push(FieldError::out_of_range("experience", 0, EXPERIENCE_MAX));
+            }
+        }
+
+        if let Some(location) = &self.location {
+            check_length(&mut errors, "location", location, LOCATION_MAX);
+        }
+
+        if let Some(rechtsform) = &self.rechtsform {
+            if rechtsform.trim().is_empty() && mode == ValidationMode::Update {
+                errors.push(FieldError::required("rechtsform"));
+            }
+        }
+
+        if let Some(lat) = self.lat {
+            if !lat.is_finite() || !(-90.0..=90.0).contains(&lat) {
+                errors.push(FieldError::out_of_range("lat", -90, 90));
+            }
+        }
+
+        if let Some(lng) = self.lng {
+            if !lng.is_finite() || !(-180.0..=180.0).contains(&lng) {
+                errors.push(FieldError::out_of_range("lng", -180, 180));
+            }
+        }
+
+        if let Some(radius) = self.service_radius_km {
+            if !(1..=SERVICE_RADIUS_KM_MAX).contains(&radius) {
+                errors.push(FieldError::out_of_range(
+                    "service_radius_km",
+                    1,
+                    SERVICE_RADIUS_KM_MAX,
+                ));
+            }
+        }
+
+        if let Some(plz) = &self.service_plz {
+            if plz.iter().any(|p| !is_valid_plz_prefix(p)) {
+                errors.push(FieldError::invalid_format(
+                    "service_plz",
+                    "postcodes or postcode prefixes of 1 to 5 digits",
+                ));
+            }
+            if plz.len() > SERVICE_AREAS_MAX {
+                errors.push(FieldError::new(
+                    "service_plz",
+                    "too_many",
+                    format!("at most {} postcodes are allowed", SERVICE_AREAS_MAX),
+                ));
+            }
+        }
+
+        if let Some(districts) = &self.service_districts {
+            if districts.iter().any(|d| d.is_empty()) {
+                errors.push(FieldError::invalid_format(
+                    "service_districts",
+                    "a list of district names",
+                ));
+            }
+            if districts.iter().any(|d| d.chars().count() > DISTRICT_MAX) {
+                errors.push(FieldError::too_long("service_districts", DISTRICT_MAX));
+            }
+            if districts.len() > SERVICE_AREAS_MAX {
+                errors.push(FieldError::new(
+                    "service_districts",
+                    "too_many",
+                    format!("at most {} districts are allowed", SERVICE_AREAS_MAX),
+                ));
+            }
+        }
+
+        if let Some(website) = non_empty(&self.website) {
+            check_length(&mut errors, "website", website, WEBSITE_MAX);
+            if !is_valid_url(website) {
+                errors.push(FieldError::invalid_format("website", "an http(s) URL"));
+            }
+        }
+
+        if let Some(instagram) = non_empty(&self.instagram) {
+            check_length(&mut errors, "instagram", instagram, INSTAGRAM_MAX);
+            if !is_valid_url(instagram) && !is_valid_instagram_handle(instagram) {
+                errors.push(FieldError::invalid_format(
+                    "instagram",
+                    "an http(s) URL or an Instagram handle",
+                ));
+            }
+        }
+
+        if let Some(bio) = &self.bio {
+            check_length(&mut errors, "bio", bio, BIO_MAX);
+        }
+
+        if let Some(nummer) = non_empty(&self.handwerks_karten_nummer) {
+            check_length(
+                &mut errors,
+                "handwerks_karten_nummer",
+                nummer,
+                HANDWERKS_KARTEN_NUMMER_MAX,
+            );
+            if !is_valid_handwerks_karten_nummer(nummer) {
+                errors.push(FieldError::invalid_format(
+                    "handwerks_karten_nummer",
+                    "a number of digits and letters, optionally separated by spaces, '/', '-' or '.'",
+                ));
+            }
+        }
+
+        if let Some(skills) = &self.skills {
+            if skills.iter().any(|s| s.trim().is_empty()) {
+                errors.push(FieldError::invalid_format(
+                    "skills",
+                    "a list of skill names",
+                ));
+            }
+        }
+
+        errors
+    }
+}
+
+impl ProfilePatch {
+    /// Reads a JSON merge-patch body: absent fields stay unchanged, `null`
+    /// clears a field and any other value replaces it.
+    pub fn from_json(body: serde_json::Value) -> (ProfilePatch, Vec<FieldError>) {
+        let serde_json::Value::Object(mut map) = body else {
+            return (
+                ProfilePatch::default(),
+                vec![FieldError::invalid_format("body", "a JSON object")],
+            );
+        };
+
+        let mut patch = ProfilePatch::default();
+        let mut errors = Vec::new();
+
+        match map.remove("skills") {
+            None => {}
+            Some(serde_json::Value::Null) => patch.skills = Some(SkillsPatch::Replace(Vec::new())),
+            Some(value) => match serde_json::from_value::<SkillsPatch>(value) {
+                Ok(skills) => patch.skills = Some(skills),
+                Err(_) => errors.push(FieldError::invalid_format(
+                    "skills",
+                    "a list of skill names or an object with add/remove lists",
+                )),
+            },
+        }
+
+        if map.contains_key("deleted_photos") {
+            errors.push(FieldError::unknown_field("deleted_photos"));
+            map.remove("deleted_photos");
+        }
+
+        for (name, value) in map.iter() {
+            if value.is_null() {
+                if CLEARABLE_FIELDS.contains(&name.as_str()) {
+                    patch.clear.push(name.clone());
+                } else {
+                    errors.push(FieldError::new(
+                        name,
+                        "not_nullable",
+                        format!("{} cannot be cleared", name),
+                    ));
+                }
+            }
+        }
+
+        let (set, set_errors) = ProfileInput::from_json(serde_json::Value::Object(map));
+        patch.set = set;
+        errors.extend(set_errors);
+
+        (patch, errors)
+    }
+
+    pub fn validate(&self) -> Vec<FieldError> {
+        let mut errors = self.set.validate(ValidationMode::Update);
+
+        let names: Vec<&String> = match &self.skills {
+            Some(SkillsPatch::Replace(names)) => names.iter().collect(),
+            Some(SkillsPatch::Modify { add, remove }) => add.iter().chain(remove).collect(),
+            None => Vec::new(),
+        };
+        if names.iter().any(|s| s.trim().is_empty()) {
+            errors.push(FieldError::invalid_format(
+                "skills",
+                "a list of skill names",
+            ));
+        }
+
+        errors
+    }
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().filter(|v| !v.trim().is_empty())
+}
+
+fn check_length(errors: &mut Vec<FieldError>, field: &str, value: &str, max: usize) {
+    // VARCHAR(n) counts characters, not bytes
+    if value.chars().count() > max {
+        errors.push(FieldError::too_long(field, max));
+    }
+}
+
+impl AvailabilitySchema {
+    pub fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+
+        if AvailabilityStatus::parse(&self.status).is_none() {
+            errors.push(FieldError::invalid_format(
+                "status",
+                "one of available, limited, booked, paused",
+            ));
+        }
+
+        if self.opening_hours.len() > OPENING_HOURS_MAX {
+            errors.push(FieldError::new(
+                "opening_hours",
+                "too_many",
+                format!("at most {} opening hours are allowed", OPENING_HOURS_MAX),
+            ));
+        }
+        for (i, hours) in self.opening_hours.iter().enumerate() {
+            if !(1..=7).contains(&hours.weekday) {
+                errors.push(FieldError::out_of_range(
+                    &format!("opening_hours[{}].weekday", i),
+                    1,
+                    7,
+                ));
+            }
+            if hours.closes <= hours.opens {
+                errors.push(FieldError::invalid_format(
+                    &format!("opening_hours[{}]", i),
+                    "a time range that closes after it opens",
+                ));
+            }
+            let overlaps = self.opening_hours[..i].iter().any(|other| {
+                other.weekday == hours.weekday
+                    && other.opens < hours.closes
+                    && hours.opens < other.closes
+            });
+            if overlaps {
+                errors.push(FieldError::new(
+                    &format!("opening_hours[{}]", i),
+                    "overlapping",
+                    "opening hours on the same day must not overlap",
+                ));
+            }
+        }
+
+        if self.closures.len() > CLOSURES_MAX {
+            errors.push(FieldError::new(
+                "closures",
+                "too_many",
+                format!("at most {} closures are allowed", CLOSURES_MAX),
+            ));
+        }
+        for (i, closure) in self.closures.iter().enumerate() {
+            if closure.ends_on < closure.starts_on {
+                errors.push(FieldError::invalid_format(
+                    &format!("closures[{}]", i),
+                    "a date range that ends on or after its start",
+                ));
+            }
+            let overlaps = self.closures[..i].iter().any(|other| {
+                other.starts_on <= closure.ends_on && closure.starts_on <= other.ends_on
+            });
+            if overlaps {
+                errors.push(FieldError::new(
+                    &format!("closures[{}]", i),
+                    "overlapping",
+                    "closures must not overlap",
+                ));
+            }
+            if let Some(note) = &closure.note {
+                check_length(
+                    &mut errors,
+                    &format!("closures[{}].note", i),
+                    note,
+                    CLOSURE_NOTE_MAX,
+                );
+            }
+        }
+
+        errors
+    }
+}
+
+impl ServiceCatalogueSchema {
+    pub fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+
+        if self.services.len() > SERVICES_MAX {
+            errors.push(FieldError::new(
+                "services",
+                "too_many",
+                format!("at most {} services are allowed", SERVICES_MAX),
+            ));
+        }
+
+        for (i, service) in self.services.iter().enumerate() {
+            if let Some(name) = &service.name {
+                if name.trim().is_empty() {
+                    errors.push(FieldError::required(&format!("services[{}].name", i)));
+                }
+                check_length(
+                    &mut errors,
+                    &format!("services[{}].name", i),
+                    name.trim(),
+                    SERVICE_NAME_MAX,
+                );
+            }
+            if service.skill.trim().is_empty() {
+                errors.push(FieldError::required(&format!("services[{}].skill", i)));
+            }
+
+            let price_model = PriceModel::parse(&service.price_model);
+            if price_model.is_none() {
+                errors.push(FieldError::invalid_format(
+                    &format!("services[{}].price_model", i),
+                    "one of hourly, fixed, on_request",
+                ));
+            }
+
+            let has_price = service.price_min_cents.is_some() || service.price_max_cents.is_some();
+            if price_model == Some(PriceModel::OnRequest) && has_price {
+                errors.push(FieldError::new(
+                    &format!("services[{}]", i),
+                    "price_on_request",
+                    "services priced on request cannot have a price",
+                ));
+            }
+            for (field, price) in [
+                ("price_min_cents", service.price_min_cents),
+                ("price_max_cents", service.price_max_cents),
+            ] {
+                if price.is_some_and(|price| price < 0) {
+                    errors.push(FieldError::out_of_range(
+                        &format!("services[{}].{}", i, field),
+                        0,
+                        i32::MAX,
+                    ));
+                }
+            }
+            if let (Some(min), Some(max)) = (service.price_min_cents, service.price_max_cents) {
+                if max < min {
+                    errors.push(FieldError::invalid_format(
+                        &format!("services[{}]", i),
+                        "a price range whose maximum is not below its minimum",
+                    ));
+                }
+            }
+        }
+
+        errors
+    }
+}
+
+impl InquiryInput {
+    pub fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+
+        for (field, value) in [
+            ("message", &self.message),
+            ("location", &self.location),
+            ("timeframe", &self.timeframe),
+        ] {
+            if value.as_deref().is_none_or(|v| v.trim().is_empty()) {
+                errors.push(FieldError::required(field));
+            }
+        }
+
+        if let Some(message) = &self.message {
+            check_length(&mut errors, "message", message.trim(), INQUIRY_MESSAGE_MAX);
+        }
+        if let Some(location) = &self.location {
+            check_length(&mut errors, "location", location.trim(), LOCATION_MAX);
+        }
+        if let Some(timeframe) = non_empty(&self.timeframe) {
+            if !INQUIRY_TIMEFRAMES.contains(&timeframe.trim()) {
+                errors.push(FieldError::invalid_format(
+                    "timeframe",
+                    "one of asap, weeks, months, flexible",
+                ));
+            }
+        }
+
+        errors
+    }
+}
+
+impl ReviewInput {
+    pub fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+
+        if !(REVIEW_RATING_MIN..=REVIEW_RATING_MAX).contains(&self.rating) {
+            errors.push(FieldError::out_of_range(
+                "rating",
+                REVIEW_RATING_MIN,
+                REVIEW_RATING_MAX,
+            ));
+        }
+        if self.body.trim().is_empty() {
+            errors.push(FieldError::required("body"));
+        }
+        check_length(&mut errors, "body", self.body.trim(), REVIEW_BODY_MAX);
+
+        errors
+    }
+}
+
+impl ReviewReplySchema {
+    pub fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+
+        if self.reply.trim().is_empty() {
+            errors.push(FieldError::required("reply"));
+        }
+        check_length(&mut errors, "reply", self.reply.trim(), REVIEW_BODY_MAX);
+
+        errors
+    }
+}
+
+impl ReviewReportSchema {
+    pub fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+
+        if !REVIEW_REPORT_REASONS.contains(&self.reason.trim()) {
+            errors.push(FieldError::invalid_format(
+                "reason",
+                "one of spam, offensive, fake, personal_data, conflict_of_interest, other",
+            ));
+        }
+        if let Some(comment) = &self.comment {
+            check_length(
+                &mut errors,
+                "comment",
+                comment.trim(),
+                REVIEW_REPORT_COMMENT_MAX,
+            );
+        }
+        if self.reason.trim() == "other" && non_empty(&self.comment).is_none() {
+            errors.push(FieldError::required("comment"));
+        }
+
+        errors
+    }
+}
+
+impl ProfileReportSchema {
+    pub fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+
+        if !PROFILE_REPORT_CATEGORIES.contains(&self.category.trim()) {
+            errors.push(FieldError::invalid_format(
+                "category",
+                "one of fake, offensive, spam, impersonation, wrong_information, other",
+            ));
+        }
+        if let Some(comment) = &self.comment {
+            check_length(
+                &mut errors,
+                "comment",
+                comment.trim(),
+                PROFILE_REPORT_COMMENT_MAX,
+            );
+        }
+        if self.category.trim() == "other" && non_empty(&self.comment).is_none() {
+            errors.push(FieldError::required("comment"));
+        }
+
+        errors
+    }
+}
+
+impl PhotoOrderSchema {
+    pub fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        let mut seen = HashSet::new();
+        for (i, photo_id) in self.photo_ids.iter().enumerate() {
+            if !seen.insert(photo_id) {
+                errors.push(FieldError::new(
+                    &format!("photo_ids[{}]", i),
+                    "duplicate",
+                    format!("photo {} is listed twice", photo_id),
+                ));
+            }
+        }
+        errors
+    }
+}
+
+impl PhotoDetailsSchema {
+    pub fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if let Some(caption) = &self.caption {
+            check_length(&mut errors, "caption", caption.trim(), PHOTO_CAPTION_MAX);
+        }
+        if let Some(alt_text) = &self.alt_text {
+            check_length(&mut errors, "alt_text", alt_text.trim(), PHOTO_ALT_TEXT_MAX);
+        }
+        errors
+    }
+}
+
+impl ProjectsSchema {
+    pub fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+
+        if self.projects.len() > PROJECTS_MAX {
+            errors.push(FieldError::new(
+                "projects",
+                "too_many",
+                format!("at most {} projects are allowed", PROJECTS_MAX),
+            ));
+        }
+
+        let mut project_ids = HashSet::new();
+        let mut photo_ids = HashSet::new();
+        for (i, project) in self.projects.iter().enumerate() {
+            if project.id.is_some_and(|id| !project_ids.insert(id)) {
+                errors.push(FieldError::new(
+                    &format!("projects[{}].id", i),
+                    "duplicate",
+                    "the project is listed twice",
+                ));
+            }
+            if project.title.trim().is_empty() {
+                errors.push(FieldError::required(&format!("projects[{}].title", i)));
+            }
+            check_length(
+                &mut errors,
+                &format!("projects[{}].title", i),
+                project.title.trim(),
+                PROJECT_TITLE_MAX,
+            );
+            if let Some(description) = &project.description {
+                check_length(
+                    &mut errors,
+                    &format!("projects[{}].description", i),
+                    description.trim(),
+                    PROJECT_DESCRIPTION_MAX,
+                );
+            }
+            // A photo belongs to one project at most
+            for (j, photo_id) in project.photo_ids.iter().enumerate() {
+                if !photo_ids.insert(photo_id) {
+                    errors.push(FieldError::new(
+                        &format!("projects[{}].photo_ids[{}]", i, j),
+                        "duplicate",
+                        format!("photo {} is listed twice", photo_id),
+                    ));
+                }
+            }
+        }
+
+        errors
+    }
+}
+
+// Whether the pattern compiles and matches the example is checked by Postgres
+impl HandwerkskammerSchema {
+    pub fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+
+        for (field, value, max) in [
+            ("name", &self.name, HANDWERKSKAMMER_NAME_MAX),
+            ("number_pattern", &self.number_pattern, NUMBER_PATTERN_MAX),
+            ("example", &self.example, HANDWERKS_KARTEN_NUMMER_MAX),
+        ] {
+            if value.trim().is_empty() {
+                errors.push(FieldError::required(field));
+            }
+            check_length(&mut errors, field, value.trim(), max);
+        }
+
+        errors
+    }
+}
+
+impl RatingSourceSchema {
+    pub fn validate(&self, sources: &[&str]) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+
+        if !sources.contains(&self.source.trim()) {
+            errors.push(FieldError::invalid_format(
+                "source",
+                &format!("one of {}", sources.join(", ")),
+            ));
+        }
+        if let Some(external_id) = &self.external_id {
+            check_length(
+                &mut errors,
+                "external_id",
+                external_id.trim(),
+                EXTERNAL_ID_MAX,
+            );
+        }
+
+        errors
+    }
+}
+
+// URLs, "www." hosts and bare domains like "example.de"
+pub fn contains_link(text: &str) -> bool {
+    const TLDS: [&str; 8] = ["de", "com", "net", "org", "info", "eu", "io", "at"];
+
+    let text = text.to_lowercase();
+    if text.contains("http://") || text.contains("https://") || text.contains("www.") {
+        return true;
+    }
+
+    text.split(|c: char| c.is_whitespace() || matches!(c, ',' | ';' | '(' | ')' | '"'))
+        .map(|word| word.trim_end_matches(['.', '!', '?', ':']))
+        .any(|word| {
+            word.rsplit_once('.').is_some_and(|(host, tld)| {
+                !host.is_empty()
+                    && host
+                        .chars()
+                        .all(|c| c.is_alphanumeric() || matches!(c, '.' | '-'))
+                    && TLDS.contains(&tld)
+            })
+        })
+}
+
+// Whole-word, case-insensitive match against the configured blocked words
+pub fn contains_blocked_word(text: &str, blocked_words: &[String]) -> bool {
+    let text = text.to_lowercase();
+    text.split(|c: char| !c.is_alphanumeric())
+        .any(|word| blocked_words.iter().any(|blocked| blocked == word))
+}
+
+pub fn is_valid_email(email: &str) -> bool {
+    let Some((local, domain)) = email.split_once('@') else {
+        return false;
+    };
+
+    !local.is_empty()
+        && !domain.contains('@')
+        && !email.chars().any(char::is_whitespace)
+        && domain.contains('.')
+        && !domain.starts_with('.')
+        && !domain.ends_with('.')
+        && !domain.contains("..")
+}
+
+// German postcodes have five digits; shorter values cover every postcode
+// starting with them
+pub fn is_valid_plz_prefix(plz: &str) -> bool {
+    (1..=5).contains(&plz.len()) && plz.chars().all(|c| c.is_ascii_digit())
+}
+
+pub fn is_valid_phone(telefon: &str) -> bool {
+    let trimmed = telefon.trim();
+    let body = trimmed.strip_prefix('+').unwrap_or(trimmed);
+    let digits = body.chars().filter(char::is_ascii_digit).count();
+
+    body.chars()
+        .all(|c| c.is_ascii_digit() || matches!(c, ' ' | '-' | '/' | '(' | ')'))
+        && (5..=20).contains(&digits)
+}
+
+pub fn is_valid_url(url: &str) -> bool {
+    let rest = match url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+    {
+        Some(rest) => rest,
+        None => return false,
+    };
+
+    let host = rest.split(['/', '?', '#']).next().unwrap_or("");
+    let host = host.rsplit_once(':').map_or(host, |(h, _)| h);
+
+    !url.chars().any(char::is_whitespace)
+        && host.contains('.')
+        && !host.starts_with('.')
+        && !host.ends_with('.')
+        && host
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '.' || c == '-')
+}
+
+// Chambers format their numbers differently; the exact pattern is checked
+// against the chamber when the Handwerkskarte is submitted for verification
+pub fn is_valid_handwerks_karten_nummer(nummer: &str) -> bool {
+    let nummer = nummer.trim();
+
+    nummer.chars().any(|c| c.is_ascii_digit())
+        && nummer.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && nummer.ends_with(|c: char| c.is_ascii_alphanumeric())
+        && nummer
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, ' ' | '/' | '-' | '.'))
+}
+
+pub fn is_valid_instagram_handle(handle: &str) -> bool {
+    let handle = handle.strip_prefix('@').unwrap_or(handle);
+
+    (1..=30).contains(&handle.len())
+        && handle
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use serde_json::json;
+
+    use super::*;
+
+    fn valid_profile() -> serde_json::Value {
+        json!({
+            "name": "Tischlerei Muster",
+            "email": "info@tischlerei-muster.de",
+            "location": "Berlin",
+            "rechtsform": "Einzelunternehmen",
+            "crafts": ["Tischler"],
+        })
+    }
+
+    fn with(field: &str, value: serde_json::Value) -> serde_json::Value {
+        let mut body = valid_profile();
+        body[field] = value;
+        body
+    }
+
+    fn create_errors(body: serde_json::Value) -> Vec<FieldError> {
+        let (input, mut errors) = ProfileInput::from_json(body);
+        errors.extend(input.validate(ValidationMode::Create));
+        errors
+    }
+
+    fn update_errors(body: serde_json::Value) -> Vec<FieldError> {
+        let (input, mut errors) = ProfileInput::from_json(body);
+        errors.extend(input.validate(ValidationMode::Update));
+        errors
+    }
+
+    fn codes(errors: &[FieldError]) -> Vec<(&str, &str)> {
+        errors.iter().map(|e| (e.field.as_str(), e.code)).collect()
+    }
+
+    #[test]
+    fn valid_profile_passes() {
+        assert_eq!(create_errors(valid_profile()), vec![]);
+    }
+
+    #[test]
+    fn create_requires_fields() {
+        assert_eq!(
+            codes(&create_errors(json!({}))),
+            vec![
+                ("name", "required"),
+                ("email", "required"),
+                ("location", "required"),
+                ("rechtsform", "required"),
+                ("crafts", "required"),
+            ]
+        );
+        assert_eq!(
+            codes(&create_errors(with("name", json!("  ")))),
+            vec![("name", "required")]
+        );
+        assert_eq!(
+            codes(&create_errors(with("rechtsform", json!("")))),
+            vec![("rechtsform", "required")]
+        );
+        assert_eq!(
+            codes(&create_errors(with("crafts", json!([])))),
+            vec![("crafts", "required")]
+        );
+    }
+
+    #[test]
+    fn update_only_checks_sent_fields() {
+        assert_eq!(update_errors(json!({})), vec![]);
+        assert_eq!(
+            codes(&update_errors(json!({ "rechtsform": " " }))),
+            vec![("rechtsform", "required")]
+        );
+        assert_eq!(update_errors(json!({ "bio": "Neu" })), vec![]);
+        assert_eq!(
+            codes(&update_errors(json!({ "name": "", "email": " " }))),
+            vec![("name", "required"), ("email", "required")]
+        );
+    }
+
+    #[test]
+    fn primary_craft_counts_as_craft() {
+        let mut body = valid_profile();
+        body.as_object_mut().unwrap().remove("crafts");
+        body["craft"] = json!("Maler");
+        assert_eq!(create_errors(body), vec![]);
+    }
+
+    #[test]
+    fn too_many_crafts() {
+        let crafts: Vec<String> = (0..=CRAFTS_MAX).map(|i| format!("Craft {}", i)).collect();
+        assert_eq!(
+            codes(&create_errors(with("crafts", json!(crafts)))),
+            vec![("crafts", "too_many")]
+        );
+    }
+
+    #[test]
+    fn too_long_at_each_varchar_limit() {
+        let email_at = |len: usize| format!("{}@example.de", "a".repeat(len - 11));
+        let website_at = |len: usize| format!("https://{}.de", "a".repeat(len - 11));
+        // Field, its limit, and a valid value of a given length
+        type Case = (&'static str, usize, fn(usize) -> String);
+        let cases: [Case; 8] = [
+            ("name", NAME_MAX, |len| "n".repeat(len)),
+            ("email", EMAIL_MAX, email_at),
+            ("telefon", TELEFON_MAX, |len| "1".repeat(len)),
+            ("location", LOCATION_MAX, |len| "l".repeat(len)),
+            ("website", WEBSITE_MAX, website_at),
+            ("instagram", INSTAGRAM_MAX, |len| {
+                format!("https://instagram.com/{}", "i".repeat(len - 22))
+            }),
+            // Multi-byte characters count once, as in VARCHAR
+            ("bio", BIO_MAX, |len| "ä".repeat(len)),
+            (
+                "handwerks_karten_nummer",
+                HANDWERKS_KARTEN_NUMMER_MAX,
+                |len| "1".repeat(len),
+            ),
+        ];
+
+        for (field, max, value) in cases {
+            let at_limit = value(max);
+            assert_eq!(at_limit.chars().count(), max);
+            let errors = create_errors(with(field, json!(at_limit)));
+            assert!(
+                !errors.iter().any(|e| e.code == "too_long"),
+                "{} at its limit: {:?}",
+                field,
+                errors
+            );
+
+            let over_limit = value(max + 1);
+            let errors = create_errors(with(field, json!(over_limit)));
+            assert!(
+                errors.contains(&FieldError::too_long(field, max)),
+                "{} over its limit: {:?}",
+                field,
+                errors
+            );
+        }
+    }
+
+    #[test]
+    fn experience_range() {
+        for experience in [0, EXPERIENCE_MAX] {
+            assert_eq!(create_errors(with("experience", json!(experience))), vec![]);
+        }
+        for experience in [-1, EXPERIENCE_MAX + 1] {
+            assert_eq!(
+                create_errors(with("experience", json!(experience))),
+                vec![FieldError::out_of_range("experience", 0, EXPERIENCE_MAX)]
+            );
+        }
+        assert_eq!(
+            codes(&create_errors(with("experience", json!("viele")))),
+            vec![("experience", "invalid_format")]
+        );
+    }
+
+    #[test]
+    fn coordinate_ranges() {
+        for (lat, lng) in [(-90.0, -180.0), (90.0, 180.0), (52.52, 13.405)] {
+            let mut body = valid_profile();
+            body["lat"] = json!(lat);
+            body["lng"] = json!(lng);
+            assert_eq!(create_errors(body), vec![]);
+        }
+
+        let mut body = valid_profile();
+        body["lat"] = json!(90.1);
+        body["lng"] = json!(-180.1);
+        assert_eq!(
+            create_errors(body),
+            vec![
+                FieldError::out_of_range("lat", -90, 90),
+                FieldError::out_of_range("lng", -180, 180),
+            ]
+        );
+
+        let input = ProfileInput {
+            lat: Some(f64::NAN),
+            lng: Some(f64::INFINITY),
+            ..Default::default()
+        };
+        assert_eq!(
+            codes(&input.validate(ValidationMode::Update)),
+            vec![("lat", "out_of_range"), ("lng", "out_of_range")]
+        );
+    }
+
+    #[test]
+    fn unknown_fields_are_reported() {
+        assert_eq!(
+            create_errors(with("nickname", json!("Max"))),
+            vec![FieldError::unknown_field("nickname")]
+        );
+    }
+
+    #[test]
+    fn non_object_body() {
+        let (_, errors) = ProfileInput::from_json(json!(["name"]));
+        assert_eq!(codes(&errors), vec![("body", "invalid_format")]);
+    }
+
+    #[test]
+    fn json_and_multipart_report_the_same_errors() {
+        let body = json!({
+            "name": "n".repeat(NAME_MAX + 1),
+            "email": "keine-adresse",
+            "experience": 1001,
+            "lat": "nördlich",
+            "lng": 200,
+            "crafts": "Tischler",
+            "website": "ftp://example.de",
+            "instagram": "not a handle!",
+            "telefon": "call me",
+            "hobby": "Angeln",
+        });
+        let fields: Vec<(String, String)> = [
+            ("name", "n".repeat(NAME_MAX + 1)),
+            ("email", "keine-adresse".to_string()),
+            ("experience", "1001".to_string()),
+            ("lat", "nördlich".to_string()),
+            ("lng", "200".to_string()),
+            ("crafts", "Tischler".to_string()),
+            ("website", "ftp://example.de".to_string()),
+            ("instagram", "not a handle!".to_string()),
+            ("telefon", "call me".to_string()),
+            ("hobby", "Angeln".to_string()),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value))
+        .collect();
+
+        let (json_input, mut json_errors) = ProfileInput::from_json(body);
+        json_errors.extend(json_input.validate(ValidationMode::Create));
+        let (form_input, mut form_errors) = ProfileInput::from_fields(fields);
+        form_errors.extend(form_input.validate(ValidationMode::Create));
+
+        let mut json_codes = codes(&json_errors);
+        let mut form_codes = codes(&form_errors);
+        json_codes.sort();
+        form_codes.sort();
+        assert_eq!(json_codes, form_codes);
+        for expected in [
+            ("name", "too_long"),
+            ("email", "invalid_format"),
+            ("experience", "out_of_range"),
+            ("lat", "invalid_format"),
+            ("lng", "out_of_range"),
+            ("crafts", "invalid_format"),
+            ("crafts", "required"),
+            ("website", "invalid_format"),
+            ("instagram", "invalid_format"),
+            ("telefon", "invalid_format"),
+            ("hobby", "unknown_field"),
+        ] {
+            assert!(json_codes.contains(&expected), "missing {:?}", expected);
+        }
+    }
+
+    #[test]
+    fn email_format() {
+        for email in [
+            "a@b.de",
+            "info@tischlerei-muster.de",
+            "first.last+tag@mail.example.com",
+        ] {
+            assert!(is_valid_email(email), "{}", email);
+        }
+        for email in [
+            "",
+            "info",
+            "@example.de",
+            "info@",
+            "info@localhost",
+            "info@.example.de",
+            "info@example.de.",
+            "info@example..de",
+            "a@b@example.de",
+            "in fo@example.de",
+        ] {
+            assert!(!is_valid_email(email), "{}", email);
+        }
+    }
+
+    #[test]
+    fn phone_format() {
+        for telefon in ["030 1234567", "+49 30 1234567", "(030) 123-45/67", "12345"] {
+            assert!(is_valid_phone(telefon), "{}", telefon);
+        }
+        for telefon in [
+            "",
+            "1234",
+            "123456789012345678901",
+            "+49 30 CALL-ME",
+            "++49 30 1234567",
+            "030.1234567",
+        ] {
+            assert!(!is_valid_phone(telefon), "{}", telefon);
+        }
+    }
+
+    #[test]
+    fn url_format() {
+        for url in [
+            "https://example.de",
+            "http://www.example.de/",
+            "https://example.de:8080/pfad?x=1#top",
+            "https://münchen.de",
+        ] {
+            assert!(is_valid_url(url), "{}", url);
+        }
+        for url in [
+            "",
+            "example.de",
+            "ftp://example.de",
+            "https://",
+            "https://localhost",
+            "https://.example.de",
+            "https://example.de.",
+            "https://exa mple.de",
+            "https://user@example.de",
+            "javascript:alert(1)",
+        ] {
+            assert!(!is_valid_url(url), "{}", url);
+        }
+    }
+
+    #[test]
+    fn instagram_handle_format() {
+        for handle in [
+            "tischlerei",
+            "@tischlerei_muster",
+            "t.muster_2",
+            &"a".repeat(30),
+        ] {
+            assert!(is_valid_instagram_handle(handle), "{}", handle);
+        }
+        for handle in [
+            "",
+            "@",
+            "tisch lerei",
+            "tischlerei!",
+            "@@tischlerei",
+            &"a".repeat(31),
+        ] {
+            assert!(!is_valid_instagram_handle(handle), "{}", handle);
+        }
+    }
+
+    #[test]
+    fn handwerks_karten_nummer_format() {
+        for nummer in ["1234567", "12345/01", "S123456", "HWK-12.345", " 12 345 "] {
+            assert!(is_valid_handwerks_karten_nummer(nummer), "{}", nummer);
+        }
+        for nummer in [
+            "",
+            "Meister",
+            "12345/",
+            "/12345",
+            "12345 !",
+            "12345\n6",
+            "１２３",
+        ] {
+            assert!(!is_valid_handwerks_karten_nummer(nummer), "{}", nummer);
+        }
+
+        let error = FieldError::invalid_format(
+            "handwerks_karten_nummer",
+            "a number of digits and letters, optionally separated by spaces, '/', '-' or '.'",
+        );
+        assert_eq!(
+            create_errors(with("handwerks_karten_nummer", json!("keine"))),
+            vec![error.clone()]
+        );
+        assert_eq!(
+            update_errors(json!({ "handwerks_karten_nummer": "keine" })),
+            vec![error]
+        );
+        assert_eq!(
+            update_errors(json!({ "handwerks_karten_nummer": "" })),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn handwerkskammer_fields() {
+        let kammer = |name: &str, number_pattern: &str, example: &str| HandwerkskammerSchema {
+            name: name.to_string(),
+            number_pattern: number_pattern.to_string(),
+            example: example.to_string(),
+        };
+
+        assert_eq!(
+            kammer("Handwerkskammer Test", "^[0-9]{5}$", "12345").validate(),
+            vec![]
+        );
+        assert_eq!(
+            codes(&kammer(" ", "", "").validate()),
+            vec![
+                ("name", "required"),
+                ("number_pattern", "required"),
+                ("example", "required")
+            ]
+        );
+        assert_eq!(
+            kammer(
+                &"k".repeat(HANDWERKSKAMMER_NAME_MAX + 1),
+                &"1".repeat(NUMBER_PATTERN_MAX + 1),
+                &"1".repeat(HANDWERKS_KARTEN_NUMMER_MAX + 1)
+            )
+            .validate(),
+            vec![
+                FieldError::too_long("name", HANDWERKSKAMMER_NAME_MAX),
+                FieldError::too_long("number_pattern", NUMBER_PATTERN_MAX),
+                FieldError::too_long("example", HANDWERKS_KARTEN_NUMMER_MAX)
+            ]
+        );
+    }
+
+    #[test]
+    fn service_areas() {
+        assert_eq!(
+            update_errors(json!({
+                "service_radius_km": SERVICE_RADIUS_KM_MAX,
+                "service_plz": ["1", "10115"],
+                "service_districts": ["Mitte"]
+            })),
+            vec![]
+        );
+        assert_eq!(
+            update_errors(json!({ "service_plz": [], "service_districts": [] })),
+            vec![]
+        );
+        for radius in [0, SERVICE_RADIUS_KM_MAX + 1] {
+            assert_eq!(
+                codes(&update_errors(json!({ "service_radius_km": radius }))),
+                vec![("service_radius_km", "out_of_range")]
+            );
+        }
+        for plz in ["", "101150", "1O115", "10 115"] {
+            assert_eq!(
+                codes(&update_errors(json!({ "service_plz": [plz] }))),
+                vec![("service_plz", "invalid_format")],
+                "{}",
+                plz
+            );
+        }
+        assert_eq!(
+            codes(&update_errors(json!({
+                "service_districts": ["", "d".repeat(DISTRICT_MAX + 1)]
+            }))),
+            vec![
+                ("service_districts", "invalid_format"),
+                ("service_districts", "too_long")
+            ]
+        );
+
+        let plz: Vec<String> = (0..=SERVICE_AREAS_MAX)
+            .map(|i| format!("{:05}", i))
+            .collect();
+        assert_eq!(
+            codes(&update_errors(json!({
+                "service_plz": plz,
+                "service_districts": plz
+            }))),
+            vec![
+                ("service_plz", "too_many"),
+                ("service_districts", "too_many")
+            ]
+        );
+    }
+
+    fn parse<T: serde::de::DeserializeOwned>(body: serde_json::Value) -> T {
+        serde_json::from_value(body).unwrap()
+    }
+
+    #[test]
+    fn availability_hours_and_closures() {
+        let availability = |opening_hours: serde_json::Value, closures: serde_json::Value| {
+            parse::<AvailabilitySchema>(json!({
+                "status": "available",
+                "opening_hours": opening_hours,
+                "closures": closures
+            }))
+            .validate()
+        };
+
+        assert_eq!(
+            availability(
+                json!([
+                    { "weekday": 1, "opens": "08:00", "closes": "12:00" },
+                    { "weekday": 1, "opens": "12:00", "closes": "17:00" },
+                    { "weekday": 7, "opens": "10:00", "closes": "14:00" }
+                ]),
+                json!([{ "starts_on": "2025-08-01", "ends_on": "2025-08-01", "note": null }])
+            ),
+            vec![]
+        );
+        assert_eq!(
+            codes(&parse::<AvailabilitySchema>(json!({ "status": "vacation" })).validate()),
+            vec![("status", "invalid_format")]
+        );
+        assert_eq!(
+            codes(&availability(
+                json!([
+                    { "weekday": 0, "opens": "08:00", "closes": "12:00" },
+                    { "weekday": 8, "opens": "08:00", "closes": "12:00" }
+                ]),
+                json!([])
+            )),
+            vec![
+                ("opening_hours[0].weekday", "out_of_range"),
+                ("opening_hours[1].weekday", "out_of_range")
+            ]
+        );
+        // closes <= opens
+        assert_eq!(
+            codes(&availability(
+                json!([
+                    { "weekday": 2, "opens": "12:00", "closes": "12:00" },
+                    { "weekday": 3, "opens": "17:00", "closes": "08:00" }
+                ]),
+                json!([])
+            )),
+            vec![
+                ("opening_hours[0]", "invalid_format"),
+                ("opening_hours[1]", "invalid_format")
+            ]
+        );
+        assert_eq!(
+            codes(&availability(
+                json!([
+                    { "weekday": 4, "opens": "08:00", "closes": "12:00" },
+                    { "weekday": 4, "opens": "11:59", "closes": "13:00" },
+                    { "weekday": 5, "opens": "11:59", "closes": "13:00" }
+                ]),
+                json!([])
+            )),
+            vec![("opening_hours[1]", "overlapping")]
+        );
+        assert_eq!(
+            codes(&availability(
+                json!([]),
+                json!([
+                    { "starts_on": "2025-08-02", "ends_on": "2025-08-01", "note": null },
+                    { "starts_on": "2025-09-01", "ends_on": "2025-09-30", "note": "n".repeat(CLOSURE_NOTE_MAX + 1) }
+                ])
+            )),
+            vec![
+                ("closures[0]", "invalid_format"),
+                ("closures[1].note", "too_long")
+            ]
+        );
+        // Both days are inclusive
+        assert_eq!(
+            codes(&availability(
+                json!([]),
+                json!([
+                    { "starts_on": "2025-08-01", "ends_on": "2025-08-15", "note": null },
+                    { "starts_on": "2025-08-16", "ends_on": "2025-08-31", "note": null },
+                    { "starts_on": "2025-08-31", "ends_on": "2025-09-05", "note": null },
+                    { "starts_on": "2025-08-10", "ends_on": "2025-08-10", "note": null }
+                ])
+            )),
+            vec![
+                ("closures[2]", "overlapping"),
+                ("closures[3]", "overlapping")
+            ]
+        );
+
+        let hours: Vec<serde_json::Value> = (0..=OPENING_HOURS_MAX)
+            .map(|i| json!({ "weekday": 1, "opens": format!("{:02}:00", i), "closes": format!("{:02}:30", i) }))
+            .collect();
+        let first_day = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+        let closures: Vec<serde_json::Value> = (0..=CLOSURES_MAX as u64)
+            .map(|i| {
+                let day = first_day + chrono::Days::new(i);
+                json!({ "starts_on": day, "ends_on": day, "note": null })
+            })
+            .collect();
+        assert_eq!(
+            codes(&availability(json!(hours), json!(closures))),
+            vec![("opening_hours", "too_many"), ("closures", "too_many")]
+        );
+    }
+
+    #[test]
+    fn service_catalogue() {
+        let services = |services: serde_json::Value| {
+            parse::<ServiceCatalogueSchema>(json!({ "services": services })).validate()
+        };
+
+        assert_eq!(
+            services(json!([
+                { "skill": "Parkett", "price_model": "hourly", "price_min_cents": 4500, "price_max_cents": 4500 },
+                { "name": "Küche nach Maß", "skill": "Möbelbau", "price_model": "fixed", "price_min_cents": 0 },
+                { "skill": "Reparatur", "price_model": "on_request" }
+            ])),
+            vec![]
+        );
+        assert_eq!(
+            codes(&services(json!([
+                { "name": " ", "skill": "", "price_model": "daily" },
+                { "name": "n".repeat(SERVICE_NAME_MAX + 1), "skill": "Parkett", "price_model": "hourly" }
+            ]))),
+            vec![
+                ("services[0].name", "required"),
+                ("services[0].skill", "required"),
+                ("services[0].price_model", "invalid_format"),
+                ("services[1].name", "too_long")
+            ]
+        );
+        // price_min > price_max
+        assert_eq!(
+            codes(&services(json!([
+                { "skill": "Parkett", "price_model": "fixed", "price_min_cents": 5001, "price_max_cents": 5000 }
+            ]))),
+            vec![("services[0]", "invalid_format")]
+        );
+        assert_eq!(
+            codes(&services(json!([
+                { "skill": "Parkett", "price_model": "hourly", "price_min_cents": -1, "price_max_cents": -1 }
+            ]))),
+            vec![
+                ("services[0].price_min_cents", "out_of_range"),
+                ("services[0].price_max_cents", "out_of_range")
+            ]
+        );
+        assert_eq!(
+            codes(&services(json!([
+                { "skill": "Parkett", "price_model": "on_request", "price_max_cents": 5000 }
+            ]))),
+            vec![("services[0]", "price_on_request")]
+        );
+
+        let too_many: Vec<serde_json::Value> = (0..=SERVICES_MAX)
+            .map(|_| json!({ "skill": "Parkett", "price_model": "on_request" }))
+            .collect();
+        assert_eq!(
+            codes(&services(json!(too_many))),
+            vec![("services", "too_many")]
+        );
+    }
+
+    #[test]
+    fn inquiry_fields() {
+        let inquiry = |message: &str, location: &str, timeframe: &str| {
+            InquiryInput {
+                message: Some(message.to_string()),
+                craft: None,
+                location: Some(location.to_string()),
+                timeframe: Some(timeframe.to_string()),
+            }
+            .validate()
+        };
+
+        for timeframe in INQUIRY_TIMEFRAMES {
+            assert_eq!(inquiry("Neue Küche", "Berlin", timeframe), vec![]);
+        }
+        assert_eq!(
+            codes(&InquiryInput::default().validate()),
+            vec![
+                ("message", "required"),
+                ("location", "required"),
+                ("timeframe", "required")
+            ]
+        );
+        assert_eq!(
+            codes(&inquiry(" ", "", " ")),
+            vec![
+                ("message", "required"),
+                ("location", "required"),
+                ("timeframe", "required")
+            ]
+        );
+        assert_eq!(
+            codes(&inquiry("Neue Küche", "Berlin", "tomorrow")),
+            vec![("timeframe", "invalid_format")]
+        );
+        assert_eq!(
+            inquiry(
+                &format!(" {} ", "m".repeat(INQUIRY_MESSAGE_MAX)),
+                &"l".repeat(LOCATION_MAX + 1),
+                "asap"
+            ),
+            vec![FieldError::too_long("location", LOCATION_MAX)]
+        );
+        assert_eq!(
+            inquiry(&"m".repeat(INQUIRY_MESSAGE_MAX + 1), "Berlin", "asap"),
+            vec![FieldError::too_long("message", INQUIRY_MESSAGE_MAX)]
+        );
+    }
+
+    #[test]
+    fn review_rating_and_body() {
+        let review = |rating: i16, body: &str| {
+            ReviewInput {
+                rating,
+                body: body.to_string(),
+                inquiry_id: None,
+            }
+            .validate()
+        };
+
+        for rating in REVIEW_RATING_MIN..=REVIEW_RATING_MAX {
+            assert_eq!(review(rating, "Pünktlich und sauber"), vec![]);
+        }
+        for rating in [REVIEW_RATING_MIN - 1, REVIEW_RATING_MAX + 1, -1] {
+            assert_eq!(
+                codes(&review(rating, "Pünktlich und sauber")),
+                vec![("rating", "out_of_range")]
+            );
+        }
+        assert_eq!(codes(&review(5, "  ")), vec![("body", "required")]);
+        assert_eq!(review(5, &"b".repeat(REVIEW_BODY_MAX)), vec![]);
+        assert_eq!(
+            review(5, &"b".repeat(REVIEW_BODY_MAX + 1)),
+            vec![FieldError::too_long("body", REVIEW_BODY_MAX)]
+        );
+
+        let reply = |reply: &str| {
+            ReviewReplySchema {
+                reply: reply.to_string(),
+            }
+            .validate()
+        };
+        assert_eq!(reply("Vielen Dank!"), vec![]);
+        assert_eq!(codes(&reply("")), vec![("reply", "required")]);
+        assert_eq!(
+            reply(&"r".repeat(REVIEW_BODY_MAX + 1)),
+            vec![FieldError::too_long("reply", REVIEW_BODY_MAX)]
+        );
+    }
+
+    #[test]
+    fn review_report_reasons() {
+        let report = |reason: &str, comment: Option<&str>| {
+            ReviewReportSchema {
+                reason: reason.to_string(),
+                comment: comment.map(str::to_string),
+            }
+            .validate()
+        };
+
+        for reason in REVIEW_REPORT_REASONS {
+            assert_eq!(report(reason, Some("Siehe Bewertung")), vec![]);
+        }
+        assert_eq!(report("spam", None), vec![]);
+        assert_eq!(
+            codes(&report("boring", None)),
+            vec![("reason", "invalid_format")]
+        );
+        assert_eq!(codes(&report("other", None)), vec![("comment", "required")]);
+        assert_eq!(
+            codes(&report("other", Some(" "))),
+            vec![("comment", "required")]
+        );
+        assert_eq!(
+            report("fake", Some(&"c".repeat(REVIEW_REPORT_COMMENT_MAX + 1))),
+            vec![FieldError::too_long("comment", REVIEW_REPORT_COMMENT_MAX)]
+        );
+    }
+
+    #[test]
+    fn link_detection() {
+        for text in [
+            "Mehr auf https://example.de/angebot",
+            "HTTP://EXAMPLE.COM",
+            "siehe www.handwerker-vergleich",
+            "Besser: tischlerei-konkurrenz.de!",
+            "(meine-firma.com)",
+            "Infos unter sub.domain.info.",
+        ] {
+            assert!(contains_link(text), "{}", text);
+        }
+        for text in [
+            "Pünktlich, sauber, z.B. die Fliesen. Gerne wieder!",
+            "Kosten ca. 3.500 Euro",
+            ".de",
+            "",
+        ] {
+            assert!(!contains_link(text), "{}", text);
+        }
+    }
+
+    #[test]
+    fn blocked_word_detection() {
+        let blocked = vec!["idiot".to_string(), "betrüger".to_string()];
+
+        assert!(contains_blocked_word("So ein Idiot!", &blocked));
+        assert!(contains_blocked_word("BETRÜGER, alle", &blocked));
+        assert!(contains_blocked_word("idiot", &blocked));
+        // Whole words only
+        assert!(!contains_blocked_word("Idiotensicher montiert", &blocked));
+        assert!(!contains_blocked_word("Betrügerisch? Nein.", &blocked));
+        assert!(!contains_blocked_word("Alles bestens", &blocked));
+        assert!(!contains_blocked_word("So ein Idiot!", &[]));
+    }
+
+    #[test]
+    fn rating_source_fields() {
+        let sources = ["places", "csv"];
+        let source = |source: &str, external_id: Option<&str>| {
+            RatingSourceSchema {
+                source: source.to_string(),
+                external_id: external_id.map(str::to_string),
+            }
+            .validate(&sources)
+        };
+
+        assert_eq!(
+            source("places", Some("ChIJ2V-Mo_l1nkcRfZixfUq4DAE")),
+            vec![]
+        );
+        assert_eq!(source(" csv ", None), vec![]);
+        assert_eq!(
+            source("yelp", None),
+            vec![FieldError::invalid_format("source", "one of places, csv")]
+        );
+        assert_eq!(
+            source("places", Some(&"i".repeat(EXTERNAL_ID_MAX + 1))),
+            vec![FieldError::too_long("external_id", EXTERNAL_ID_MAX)]
+        );
+        assert_eq!(
+            codes(
+                &RatingSourceSchema {
+                    source: "places".to_string(),
+                    external_id: None,
+                }
+                .validate(&[])
+            ),
+            vec![("source", "invalid_format")]
+        );
+    }
+
+    #[test]
+    fn profile_report_categories() {
+        let report = |category: &str, comment: Option<&str>| {
+            ProfileReportSchema {
+                category: category.to_string(),
+                comment: comment.map(str::to_string),
+            }
+            .validate()
+        };
+
+        for category in PROFILE_REPORT_CATEGORIES {
+            assert_eq!(report(category, Some("Falsche Adresse")), vec![]);
+        }
+        assert_eq!(report(" impersonation ", None), vec![]);
+        for category in ["", "Spam", "scam"] {
+            assert_eq!(
+                codes(&report(category, None)),
+                vec![("category", "invalid_format")],
+                "{}",
+                category
+            );
+        }
+        assert_eq!(codes(&report("other", None)), vec![("comment", "required")]);
+        assert_eq!(
+            codes(&report("other", Some(""))),
+            vec![("comment", "required")]
+        );
+        assert_eq!(
+            report("spam", Some(&"c".repeat(PROFILE_REPORT_COMMENT_MAX + 1))),
+            vec![FieldError::too_long("comment", PROFILE_REPORT_COMMENT_MAX)]
+        );
+    }
+
+    #[test]
+    fn photo_order_and_details() {
+        let (first, second) = (Uuid::new_v4(), Uuid::new_v4());
+
+        // Whether the list is complete depends on the profile's photos
+        assert_eq!(PhotoOrderSchema { photo_ids: vec![] }.validate(), vec![]);
+        assert_eq!(
+            PhotoOrderSchema {
+                photo_ids: vec![first, second]
+            }
+            .validate(),
+            vec![]
+        );
+        assert_eq!(
+            codes(
+                &PhotoOrderSchema {
+                    photo_ids: vec![first, second, first, first]
+                }
+                .validate()
+            ),
+            vec![("photo_ids[2]", "duplicate"), ("photo_ids[3]", "duplicate")]
+        );
+
+        let details = |caption: &str, alt_text: &str| {
+            PhotoDetailsSchema {
+                caption: Some(caption.to_string()),
+                alt_text: Some(alt_text.to_string()),
+            }
+            .validate()
+        };
+        assert_eq!(PhotoDetailsSchema::default().validate(), vec![]);
+        assert_eq!(
+            details(
+                &"c".repeat(PHOTO_CAPTION_MAX),
+                &"a".repeat(PHOTO_ALT_TEXT_MAX)
+            ),
+            vec![]
+        );
+        assert_eq!(
+            details(
+                &"c".repeat(PHOTO_CAPTION_MAX + 1),
+                &"a".repeat(PHOTO_ALT_TEXT_MAX + 1)
+            ),
+            vec![
+                FieldError::too_long("caption", PHOTO_CAPTION_MAX),
+                FieldError::too_long("alt_text", PHOTO_ALT_TEXT_MAX)
+            ]
+        );
+    }
+
+    #[test]
+    fn projects() {
+        let projects = |projects: serde_json::Value| {
+            parse::<ProjectsSchema>(json!({ "projects": projects })).validate()
+        };
+        let (project, photo) = (Uuid::new_v4(), Uuid::new_v4());
+
+        assert_eq!(projects(json!([])), vec![]);
+        assert_eq!(
+            projects(json!([
+                { "id": project, "title": "Altbausanierung", "photo_ids": [photo] },
+                { "title": "Dachausbau", "description": "Mit Gauben" }
+            ])),
+            vec![]
+        );
+        assert_eq!(
+            codes(&projects(json!([
+                { "id": project, "title": " ", "photo_ids": [photo] },
+                { "id": project, "title": "Dachausbau", "photo_ids": [Uuid::new_v4(), photo] }
+            ]))),
+            vec![
+                ("projects[0].title", "required"),
+                ("projects[1].id", "duplicate"),
+                ("projects[1].photo_ids[1]", "duplicate")
+            ]
+        );
+        assert_eq!(
+            projects(json!([{
+                "title": "t".repeat(PROJECT_TITLE_MAX + 1),
+                "description": "d".repeat(PROJECT_DESCRIPTION_MAX + 1)
+            }])),
+            vec![
+                FieldError::too_long("projects[0].title", PROJECT_TITLE_MAX),
+                FieldError::too_long("projects[0].description", PROJECT_DESCRIPTION_MAX)
+            ]
+        );
+
+        let too_many: Vec<serde_json::Value> = (0..=PROJECTS_MAX)
+            .map(|i| json!({ "title": format!("Projekt {}", i) }))
+            .collect();
+        assert_eq!(
+            codes(&projects(json!(too_many))),
+            vec![("projects", "too_many")]
+        );
+    }
+}